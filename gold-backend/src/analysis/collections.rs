@@ -0,0 +1,155 @@
+//! Lists and maps. `List(Int)` and `Map(String, Int)` are written the same way
+//! as a type and to make an empty one, and the functions that use them take a
+//! collection of any type, so calls to them are checked here instead of
+//! against a signature. So are `map`, `filter` and `fold`, which take a
+//! function of whatever the list holds.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use gold_frontend::error::TypeError;
+use gold_frontend::frontend::{Expr, Type};
+use gold_standard::load::{CONTAINS_SYMBOL, FILTER_SYMBOL, FOLD_SYMBOL, GET_SYMBOL, INSERT_SYMBOL, KEYS_SYMBOL, LEN_SYMBOL, LIST_SYMBOL, MAP_FN_SYMBOL, MAP_SYMBOL, PUSH_SYMBOL, REMOVE_SYMBOL};
+
+use super::lower::{Analyzer, FuncSig, Lower, VarSig};
+
+const FUNCTIONS: [&str; 12] = [LIST_SYMBOL, MAP_SYMBOL, INSERT_SYMBOL, GET_SYMBOL, CONTAINS_SYMBOL, REMOVE_SYMBOL, LEN_SYMBOL, KEYS_SYMBOL, PUSH_SYMBOL, MAP_FN_SYMBOL, FILTER_SYMBOL, FOLD_SYMBOL];
+
+/// Whether a call is to one of these, rather than to something the program
+/// declared with the same name.
+pub fn is_collection_call(name: &str, func_ref: &HashMap<String, FuncSig>) -> bool {
+    FUNCTIONS.contains(&name) && !func_ref.contains_key(name)
+}
+
+/// Whether values of a type can be the keys of a map.
+pub fn is_key_type(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::String | Type::Bool)
+}
+
+/// The type written as what a `List(...)` or `Map(...)` holds.
+pub fn written_type(expr: &Expr) -> Option<Type> {
+    match expr {
+        Expr::Var(name, _) => Some(Type::from(name.to_owned())),
+        Expr::Call(name, args, _, _) => collection_type(name, args),
+        _ => None,
+    }
+}
+
+fn collection_type(name: &str, args: &[Expr]) -> Option<Type> {
+    match (name, args) {
+        (LIST_SYMBOL, [item]) => Some(Type::List(Box::new(written_type(item)?))),
+        (MAP_SYMBOL, [key, value]) => Some(Type::Map(Box::new(written_type(key)?), Box::new(written_type(value)?))),
+        _ => None,
+    }
+}
+
+/// The type of a call to one of these.
+pub fn call_type(name: &str, args: &[Expr], func_ref: &HashMap<String, FuncSig>, var_ref: &HashMap<String, VarSig>) -> Type {
+    let collection = || args.first().map(|arg| arg.get_type(func_ref, var_ref));
+    match name {
+        LIST_SYMBOL | MAP_SYMBOL => collection_type(name, args),
+        GET_SYMBOL => match collection() {
+            Some(Type::List(item)) => Some(*item),
+            Some(Type::Map(_, value)) => Some(*value),
+            _ => None,
+        },
+        KEYS_SYMBOL => match collection() {
+            Some(Type::Map(key, _)) => Some(Type::List(key)),
+            _ => None,
+        },
+        MAP_FN_SYMBOL => match args.get(1).map(|arg| arg.get_type(func_ref, var_ref)) {
+            Some(Type::Function(_, returns)) => Some(Type::List(returns)),
+            _ => None,
+        },
+        FILTER_SYMBOL => collection(),
+        FOLD_SYMBOL => args.get(1).map(|arg| arg.get_type(func_ref, var_ref)),
+        CONTAINS_SYMBOL | REMOVE_SYMBOL => Some(Type::Bool),
+        LEN_SYMBOL => Some(Type::Int),
+        _ => None,
+    }.unwrap_or(Type::Void)
+}
+
+/// Checks a call to one of these.
+pub fn check_call(typechecker: &mut Analyzer, name: &str, args: &[Expr], arg_loc: &Range<usize>, var_ref: &HashMap<String, VarSig>) {
+    if name == LIST_SYMBOL || name == MAP_SYMBOL {
+        return check_constructor(typechecker, name, args, arg_loc);
+    }
+    for arg in args {
+        arg.typecheck(typechecker);
+    }
+    let count = match name {
+        INSERT_SYMBOL | FOLD_SYMBOL => 3,
+        LEN_SYMBOL | KEYS_SYMBOL => 1,
+        _ => 2,
+    };
+    if args.len() != count {
+        typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), count, args.len()));
+        return;
+    }
+    // Undefined variables have already been reported
+    let undefined = |arg: &Expr| matches!(arg, Expr::Var(var, _) if !var_ref.contains_key(var));
+    if undefined(&args[0]) {
+        return;
+    }
+    let collection = args[0].get_type(&typechecker.functions, var_ref);
+    // What the values after the collection have to be
+    let expected = match (name, &collection) {
+        (GET_SYMBOL, Type::List(_)) => vec![Type::Int],
+        (GET_SYMBOL | CONTAINS_SYMBOL | REMOVE_SYMBOL, Type::Map(key, _)) => vec![*key.clone()],
+        (INSERT_SYMBOL, Type::Map(key, value)) => vec![*key.clone(), *value.clone()],
+        (PUSH_SYMBOL, Type::List(item)) => vec![*item.clone()],
+        (LEN_SYMBOL, Type::List(_) | Type::Map(..)) | (KEYS_SYMBOL, Type::Map(..)) => Vec::new(),
+        // The function can give back anything but nothing
+        (MAP_FN_SYMBOL, Type::List(item)) => {
+            let returns = match args[1].get_type(&typechecker.functions, var_ref) {
+                Type::Function(_, returns) if *returns != Type::Void => returns,
+                _ => item.clone(),
+            };
+            vec![Type::Function(vec![*item.clone()], returns)]
+        }
+        (FILTER_SYMBOL, Type::List(item)) => vec![Type::Function(vec![*item.clone()], Box::new(Type::Bool))],
+        // The function is given what it gave back last, starting with the
+        // initial value, along with each item
+        (FOLD_SYMBOL, Type::List(item)) => {
+            let initial = args[1].get_type(&typechecker.functions, var_ref);
+            vec![initial.clone(), Type::Function(vec![initial.clone(), *item.clone()], Box::new(initial))]
+        }
+        _ => {
+            let takes = match name {
+                GET_SYMBOL | LEN_SYMBOL => "list or a map",
+                PUSH_SYMBOL | MAP_FN_SYMBOL | FILTER_SYMBOL | FOLD_SYMBOL => "list",
+                _ => "map",
+            };
+            typechecker.report(TypeError::NotACollection(args[0].expression_range(), name.to_owned(), takes, collection));
+            return;
+        }
+    };
+    for (arg, ty) in args[1..].iter().zip(expected) {
+        let arg_type = arg.get_type(&typechecker.functions, var_ref);
+        if arg_type != ty && !undefined(arg) {
+            typechecker.report(TypeError::IncorrectTypeValueForArgument(Range::default(), arg.expression_range(), ty, arg_type));
+        }
+    }
+}
+
+// The arguments are types rather than values
+fn check_constructor(typechecker: &mut Analyzer, name: &str, args: &[Expr], arg_loc: &Range<usize>) {
+    let count = if name == LIST_SYMBOL { 1 } else { 2 };
+    if args.len() != count {
+        typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), count, args.len()));
+        return;
+    }
+    for arg in args {
+        let loc = arg.expression_range();
+        match written_type(arg) {
+            Some(ty) => typechecker.check_type(&ty, &loc),
+            None => typechecker.report(TypeError::UnknownType(typechecker.source[loc.clone()].to_owned(), loc)),
+        }
+    }
+    if name == MAP_SYMBOL {
+        match written_type(&args[0]) {
+            Some(key) if !is_key_type(&key) => typechecker.report(TypeError::InvalidKeyType(args[0].expression_range(), key)),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! The formats `printf` takes, which say where each value goes with `%d` for
+//! an `Int`, `%f` or `%.2f` for a `Float`, `%s` for a `String` and `%b` for a
+//! `Bool`. `%%` is a percent sign.
+
+use gold_frontend::frontend::Type;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Piece {
+    Text(String),
+    /// A value of the type, and for a float the number of digits after the
+    /// point when they're given
+    Value(Type, Option<i64>),
+}
+
+/// Splits a format into its text and the values that go in between, or
+/// explains what's wrong with it.
+pub fn parse(format: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+        let mut digits = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut number = String::new();
+            while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                number.push(*digit);
+                chars.next();
+            }
+            digits = Some(number.parse().map_err(|_| "`%.` has to be followed by a number of digits".to_owned())?);
+        }
+        let ty = match (chars.next(), digits) {
+            (Some('%'), None) => {
+                text.push('%');
+                continue;
+            }
+            (Some('d'), None) => Type::Int,
+            (Some('f'), _) => Type::Float,
+            (Some('s'), None) => Type::String,
+            (Some('b'), None) => Type::Bool,
+            (Some(c), Some(_)) => return Err(format!("only `%f` can have digits, not `%{}`", c)),
+            (Some(c), None) => return Err(format!("`%{}` isn't a placeholder, which are `%d`, `%f`, `%s` and `%b`", c)),
+            (None, _) => return Err("the format ends in the middle of a placeholder".to_owned()),
+        };
+        if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+        }
+        pieces.push(Piece::Value(ty, digits));
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// The types of the values a format takes, in order.
+pub fn value_types(pieces: &[Piece]) -> Vec<Type> {
+    pieces.iter().filter_map(|piece| match piece {
+        Piece::Value(ty, _) => Some(ty.clone()),
+        Piece::Text(_) => None,
+    }).collect()
+}
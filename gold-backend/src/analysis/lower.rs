@@ -1,14 +1,101 @@
 use std::collections::HashMap;
 use std::ops::Range;
 
-use gold_frontend::error::{report_type_error, TypeError};
-use gold_frontend::frontend::{Expr, Type};
-use gold_standard::load::{PRINT_SYMBOL, PRINTLN_SYMBOL, PRINT_INT_SYMBOL, PRINT_FLOAT_SYMBOL};
+use gold_frontend::error::TypeError;
+use gold_frontend::frontend::{Expr, Parameter, Type};
+use gold_frontend::module::Module;
+use gold_standard::ffi;
+use gold_standard::load::{ABS_SYMBOL, ACOS_SYMBOL, ASIN_SYMBOL, ASSERT_EQ_SYMBOL, ASSERT_SYMBOL, ATAN2_SYMBOL, ATAN_SYMBOL, CEIL_SYMBOL, CLAMP_SYMBOL, CLOSE_FILE_SYMBOL, COS_SYMBOL, EPRINT_SYMBOL, EPRINTLN_SYMBOL, EXP_SYMBOL, FLOOR_SYMBOL, GCD_SYMBOL, IO_ERROR_SYMBOL, LN_SYMBOL, LOG10_SYMBOL, LOG2_SYMBOL, MAX_SYMBOL, MIN_SYMBOL, OPEN_FILE_SYMBOL, PRINT_SYMBOL, PRINTF_SYMBOL, PRINTLN_SYMBOL, PRINT_INT_SYMBOL, PRINT_FLOAT_SYMBOL, RANDOM_FLOAT_SYMBOL, RANDOM_INT_SYMBOL, READ_FILE_SYMBOL, READ_INT_SYMBOL, READ_LINE_SYMBOL, ROUND_SYMBOL, SEED_RANDOM_SYMBOL, SIN_SYMBOL, SQRT_SYMBOL, TAN_SYMBOL, TO_FLOAT_SYMBOL, TO_INT_SYMBOL, WRITE_FILE_SYMBOL};
 
+use super::{collections, format};
+
+#[derive(Clone)]
 pub struct FuncSig {
     pub return_type: Type,
     pub param_types: Vec<(Type, Range<usize>)>,
     pub scope_index: usize,
+    // A structure is registered as its constructor, which takes the fields in
+    // the order they are declared. Empty for ordinary functions.
+    pub field_names: Vec<String>,
+    // Enum variants are registered as constructors too, tagged with their
+    // position in the enum.
+    pub tag: Option<usize>,
+    // The C library an external function comes from
+    pub library: Option<String>,
+    // Whether this is a function of the runtime rather than one a program or
+    // the application declared
+    pub runtime: bool,
+}
+
+impl FuncSig {
+    fn new(return_type: Type, params: &[(Parameter, Range<usize>)], scope_index: usize) -> Self {
+        Self {
+            return_type,
+            param_types: params.iter().map(|p| (p.0.typename.clone(), p.1.to_owned())).collect::<Vec<(Type, Range<usize>)>>(),
+            scope_index,
+            field_names: Vec::new(),
+            tag: None,
+            library: None,
+            runtime: false,
+        }
+    }
+
+    /// A function of the runtime, which isn't declared anywhere in the source.
+    fn builtin(params: &[Type], return_type: Type) -> Self {
+        Self {
+            return_type,
+            param_types: params.iter().map(|ty| (ty.clone(), Range::default())).collect(),
+            scope_index: 0,
+            field_names: Vec::new(),
+            tag: None,
+            library: None,
+            runtime: true,
+        }
+    }
+
+    /// Whether calling this builds a structure rather than calling a function.
+    pub fn is_constructor(&self, name: &str) -> bool {
+        matches!(&self.return_type, Type::Named(record) if record == name)
+    }
+
+    /// Whether this is a variant that doesn't carry any values, which can be
+    /// written without parentheses.
+    pub fn is_unit_variant(&self) -> bool {
+        self.tag.is_some() && self.param_types.is_empty()
+    }
+
+    /// The type of the function when it's used as a value, which only
+    /// functions written in Gold or given by the application can be.
+    pub fn value_type(&self, name: &str) -> Option<Type> {
+        if self.runtime || self.library.is_some() || self.tag.is_some() || self.is_constructor(name) {
+            return None;
+        }
+        let params = self.param_types.iter().map(|(ty, _)| ty.clone()).collect();
+        Some(Type::Function(params, Box::new(self.return_type.clone())))
+    }
+
+    /// The type of a field along with where it was declared.
+    pub fn field(&self, name: &str) -> Option<&(Type, Range<usize>)> {
+        let index = self.field_names.iter().position(|field| field == name)?;
+        self.param_types.get(index)
+    }
+}
+
+/// The type of `record.field`, if `record` is a structure that has the field.
+pub fn field_type(func_ref: &HashMap<String, FuncSig>, record: &Type, field: &str) -> Option<Type> {
+    match record {
+        Type::Named(name) => func_ref.get(name)
+            .filter(|sig| sig.is_constructor(name))
+            .and_then(|sig| sig.field(field))
+            .map(|(ty, _)| ty.clone()),
+        _ => None,
+    }
+}
+
+/// The name the signature of an anonymous function is kept under, which no
+/// declared function can have.
+pub fn lambda_name(loc: &Range<usize>) -> String {
+    format!("fn@{}", loc.start)
 }
 
 #[derive(Clone)]
@@ -18,15 +105,40 @@ pub struct VarSig {
 
 pub struct VariableRegistry {
     pub scopes: Vec<HashMap<String, VarSig>>,
+    // The variables of blocks that have ended, by the scope they were in
+    ended: HashMap<usize, HashMap<String, VarSig>>,
 }
 
 impl VariableRegistry {
     fn new() -> Self {
         Self {
             scopes: vec![HashMap::new()],
+            ended: HashMap::new(),
         }
     }
 
+    /// Starts a block whose variables can only be used inside it.
+    pub fn begin_block(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Ends the innermost block. Its variables are kept with those of the
+    /// scope around it, for `variables_of`.
+    pub fn end_block(&mut self) {
+        let index = self.scopes.len() - 1;
+        let mut ended = self.ended.remove(&index).unwrap_or_default();
+        ended.extend(self.scopes.pop().unwrap());
+        self.ended.entry(index - 1).or_default().extend(ended);
+    }
+
+    /// Every variable of a function, including those of its blocks, by the
+    /// scope index of the function.
+    pub fn variables_of(&self, index: usize) -> HashMap<String, VarSig> {
+        let mut variables = self.ended.get(&index).cloned().unwrap_or_default();
+        variables.extend(self.scopes[index].clone());
+        variables
+    }
+
     fn unify(&self) -> HashMap<String, VarSig> {
         let mut unified_scope = HashMap::new();
 
@@ -56,40 +168,211 @@ pub struct Analyzer {
     pub variables: VariableRegistry,
     pub source: String,
     pub filename: String,
-    pub errors: usize,
+    pub errors: Vec<TypeError>,
+    // The variants of every enum, in tag order
+    pub enums: HashMap<String, Vec<String>>,
+    // How many loops the code being checked is in
+    pub loops: usize,
+    // The type of every variable by where it's declared, for declarations
+    // the optimizer has taken the value away from
+    pub declarations: HashMap<Range<usize>, Type>,
 }
 
 impl Analyzer {
     pub fn new(src: String, filename: String) -> Self {
         let mut functions = HashMap::new();
-        functions.insert(PRINT_SYMBOL.to_owned(), FuncSig {
-            return_type: Type::Int,
-            param_types: vec![(Type::String, Range::default())],
-            scope_index: 0,
-        });
-        functions.insert(PRINTLN_SYMBOL.to_owned(), FuncSig {
-            return_type: Type::Int,
-            param_types: vec![(Type::String, Range::default())],
-            scope_index: 0,
-        });
-        functions.insert(PRINT_INT_SYMBOL.to_owned(), FuncSig {
-            return_type: Type::Void,
-            param_types: vec![(Type::Int, Range::default())],
-            scope_index: 0,
-        });
-        functions.insert(PRINT_FLOAT_SYMBOL.to_owned(), FuncSig {
-            return_type: Type::Void,
-            param_types: vec![(Type::Float, Range::default())],
-            scope_index: 0,
-        });
+        functions.insert(PRINT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String], Type::Int));
+        functions.insert(PRINTLN_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String], Type::Int));
+        functions.insert(PRINT_INT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int], Type::Void));
+        functions.insert(PRINT_FLOAT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Float], Type::Void));
+        functions.insert(EPRINT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String], Type::Int));
+        functions.insert(EPRINTLN_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String], Type::Int));
+        // `printf` takes the values its format says it does, so it's checked
+        // on its own instead
+        functions.insert(PRINTF_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String], Type::Int));
+        functions.insert(READ_LINE_SYMBOL.to_owned(), FuncSig::builtin(&[], Type::String));
+        functions.insert(READ_INT_SYMBOL.to_owned(), FuncSig::builtin(&[], Type::Int));
+        functions.insert(OPEN_FILE_SYMBOL.to_owned(), FuncSig::builtin(&[Type::String, Type::String], Type::Int));
+        functions.insert(READ_FILE_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int], Type::String));
+        functions.insert(WRITE_FILE_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int, Type::String], Type::Int));
+        functions.insert(CLOSE_FILE_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int], Type::Int));
+        functions.insert(IO_ERROR_SYMBOL.to_owned(), FuncSig::builtin(&[], Type::String));
+        // `assert_eq` takes two values of any type it can compare, so it's
+        // checked on its own instead
+        functions.insert(ASSERT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Bool], Type::Void));
+        // A `Number` is either an `Int` or a `Float`, the same one for all
+        // the values and what's given back
+        functions.insert(ABS_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Number], Type::Number));
+        functions.insert(MIN_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Number, Type::Number], Type::Number));
+        functions.insert(MAX_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Number, Type::Number], Type::Number));
+        functions.insert(CLAMP_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Number, Type::Number, Type::Number], Type::Number));
+        for name in [SQRT_SYMBOL, FLOOR_SYMBOL, CEIL_SYMBOL, ROUND_SYMBOL, SIN_SYMBOL, COS_SYMBOL, TAN_SYMBOL, ASIN_SYMBOL, ACOS_SYMBOL, ATAN_SYMBOL, EXP_SYMBOL, LN_SYMBOL, LOG10_SYMBOL, LOG2_SYMBOL] {
+            functions.insert(name.to_owned(), FuncSig::builtin(&[Type::Float], Type::Float));
+        }
+        functions.insert(ATAN2_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Float, Type::Float], Type::Float));
+        functions.insert(GCD_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int, Type::Int], Type::Int));
+        functions.insert(TO_FLOAT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int], Type::Float));
+        functions.insert(TO_INT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Float], Type::Int));
+        functions.insert(SEED_RANDOM_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int], Type::Void));
+        functions.insert(RANDOM_INT_SYMBOL.to_owned(), FuncSig::builtin(&[Type::Int, Type::Int], Type::Int));
+        functions.insert(RANDOM_FLOAT_SYMBOL.to_owned(), FuncSig::builtin(&[], Type::Float));
         Self {
             functions,
             variables: VariableRegistry::new(),
             source: src,
-            filename: filename,
-            errors: 0,
+            filename,
+            errors: Vec::new(),
+            enums: HashMap::new(),
+            loops: 0,
+            declarations: HashMap::new(),
+        }
+    }
+
+    /// Registers the signature of every function, structure and enum up front,
+    /// so they can be used no matter what order they are written in.
+    pub fn declare_functions(&mut self, functions: &[Expr]) {
+        for function in functions {
+            match function {
+                Expr::Function(name, params, ty, _, _) => {
+                    self.functions.insert(name.to_owned(), FuncSig::new(ty.clone(), params, 0));
+                }
+                Expr::Test(name, _, _) => {
+                    self.functions.insert(name.to_owned(), FuncSig::new(Type::Void, &[], 0));
+                }
+                Expr::External(name, params, ret, library, _) => {
+                    // Gold strings are already nul terminated, so on this side a CString is a String
+                    let params = params.iter()
+                        .map(|(param, loc)| (Parameter { name: param.name.to_owned(), typename: from_c(&param.typename) }, loc.to_owned()))
+                        .collect::<Vec<(Parameter, Range<usize>)>>();
+                    let mut sig = FuncSig::new(from_c(ret), &params, 0);
+                    sig.library = Some(library.to_owned());
+                    self.functions.insert(name.to_owned(), sig);
+                }
+                Expr::Structure(name, fields, _) => {
+                    let mut sig = FuncSig::new(Type::Named(name.to_owned()), fields, 0);
+                    sig.field_names = fields.iter().map(|(field, _)| field.name.to_owned()).collect();
+                    self.functions.insert(name.to_owned(), sig);
+                }
+                Expr::Enumeration(name, variants, _) => {
+                    for (tag, variant) in variants.iter().enumerate() {
+                        let mut sig = FuncSig::new(Type::Named(name.to_owned()), &variant.fields, 0);
+                        sig.field_names = variant.fields.iter().map(|(field, _)| field.name.to_owned()).collect();
+                        sig.tag = Some(tag);
+                        self.functions.insert(variant.name.to_owned(), sig);
+                    }
+                    self.enums.insert(name.to_owned(), variants.iter().map(|variant| variant.name.to_owned()).collect());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Starts the analysis of one of the loaded modules.
+    pub fn for_module(module: &Module) -> Self {
+        Analyzer::new(module.source.to_owned(), module.filename.to_owned())
+    }
+
+    /// Typechecks one of the loaded modules against what the modules it uses
+    /// declare. Errors found while loading it are left on the module.
+    pub fn check_module(&mut self, modules: &[Module], index: usize) {
+        let module = &modules[index];
+        for &import in &module.imports {
+            self.declare_module(&modules[import].name, &modules[import].program.functions);
+        }
+        self.declare_functions(&module.program.functions);
+        for item in &module.program.functions {
+            item.typecheck(self);
         }
     }
+
+    /// Registers a function provided by the application Gold is embedded in,
+    /// which is called like any other function.
+    pub fn declare_host_function(&mut self, name: &str, params: &[Type], return_type: Type) {
+        let mut sig = FuncSig::builtin(params, return_type);
+        sig.runtime = false;
+        self.functions.insert(name.to_owned(), sig);
+    }
+
+    /// Registers what a used module declares. Its functions are called as
+    /// `module.function`, while structures and enums keep their own names
+    /// since a type in a comment header can't be qualified. Locations point
+    /// into the other file, so they are dropped.
+    pub fn declare_module(&mut self, module: &str, items: &[Expr]) {
+        let mut imported = Analyzer::new(String::new(), String::new());
+        imported.functions.clear();
+        imported.declare_functions(items);
+        for (name, mut sig) in imported.functions {
+            for (_, loc) in &mut sig.param_types {
+                *loc = Range::default();
+            }
+            if sig.is_constructor(&name) || sig.tag.is_some() {
+                self.functions.insert(name, sig);
+            } else {
+                self.functions.insert([module, name.as_str()].join("."), sig);
+            }
+        }
+        self.enums.extend(imported.enums);
+    }
+
+    /// Reports declared types that aren't builtin types, structures or enums.
+    fn check_declared_types(&mut self, params: &[(Parameter, Range<usize>)]) {
+        for (param, loc) in params {
+            self.check_type(&param.typename, loc);
+        }
+    }
+
+    /// Reports the condition of an `if`, `elif` or `while` that isn't a `Bool`.
+    fn check_condition(&mut self, cond: &Expr, var_ref: &HashMap<String, VarSig>) {
+        let ty = cond.get_type(&self.functions, var_ref);
+        // Undefined variables have already been reported
+        let undefined = matches!(cond, Expr::Var(var, _) if !var_ref.contains_key(var));
+        if ty != Type::Bool && !undefined {
+            self.report(TypeError::NotACondition(cond.expression_range(), ty));
+        }
+    }
+
+    /// Reports a written type that isn't a builtin type, structure or enum,
+    /// along with what a list or map holds.
+    pub(super) fn check_type(&mut self, ty: &Type, loc: &Range<usize>) {
+        match ty {
+            Type::CString => self.report(TypeError::CStringOutsideExternal(loc.to_owned())),
+            Type::Named(name) => {
+                let known = self.enums.contains_key(name)
+                    || self.functions.get(name).map(|sig| sig.is_constructor(name)).unwrap_or(false);
+                if !known {
+                    self.report(TypeError::UnknownType(name.to_owned(), loc.to_owned()));
+                }
+            }
+            Type::List(item) => self.check_type(item, loc),
+            Type::Function(params, returns) => {
+                for param in params {
+                    self.check_type(param, loc);
+                }
+                self.check_type(returns, loc);
+            }
+            Type::Map(key, value) => {
+                if !collections::is_key_type(key) {
+                    self.report(TypeError::InvalidKeyType(loc.to_owned(), *key.clone()));
+                }
+                self.check_type(key, loc);
+                self.check_type(value, loc);
+            }
+            _ => {}
+        }
+    }
+
+    /// Records a type error. Analysis keeps going so every error in the file
+    /// can be reported at once.
+    pub fn report(&mut self, error: TypeError) {
+        self.errors.push(error);
+    }
+}
+
+fn from_c(ty: &Type) -> Type {
+    match ty {
+        Type::CString => Type::String,
+        ty => ty.clone(),
+    }
 }
 
 pub trait Lower {
@@ -97,57 +380,116 @@ pub trait Lower {
     fn typecheck(&self, typechecker: &mut Analyzer);
 }
 
+fn block_type(block: &Expr, func_ref: &HashMap<String, FuncSig>, var_ref: &HashMap<String, VarSig>) -> Type {
+    match block {
+        Expr::Block(stmts, _) => match stmts.last() {
+            Some(stmt) => stmt.get_type(func_ref, var_ref),
+            None => Type::Void,
+        },
+        _ => unreachable!()
+    }
+}
+
+/// What a `Number` stands for in a call, which is the type of the first value
+/// given for one when that's an `Int` or a `Float`.
+fn number_type(func: &FuncSig, args: &[Expr], func_ref: &HashMap<String, FuncSig>, var_ref: &HashMap<String, VarSig>) -> Type {
+    func.param_types.iter().zip(args)
+        .find(|((ty, _), _)| *ty == Type::Number)
+        .map(|(_, arg)| arg.get_type(func_ref, var_ref))
+        .filter(|ty| matches!(ty, Type::Int | Type::Float))
+        .unwrap_or(Type::Number)
+}
+
 impl Lower for Expr {
     fn get_type(&self, func_ref: &HashMap<String, FuncSig>, var_ref: &HashMap<String, VarSig>) -> Type {
         match self {
             Expr::NoExpr => unreachable!(),
             Expr::Number(_, _) => Type::Int,
+            Expr::Bool(_, _) => Type::Bool,
             Expr::String(_, _) => Type::String,
-            Expr::Parameter(param, _) => param.typename,
-            Expr::Function(_, _, ty, _, _) => *ty,
-            Expr::Else(block, _) => {
-                match block.as_ref() {
-                    Expr::Block(stmts, _) => stmts.last().unwrap().get_type(func_ref, var_ref),
-                    _ => unreachable!()
-                }
-            }
-            Expr::Elif(_, block, _) => {
-                match block.as_ref() {
-                    Expr::Block(stmts, _) => stmts.last().unwrap().get_type(func_ref, var_ref),
-                    _ => unreachable!()
+            Expr::Interpolation(_, _) => Type::String,
+            Expr::Parameter(param, _) => param.typename.clone(),
+            Expr::Function(_, _, ty, _, _) => ty.clone(),
+            Expr::Test(_, _, _) => Type::Void,
+            Expr::Structure(name, _, _) | Expr::Enumeration(name, _, _) => Type::Named(name.to_owned()),
+            Expr::Import(_, _) => Type::Void,
+            Expr::External(_, _, ty, _, _) => from_c(ty),
+            Expr::Else(block, _) => block_type(block, func_ref, var_ref),
+            Expr::Elif(_, block, _) => block_type(block, func_ref, var_ref),
+            Expr::If(_, block, _, _, _) => block_type(block, func_ref, var_ref),
+            Expr::While(_, block, _) => block_type(block, func_ref, var_ref),
+            Expr::For(..) | Expr::Break(_) | Expr::Continue(_) => Type::Void,
+            // What a for loop over it goes through
+            Expr::Range(..) => Type::Int,
+            // The bindings of the first arm hide any variables of the same name
+            Expr::Match(_, arms, _) => match arms.first() {
+                Some(arm) => {
+                    let mut scope = var_ref.to_owned();
+                    if let Some(fields) = arm.variant.as_ref().and_then(|variant| func_ref.get(variant)) {
+                        for ((binding, _), (ty, _)) in arm.bindings.iter().zip(&fields.param_types) {
+                            scope.insert(binding.to_owned(), VarSig { ty: ty.clone() });
+                        }
+                    }
+                    block_type(&arm.body, func_ref, &scope)
                 }
+                None => Type::Void,
+            },
+            Expr::Call(name, _, _, _) if var_ref.contains_key(name) => match &var_ref[name].ty {
+                Type::Function(_, returns) => *returns.clone(),
+                _ => Type::Void,
+            },
+            Expr::Call(name, args, _, _) if collections::is_collection_call(name, func_ref) => {
+                collections::call_type(name, args, func_ref, var_ref)
             }
-            Expr::If(_, block, _, _, _) => {
-                match block.as_ref() {
-                    Expr::Block(stmts, _) => stmts.last().unwrap().get_type(func_ref, var_ref),
-                    _ => unreachable!()
+            Expr::Call(name, args, _, _) => {
+                match func_ref.get(name) {
+                    Some(probably_correct_func) => match &probably_correct_func.return_type {
+                        Type::Number => number_type(probably_correct_func, args, func_ref, var_ref),
+                        ty => ty.clone(),
+                    },
+                    None => Type::Void,
                 }
             }
-            Expr::While(_, block, _) => {
-                match block.as_ref() {
-                    Expr::Block(stmts, _) => stmts.last().unwrap().get_type(func_ref, var_ref),
-                    _ => unreachable!()
+            Expr::Lambda(params, _, loc) => match func_ref.get(&lambda_name(loc)) {
+                Some(sig) => {
+                    let params = params.iter().map(|(param, _)| param.typename.clone()).collect();
+                    Type::Function(params, Box::new(sig.return_type.clone()))
                 }
+                None => Type::Void,
+            },
+            Expr::List(values, _) => {
+                let item = match values.first() {
+                    Some(value) => value.get_type(func_ref, var_ref),
+                    None => Type::Void,
+                };
+                Type::List(Box::new(item))
             }
-            Expr::Call(name, _, _, _) => {
-                let probably_correct_func = func_ref.get(name).unwrap();
-                probably_correct_func.return_type
-            }
-            Expr::List(values, _) => values.first().unwrap().get_type(func_ref, var_ref),
             Expr::Equality(_, _) => Type::Bool,
             Expr::NotEqual(_, _) => Type::Bool,
             Expr::GreaterThan(_, _) => Type::Bool,
             Expr::LessThan(_, _) => Type::Bool,
             Expr::GreaterThanEqual(_, _) => Type::Bool,
             Expr::LessThanEqual(_, _) => Type::Bool,
-            Expr::Addition(_, _) => Type::Number,
-            Expr::Subtraction(_, _) => Type::Number,
-            Expr::Multiplication(_, _) => Type::Number,
-            Expr::Division(_, _) => Type::Number,
-            Expr::Power(_, _) => Type::Number,
-            Expr::Var(ident, _) => todo!(),
+            Expr::Addition(lhs, _) => lhs.get_type(func_ref, var_ref),
+            Expr::Subtraction(lhs, _) => lhs.get_type(func_ref, var_ref),
+            Expr::Multiplication(lhs, _) => lhs.get_type(func_ref, var_ref),
+            Expr::Division(lhs, _) => lhs.get_type(func_ref, var_ref),
+            Expr::Power(lhs, _) => lhs.get_type(func_ref, var_ref),
+            Expr::Negate(expr, _) => expr.get_type(func_ref, var_ref),
+            Expr::Var(ident, _) => {
+                match (var_ref.get(ident), func_ref.get(ident)) {
+                    (Some(var), _) => var.ty.clone(),
+                    (None, Some(sig)) if sig.is_unit_variant() => sig.return_type.clone(),
+                    (None, Some(sig)) => sig.value_type(ident).unwrap_or(Type::Void),
+                    _ => Type::Void,
+                }
+            }
             Expr::Assign(_, e, _) => e.get_type(func_ref, var_ref),
             Expr::Reassign(_, e, _) => e.get_type(func_ref, var_ref),
+            Expr::Field(record, field, _) => {
+                field_type(func_ref, &record.get_type(func_ref, var_ref), field).unwrap_or(Type::Void)
+            }
+            Expr::ReassignField(_, e, _) => e.get_type(func_ref, var_ref),
             Expr::Block(_, _) => Type::Void,
         }
     }
@@ -157,146 +499,439 @@ impl Lower for Expr {
         match self {
             Expr::NoExpr => unreachable!(),
             Expr::Var(sym, err) => {
-                match unified_theory_of_shit.get(sym) {
-                    Some(var) => {}
-                    None => {
-                        report_type_error(TypeError::NotDefined(err.to_owned()), typechecker.filename.as_str(), typechecker.source.as_str());
-                    }
+                // Variants without values and functions can be used by name
+                let value = typechecker.functions.get(sym)
+                    .map(|sig| sig.is_unit_variant() || sig.value_type(sym).is_some())
+                    .unwrap_or(false);
+                if !unified_theory_of_shit.contains_key(sym) && !value {
+                    typechecker.report(TypeError::NotDefined(err.to_owned()));
                 }
             }
-            Expr::Assign(name, expr, _) => {
-                let fun = &typechecker.functions;
-                typechecker.variables.push(name.to_owned(), VarSig { ty: expr.get_type(fun, &unified_theory_of_shit) });
+            Expr::Assign(name, expr, loc) => {
                 expr.typecheck(typechecker);
+                let ty = expr.get_type(&typechecker.functions, &unified_theory_of_shit);
+                typechecker.declarations.insert(loc.to_owned(), ty.clone());
+                typechecker.variables.push(name.to_owned(), VarSig { ty });
             }
             Expr::Reassign(name, expr, err) => {
-                match typechecker.variables.unify().get(name) {
-                    Some(var) => { expr.typecheck(typechecker); }
-                    None => {
-                        report_type_error(TypeError::NotDefined(err.to_owned()), typechecker.filename.as_str(), typechecker.source.as_str());
-                    }
+                if unified_theory_of_shit.contains_key(name) {
+                    expr.typecheck(typechecker);
+                } else {
+                    typechecker.report(TypeError::NotDefined(err.to_owned()));
                 }
             }
             Expr::Number(_, _) => {}
+            Expr::Bool(_, _) => {}
             Expr::String(_, _) => {}
-            Expr::Parameter(_, _) => todo!(),
-            Expr::Function(name, params, ty, block, loc) => {
+            Expr::Interpolation(parts, _) => {
+                for part in parts {
+                    part.typecheck(typechecker);
+                    // Undefined variables have already been reported
+                    let undefined = matches!(part, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                    let ty = part.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    if !undefined && !matches!(ty, Type::Int | Type::Float | Type::String | Type::Bool) {
+                        typechecker.report(TypeError::CannotInterpolate(part.expression_range(), ty));
+                    }
+                }
+            }
+            Expr::Parameter(_, _) => {}
+            Expr::Function(name, params, ty, block, name_loc) => {
+                typechecker.check_declared_types(params);
+                typechecker.check_type(ty, name_loc);
                 // Add the function scope
                 typechecker.variables.scopes.push(HashMap::new());
-                typechecker.functions.insert(name.to_owned(), FuncSig {
-                    return_type: *ty,
-                    param_types: params.iter().map(|p| ((*p).0.typename, (*p).1.to_owned())).collect::<Vec<(Type, Range<usize>)>>(),
-                    scope_index: typechecker.variables.scopes.len() - 1,
-                });
+                let scope_index = typechecker.variables.scopes.len() - 1;
+                typechecker.functions.insert(name.to_owned(), FuncSig::new(ty.clone(), params, scope_index));
+                for (param, _) in params {
+                    typechecker.variables.push(param.name.to_owned(), VarSig { ty: param.typename.clone() });
+                }
+                let errors = typechecker.errors.len();
                 block.typecheck(typechecker);
-            }
-            Expr::Block(stmts, _) => {
-                for stmt in stmts {
-                    stmt.typecheck(typechecker)
+
+                // The last statement is the return value
+                if *ty != Type::Void {
+                    let scope = typechecker.variables.unify();
+                    let (got, loc) = match block.as_ref() {
+                        Expr::Block(stmts, loc) => match stmts.last() {
+                            Some(stmt) => (stmt.get_type(&typechecker.functions, &scope), stmt.expression_range()),
+                            None => (Type::Void, loc.to_owned()),
+                        },
+                        _ => unreachable!()
+                    };
+                    // A Void here usually comes from something already reported
+                    let cascade = got == Type::Void && typechecker.errors.len() > errors;
+                    if got != *ty && !cascade {
+                        typechecker.report(TypeError::NotEqualFunctionReturnType(name_loc.to_owned(), loc, ty.clone(), got));
+                    }
                 }
             }
-            Expr::Else(block, _) => block.typecheck(typechecker),
-            Expr::Elif(block, _, _) => block.typecheck(typechecker),
-            Expr::If(cond, body, elifs, else_body, loc) => {
-                cond.typecheck(typechecker);
-                if elifs.is_some() {
-                    for elif in elifs.as_ref().unwrap() { elif.typecheck(typechecker); }
+            Expr::Test(name, block, _) => {
+                // Checked like a function that takes and returns nothing
+                typechecker.variables.scopes.push(HashMap::new());
+                let scope_index = typechecker.variables.scopes.len() - 1;
+                typechecker.functions.insert(name.to_owned(), FuncSig::new(Type::Void, &[], scope_index));
+                block.typecheck(typechecker);
+            }
+            Expr::Structure(_, fields, _) => typechecker.check_declared_types(fields),
+            Expr::Import(_, _) => {}
+            Expr::External(name, params, ty, library, loc) => {
+                for (param, param_loc) in params {
+                    if !matches!(param.typename, Type::Int | Type::Float | Type::Pointer | Type::CString) {
+                        typechecker.report(TypeError::NotAnExternalType(param_loc.to_owned(), param.typename.clone()));
+                    }
+                }
+                if !matches!(ty, Type::Int | Type::Float | Type::Pointer | Type::CString | Type::Void) {
+                    typechecker.report(TypeError::NotAnExternalType(loc.to_owned(), ty.clone()));
                 }
-                if else_body.is_some() {
-                    else_body.as_ref().unwrap().typecheck(typechecker)
+                if ffi::resolve(library, name).is_none() {
+                    typechecker.report(TypeError::ExternalNotFound(name.to_owned(), library.to_owned(), loc.to_owned()));
                 }
             }
-            Expr::Call(name, args, nloc, arg_loc) => {
-                let probably_correct_func = (&typechecker).functions.get(name);
-                match probably_correct_func {
-                    Some(func) => {
-                        if args.len() != func.param_types.len() {
-                            report_type_error(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), func.param_types.len(), args.len()), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Enumeration(_, variants, _) => {
+                for variant in variants {
+                    typechecker.check_declared_types(&variant.fields);
+                }
+            }
+            Expr::Match(value, arms, _) => {
+                value.typecheck(typechecker);
+                let ty = value.get_type(&typechecker.functions, &unified_theory_of_shit);
+                let variants = match &ty {
+                    Type::Named(name) if typechecker.enums.contains_key(name) => typechecker.enums[name].to_owned(),
+                    // Undefined variables have already been reported
+                    Type::Void if matches!(value.as_ref(), Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var)) => return,
+                    _ => {
+                        typechecker.report(TypeError::NotAnEnum(value.expression_range(), ty));
+                        return;
+                    }
+                };
+                let enum_name = ty.to_string();
+
+                let mut matched: HashMap<&str, Range<usize>> = HashMap::new();
+                let mut else_arm: Option<Range<usize>> = None;
+                let mut first_arm: Option<(Type, Range<usize>)> = None;
+                for arm in arms {
+                    if let Some(earlier) = &else_arm {
+                        typechecker.report(TypeError::DuplicateMatchArm(earlier.to_owned(), arm.loc.to_owned()));
+                    }
+                    typechecker.variables.begin_block();
+                    match &arm.variant {
+                        Some(variant) if !variants.contains(variant) => {
+                            typechecker.report(TypeError::UnknownVariant(variant.to_owned(), enum_name.to_owned(), arm.loc.to_owned()));
                         }
-                        for (pos, arg) in args.iter().enumerate() {
-                            let (ty, def) = func.param_types.get(pos).unwrap();
-                            let arg_type = arg.get_type(&typechecker.functions, &HashMap::new());
-                            if arg_type != *ty {
-                                report_type_error(TypeError::IncorrectTypeValueForArgument(def.to_owned(), arg.expression_range(), *ty, arg_type), typechecker.filename.as_str(), typechecker.source.as_str());
+                        Some(variant) => {
+                            if let Some(earlier) = matched.get(variant.as_str()) {
+                                typechecker.report(TypeError::DuplicateMatchArm(earlier.to_owned(), arm.loc.to_owned()));
+                            }
+                            matched.insert(variant, arm.loc.to_owned());
+
+                            let fields = typechecker.functions[variant].param_types.to_owned();
+                            if fields.len() != arm.bindings.len() {
+                                typechecker.report(TypeError::IncorrectNumberOfBindings(arm.loc.to_owned(), fields.len(), arm.bindings.len()));
+                            }
+                            for ((binding, _), (ty, _)) in arm.bindings.iter().zip(fields) {
+                                typechecker.variables.push(binding.to_owned(), VarSig { ty });
                             }
                         }
+                        None => else_arm = Some(arm.loc.to_owned()),
                     }
-                    None => {
-                        report_type_error(TypeError::FunctionDoesNotExist(name.to_string(), nloc.to_owned()), typechecker.filename.as_str(), typechecker.source.as_str());
+
+                    arm.body.typecheck(typechecker);
+                    let scope = typechecker.variables.unify();
+                    let arm_type = block_type(&arm.body, &typechecker.functions, &scope);
+                    typechecker.variables.end_block();
+                    match &first_arm {
+                        Some((expected, first)) if *expected != arm_type => {
+                            typechecker.report(TypeError::MismatchedArmTypes(first.to_owned(), arm.body.expression_range(), expected.to_owned(), arm_type));
+                        }
+                        Some(_) => {}
+                        None => first_arm = Some((arm_type, arm.body.expression_range())),
+                    }
+                }
+
+                if else_arm.is_none() {
+                    let missing = variants.iter()
+                        .filter(|variant| !matched.contains_key(variant.as_str()))
+                        .cloned()
+                        .collect::<Vec<String>>();
+                    if !missing.is_empty() {
+                        typechecker.report(TypeError::NonExhaustiveMatch(value.expression_range(), missing));
                     }
                 }
             }
-            Expr::While(cond, body, _) => {
-                cond.typecheck(typechecker);
-                body.typecheck(typechecker);
+            Expr::Field(record, field, loc) => {
+                record.typecheck(typechecker);
+                let record_ty = record.get_type(&typechecker.functions, &unified_theory_of_shit);
+                match &record_ty {
+                    Type::Named(name) if !typechecker.enums.contains_key(name) => {
+                        if field_type(&typechecker.functions, &record_ty, field).is_none() {
+                            typechecker.report(TypeError::UnknownField(field.to_owned(), name.to_owned(), loc.to_owned()));
+                        }
+                    }
+                    // Undefined variables have already been reported
+                    Type::Void if matches!(record.as_ref(), Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var)) => {}
+                    _ => typechecker.report(TypeError::NotARecord(record.expression_range(), record_ty)),
+                }
             }
-            Expr::List(_, _) => todo!(),
-            Expr::Equality(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::ReassignField(field, value, _) => {
+                field.typecheck(typechecker);
+                value.typecheck(typechecker);
+                if let Expr::Field(record, name, _) = field.as_ref() {
+                    let record_ty = record.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    let declared = match &record_ty {
+                        Type::Named(record_name) => typechecker.functions.get(record_name)
+                            .and_then(|sig| sig.field(name))
+                            .cloned(),
+                        _ => None,
+                    };
+                    if let Some((expected, def)) = declared {
+                        let got = value.get_type(&typechecker.functions, &unified_theory_of_shit);
+                        if got != expected {
+                            typechecker.report(TypeError::IncorrectTypeForField(def, value.expression_range(), expected, got));
+                        }
+                    }
                 }
             }
-            Expr::NotEqual(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Block(stmts, _) => {
+                for stmt in stmts {
+                    stmt.typecheck(typechecker)
                 }
             }
-            Expr::GreaterThan(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &typechecker.variables.unify()), rhs.get_type(&typechecker.functions, &typechecker.variables.unify()));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Else(block, _) => block.typecheck(typechecker),
+            Expr::Elif(cond, block, _) => {
+                cond.typecheck(typechecker);
+                typechecker.check_condition(cond, &unified_theory_of_shit);
+                block.typecheck(typechecker);
+            }
+            Expr::If(cond, body, elifs, else_body, _) => {
+                cond.typecheck(typechecker);
+                typechecker.check_condition(cond, &unified_theory_of_shit);
+                body.typecheck(typechecker);
+                if let Some(elifs) = elifs {
+                    for elif in elifs { elif.typecheck(typechecker); }
+                }
+                if let Some(else_body) = else_body {
+                    else_body.typecheck(typechecker)
                 }
             }
-            Expr::LessThan(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Call(name, args, nloc, arg_loc) if unified_theory_of_shit.contains_key(name) => {
+                for arg in args {
+                    arg.typecheck(typechecker);
+                }
+                let params = match &unified_theory_of_shit[name].ty {
+                    Type::Function(params, _) => params,
+                    ty => {
+                        typechecker.report(TypeError::NotCallable(nloc.to_owned(), ty.clone()));
+                        return;
+                    }
+                };
+                if args.len() != params.len() {
+                    typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), params.len(), args.len()));
+                }
+                for (arg, ty) in args.iter().zip(params) {
+                    let arg_type = arg.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    // Undefined variables have already been reported
+                    let undefined = matches!(arg, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                    if arg_type != *ty && !undefined {
+                        typechecker.report(TypeError::IncorrectTypeValueForArgument(Range::default(), arg.expression_range(), ty.clone(), arg_type));
+                    }
                 }
             }
-            Expr::GreaterThanEqual(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Lambda(params, body, loc) => {
+                typechecker.check_declared_types(params);
+                // The parameters only exist in the body, which can't leave a
+                // loop it's written in
+                typechecker.variables.begin_block();
+                for (param, _) in params {
+                    typechecker.variables.push(param.name.to_owned(), VarSig { ty: param.typename.clone() });
                 }
+                let loops = std::mem::take(&mut typechecker.loops);
+                body.typecheck(typechecker);
+                typechecker.loops = loops;
+                let scope = typechecker.variables.unify();
+                let returns = block_type(body, &typechecker.functions, &scope);
+                typechecker.variables.end_block();
+                typechecker.functions.insert(lambda_name(loc), FuncSig::new(returns, params, 0));
+            }
+            Expr::Call(name, args, _, arg_loc) if collections::is_collection_call(name, &typechecker.functions) => {
+                collections::check_call(typechecker, name, args, arg_loc, &unified_theory_of_shit);
             }
-            Expr::LessThanEqual(lhs, rhs) => {
+            Expr::Call(name, args, _, arg_loc) if name == ASSERT_EQ_SYMBOL => {
+                for arg in args {
+                    arg.typecheck(typechecker);
+                }
+                if args.len() != 2 {
+                    typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), 2, args.len()));
+                    return;
+                }
+                // Undefined variables have already been reported
+                if args.iter().any(|arg| matches!(arg, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var))) {
+                    return;
+                }
+                let (lhs, rhs) = (&args[0], &args[1]);
                 let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
                 if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+                    typechecker.report(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty));
+                } else if !matches!(lhs_ty, Type::Int | Type::Float | Type::String | Type::Bool) {
+                    typechecker.report(TypeError::CannotAssertEqual(lhs.expression_range(), lhs_ty));
                 }
             }
-            Expr::Addition(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Call(name, args, _, arg_loc) if name == PRINTF_SYMBOL => {
+                for arg in args {
+                    arg.typecheck(typechecker);
+                }
+                let (format, values) = match args.split_first() {
+                    Some(split) => split,
+                    None => {
+                        typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), 1, 0));
+                        return;
+                    }
+                };
+                let pieces = match format {
+                    Expr::String(format, _) => format::parse(format),
+                    _ => Err("the format has to be written out as a string".to_owned()),
+                };
+                let types = match pieces {
+                    Ok(pieces) => format::value_types(&pieces),
+                    Err(problem) => {
+                        typechecker.report(TypeError::InvalidFormat(format.expression_range(), problem));
+                        return;
+                    }
+                };
+                if values.len() != types.len() {
+                    typechecker.report(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), types.len() + 1, args.len()));
+                }
+                for (value, ty) in values.iter().zip(types) {
+                    let value_type = value.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    // Undefined variables have already been reported
+                    let undefined = matches!(value, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                    if value_type != ty && !undefined {
+                        typechecker.report(TypeError::IncorrectTypeValueForArgument(format.expression_range(), value.expression_range(), ty, value_type));
+                    }
                 }
             }
-            Expr::Subtraction(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Call(name, args, nloc, arg_loc) => {
+                for arg in args {
+                    arg.typecheck(typechecker);
+                }
+                let mut errors = Vec::new();
+                match typechecker.functions.get(name) {
+                    Some(func) => {
+                        if args.len() != func.param_types.len() {
+                            errors.push(TypeError::IncorrectNumberOfFunctionArguments(arg_loc.to_owned(), func.param_types.len(), args.len()));
+                        }
+                        let number = number_type(func, args, &typechecker.functions, &unified_theory_of_shit);
+                        for (arg, (ty, def)) in args.iter().zip(func.param_types.iter()) {
+                            let ty = if *ty == Type::Number { &number } else { ty };
+                            let arg_type = arg.get_type(&typechecker.functions, &unified_theory_of_shit);
+                            // Undefined variables have already been reported
+                            let undefined = matches!(arg, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                            if arg_type != *ty && !undefined {
+                                errors.push(TypeError::IncorrectTypeValueForArgument(def.to_owned(), arg.expression_range(), ty.clone(), arg_type));
+                            }
+                        }
+                    }
+                    None => {
+                        errors.push(TypeError::FunctionDoesNotExist(name.to_string(), nloc.to_owned()));
+                    }
+                }
+                for error in errors {
+                    typechecker.report(error);
                 }
             }
-            Expr::Multiplication(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::While(cond, body, _) => {
+                cond.typecheck(typechecker);
+                typechecker.check_condition(cond, &unified_theory_of_shit);
+                typechecker.loops += 1;
+                body.typecheck(typechecker);
+                typechecker.loops -= 1;
+            }
+            Expr::For(name, _, values, body, _) => {
+                values.typecheck(typechecker);
+                let ty = values.get_type(&typechecker.functions, &unified_theory_of_shit);
+                let item = match ty {
+                    _ if matches!(values.as_ref(), Expr::Range(..)) => Type::Int,
+                    Type::List(item) => *item,
+                    Type::Map(key, _) => *key,
+                    Type::String => Type::String,
+                    // Undefined variables have already been reported
+                    Type::Void if matches!(values.as_ref(), Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var)) => Type::Void,
+                    ty => {
+                        typechecker.report(TypeError::NotIterable(values.expression_range(), ty));
+                        Type::Void
+                    }
+                };
+                // The variable only exists in the body
+                typechecker.variables.begin_block();
+                typechecker.variables.push(name.to_owned(), VarSig { ty: item });
+                typechecker.loops += 1;
+                body.typecheck(typechecker);
+                typechecker.loops -= 1;
+                typechecker.variables.end_block();
+            }
+            Expr::Range(start, end, _) => {
+                for bound in [start, end] {
+                    bound.typecheck(typechecker);
+                    let ty = bound.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    // Undefined variables have already been reported
+                    let undefined = matches!(bound.as_ref(), Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                    if ty != Type::Int && !undefined {
+                        typechecker.report(TypeError::InvalidRangeBound(bound.expression_range(), ty));
+                    }
                 }
             }
-            Expr::Division(lhs, rhs) => {
-                let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
-                if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+            Expr::Break(loc) | Expr::Continue(loc) if typechecker.loops == 0 => {
+                let keyword = if matches!(self, Expr::Break(_)) { "break" } else { "continue" };
+                typechecker.report(TypeError::OutsideOfLoop(loc.to_owned(), keyword));
+            }
+            Expr::Break(_) | Expr::Continue(_) => {}
+            Expr::List(values, loc) => {
+                for value in values {
+                    value.typecheck(typechecker);
+                }
+                let first = match values.first() {
+                    Some(first) => first,
+                    None => {
+                        typechecker.report(TypeError::EmptyList(loc.to_owned()));
+                        return;
+                    }
+                };
+                let ty = first.get_type(&typechecker.functions, &unified_theory_of_shit);
+                for value in &values[1..] {
+                    let value_type = value.get_type(&typechecker.functions, &unified_theory_of_shit);
+                    // Undefined variables have already been reported
+                    let undefined = matches!(value, Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var));
+                    if value_type != ty && !undefined {
+                        typechecker.report(TypeError::MismatchedListItems(first.expression_range(), value.expression_range(), ty.clone(), value_type));
+                    }
+                }
+            }
+            Expr::Negate(expr, _) => {
+                expr.typecheck(typechecker);
+                let ty = expr.get_type(&typechecker.functions, &unified_theory_of_shit);
+                // Undefined variables have already been reported
+                let undefined = matches!(expr.as_ref(), Expr::Var(var, _) if !unified_theory_of_shit.contains_key(var) && !typechecker.functions.contains_key(var));
+                if !undefined && !matches!(ty, Type::Int | Type::Float) {
+                    typechecker.report(TypeError::CannotNegate(expr.expression_range(), ty));
                 }
             }
-            Expr::Power(lhs, rhs) => {
+            Expr::Equality(lhs, rhs)
+            | Expr::NotEqual(lhs, rhs)
+            | Expr::GreaterThan(lhs, rhs)
+            | Expr::LessThan(lhs, rhs)
+            | Expr::GreaterThanEqual(lhs, rhs)
+            | Expr::LessThanEqual(lhs, rhs)
+            | Expr::Addition(lhs, rhs)
+            | Expr::Subtraction(lhs, rhs)
+            | Expr::Multiplication(lhs, rhs)
+            | Expr::Division(lhs, rhs)
+            | Expr::Power(lhs, rhs) => {
+                lhs.typecheck(typechecker);
+                rhs.typecheck(typechecker);
                 let (lhs_ty, rhs_ty) = (lhs.get_type(&typechecker.functions, &unified_theory_of_shit), rhs.get_type(&typechecker.functions, &unified_theory_of_shit));
                 if lhs_ty != rhs_ty {
-                    report_type_error(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty), typechecker.filename.as_str(), typechecker.source.as_str());
+                    typechecker.report(TypeError::InvaidTypesForOperation(lhs.expression_range(), rhs.expression_range(), lhs_ty, rhs_ty));
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -1 +1,4 @@
-pub mod lower;
\ No newline at end of file
+pub mod collections;
+pub mod format;
+pub mod lower;
+pub mod optimize;
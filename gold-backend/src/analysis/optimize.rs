@@ -0,0 +1,378 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use gold_frontend::frontend::{BinaryExpr, Expr, MatchArm};
+
+/// Simplifies checked functions before code generation. Arithmetic and
+/// comparisons on literals are folded, branches whose condition is known to
+/// be false are dropped, and assignments to variables that are never read
+/// are removed. Anything with a side effect is kept.
+///
+/// The variables a dropped branch declares can still be used after it, so
+/// they are kept as declarations without a value (`var name = ()`), which
+/// leaves them zero.
+pub fn optimize(items: Vec<Expr>) -> Vec<Expr> {
+    items.into_iter().map(|item| match item {
+        Expr::Function(name, params, ty, body, loc) => Expr::Function(name, params, ty, Box::new(optimize_body(*body)), loc),
+        Expr::Test(name, body, loc) => Expr::Test(name, Box::new(optimize_body(*body)), loc),
+        item => item,
+    }).collect()
+}
+
+fn optimize_body(body: Expr) -> Expr {
+    let body = fold(body);
+    let mut read = HashSet::new();
+    reads(&body, &mut read);
+    remove_unused(body, &read)
+}
+
+/// Renders the functions as s-expressions, for comparing what the
+/// optimizations did.
+pub fn dump(items: &[Expr]) -> String {
+    let mut out = String::new();
+    for item in items {
+        if let Expr::Function(name, _, _, body, _) | Expr::Test(name, body, _) = item {
+            out.push_str(name);
+            out.push_str(":\n");
+            if let Expr::Block(stmts, _) = body.as_ref() {
+                for stmt in stmts {
+                    out.push_str("  ");
+                    out.push_str(&sexpr(stmt));
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+fn fold(expr: Expr) -> Expr {
+    match expr {
+        Expr::Addition(lhs, rhs) => arithmetic(Expr::Addition, *lhs, *rhs, |a, b| Some(a.wrapping_add(b))),
+        Expr::Subtraction(lhs, rhs) => arithmetic(Expr::Subtraction, *lhs, *rhs, |a, b| Some(a.wrapping_sub(b))),
+        Expr::Multiplication(lhs, rhs) => arithmetic(Expr::Multiplication, *lhs, *rhs, |a, b| Some(a.wrapping_mul(b))),
+        // Dividing by zero is left for the program to trap on
+        Expr::Division(lhs, rhs) => arithmetic(Expr::Division, *lhs, *rhs, i64::checked_div),
+        Expr::Power(lhs, rhs) => arithmetic(Expr::Power, *lhs, *rhs, |a, b| a.checked_pow(u32::try_from(b).ok()?)),
+        Expr::Equality(lhs, rhs) => comparison(Expr::Equality, *lhs, *rhs, |o| o == Ordering::Equal),
+        Expr::NotEqual(lhs, rhs) => comparison(Expr::NotEqual, *lhs, *rhs, |o| o != Ordering::Equal),
+        Expr::GreaterThan(lhs, rhs) => comparison(Expr::GreaterThan, *lhs, *rhs, |o| o == Ordering::Greater),
+        Expr::LessThan(lhs, rhs) => comparison(Expr::LessThan, *lhs, *rhs, |o| o == Ordering::Less),
+        Expr::GreaterThanEqual(lhs, rhs) => comparison(Expr::GreaterThanEqual, *lhs, *rhs, |o| o != Ordering::Less),
+        Expr::LessThanEqual(lhs, rhs) => comparison(Expr::LessThanEqual, *lhs, *rhs, |o| o != Ordering::Greater),
+        Expr::Negate(value, loc) => match fold(*value) {
+            Expr::Number(n, _) => Expr::Number(n.wrapping_neg(), loc),
+            value => Expr::Negate(Box::new(value), loc),
+        },
+        Expr::Interpolation(parts, loc) => {
+            let parts = parts.into_iter().map(fold).collect::<Vec<Expr>>();
+            let mut text = String::new();
+            for part in &parts {
+                match part {
+                    Expr::String(s, _) => text.push_str(s),
+                    Expr::Number(n, _) => text.push_str(&n.to_string()),
+                    Expr::Bool(b, _) => text.push_str(&b.to_string()),
+                    _ => return Expr::Interpolation(parts, loc),
+                }
+            }
+            Expr::String(text, loc)
+        }
+        Expr::If(cond, body, elifs, else_body, loc) => {
+            let mut branches = vec![(*cond, *body, loc.to_owned())];
+            for elif in elifs.unwrap_or_default() {
+                if let Expr::Elif(cond, body, loc) = elif {
+                    branches.push((*cond, *body, loc));
+                }
+            }
+            let mut otherwise = else_body.map(|else_body| match *else_body {
+                Expr::Else(body, loc) => (fold(*body), loc),
+                _ => unreachable!()
+            });
+
+            // Branches that can't be taken are dropped, and one that always
+            // is takes the place of everything after it
+            let mut kept = Vec::new();
+            let mut dropped = Vec::new();
+            let mut branches = branches.into_iter();
+            while let Some((cond, body, loc)) = branches.next() {
+                match fold(cond) {
+                    Expr::Bool(false, _) => declarations(&body, &mut dropped),
+                    Expr::Bool(true, _) => {
+                        for (_, body, _) in branches.by_ref() {
+                            declarations(&body, &mut dropped);
+                        }
+                        if let Some((body, _)) = &otherwise {
+                            declarations(body, &mut dropped);
+                        }
+                        otherwise = Some((fold(body), loc));
+                    }
+                    cond => kept.push((cond, fold(body), loc)),
+                }
+            }
+
+            let mut kept = kept.into_iter();
+            let folded = match kept.next() {
+                Some((cond, body, _)) => {
+                    let elifs = kept.map(|(cond, body, loc)| Expr::Elif(Box::new(cond), Box::new(body), loc)).collect::<Vec<Expr>>();
+                    let elifs = if elifs.is_empty() { None } else { Some(elifs) };
+                    let otherwise = otherwise.map(|(body, loc)| Box::new(Expr::Else(Box::new(body), loc)));
+                    Expr::If(Box::new(cond), Box::new(body), elifs, otherwise, loc.to_owned())
+                }
+                None => match otherwise {
+                    Some((body, _)) => body,
+                    None => Expr::Block(Vec::new(), loc.to_owned()),
+                },
+            };
+            match folded {
+                folded if dropped.is_empty() => folded,
+                Expr::Block(stmts, _) => {
+                    dropped.extend(stmts);
+                    Expr::Block(dropped, loc)
+                }
+                folded => {
+                    dropped.push(folded);
+                    Expr::Block(dropped, loc)
+                }
+            }
+        }
+        Expr::While(cond, body, loc) => match fold(*cond) {
+            Expr::Bool(false, _) => {
+                let mut dropped = Vec::new();
+                declarations(&body, &mut dropped);
+                Expr::Block(dropped, loc)
+            }
+            cond => Expr::While(Box::new(cond), Box::new(fold(*body)), loc),
+        },
+        Expr::For(name, name_loc, values, body, loc) => Expr::For(name, name_loc, Box::new(fold(*values)), Box::new(fold(*body)), loc),
+        Expr::Range(start, end, inclusive) => Expr::Range(Box::new(fold(*start)), Box::new(fold(*end)), inclusive),
+        Expr::Block(stmts, loc) => {
+            let mut folded = Vec::new();
+            let count = stmts.len();
+            for (index, stmt) in stmts.into_iter().enumerate() {
+                match fold(stmt) {
+                    // Branches that were picked or dropped leave a block behind.
+                    // Variables belong to the function, so its statements can
+                    // join this block, unless it gives the value of this one.
+                    Expr::Block(inner, _) if index + 1 < count => folded.extend(inner),
+                    stmt => folded.push(stmt),
+                }
+            }
+            Expr::Block(folded, loc)
+        }
+        Expr::Call(name, args, loc, args_loc) => Expr::Call(name, args.into_iter().map(fold).collect(), loc, args_loc),
+        Expr::Lambda(params, body, loc) => Expr::Lambda(params, Box::new(fold(*body)), loc),
+        Expr::List(values, loc) => Expr::List(values.into_iter().map(fold).collect(), loc),
+        Expr::Assign(name, value, loc) => Expr::Assign(name, Box::new(fold(*value)), loc),
+        Expr::Reassign(name, value, loc) => Expr::Reassign(name, Box::new(fold(*value)), loc),
+        Expr::Field(record, field, loc) => Expr::Field(Box::new(fold(*record)), field, loc),
+        Expr::ReassignField(field, value, loc) => Expr::ReassignField(Box::new(fold(*field)), Box::new(fold(*value)), loc),
+        Expr::Match(value, arms, loc) => {
+            let arms = arms.into_iter().map(|arm| MatchArm { body: fold(arm.body), ..arm }).collect();
+            Expr::Match(Box::new(fold(*value)), arms, loc)
+        }
+        expr => expr,
+    }
+}
+
+/// Collects the variables declared by code that is dropped, as declarations
+/// without a value. Those of anonymous functions, for loops and match arms
+/// only exist inside them.
+fn declarations(expr: &Expr, dropped: &mut Vec<Expr>) {
+    match expr {
+        Expr::Assign(name, value, loc) => {
+            declarations(value, dropped);
+            dropped.push(Expr::Assign(name.to_owned(), Box::new(Expr::NoExpr), loc.to_owned()));
+        }
+        Expr::Lambda(..) => {}
+        Expr::For(_, _, values, _, _) => declarations(values, dropped),
+        Expr::Match(value, _, _) => declarations(value, dropped),
+        expr => {
+            for child in children(expr) {
+                declarations(child, dropped);
+            }
+        }
+    }
+}
+
+fn span(lhs: &Expr, rhs: &Expr) -> Range<usize> {
+    lhs.expression_range().start..rhs.expression_range().end
+}
+
+fn arithmetic(make: BinaryExpr, lhs: Expr, rhs: Expr, op: fn(i64, i64) -> Option<i64>) -> Expr {
+    let (lhs, rhs) = (fold(lhs), fold(rhs));
+    if let (Expr::Number(a, _), Expr::Number(b, _)) = (&lhs, &rhs) {
+        if let Some(value) = op(*a, *b) {
+            return Expr::Number(value, span(&lhs, &rhs));
+        }
+    }
+    make(Box::new(lhs), Box::new(rhs))
+}
+
+fn comparison(make: BinaryExpr, lhs: Expr, rhs: Expr, op: fn(Ordering) -> bool) -> Expr {
+    let (lhs, rhs) = (fold(lhs), fold(rhs));
+    let ordering = match (&lhs, &rhs) {
+        (Expr::Number(a, _), Expr::Number(b, _)) => a.cmp(b),
+        (Expr::Bool(a, _), Expr::Bool(b, _)) => a.cmp(b),
+        // Ordered byte by byte, the same as the runtime's strcmp
+        (Expr::String(a, _), Expr::String(b, _)) => a.cmp(b),
+        _ => return make(Box::new(lhs), Box::new(rhs)),
+    };
+    Expr::Bool(op(ordering), span(&lhs, &rhs))
+}
+
+/// Collects the name of every variable that is read, including those of
+/// closures that are called.
+fn reads(expr: &Expr, read: &mut HashSet<String>) {
+    if let Expr::Var(name, _) | Expr::Call(name, _, _, _) = expr {
+        read.insert(name.to_owned());
+    }
+    for child in children(expr) {
+        reads(child, read);
+    }
+}
+
+fn remove_unused(expr: Expr, read: &HashSet<String>) -> Expr {
+    match expr {
+        Expr::Block(stmts, loc) => {
+            let count = stmts.len();
+            let mut kept = Vec::new();
+            for (index, stmt) in stmts.into_iter().enumerate() {
+                let last = index + 1 == count;
+                match stmt {
+                    Expr::Assign(name, value, _) | Expr::Reassign(name, value, _) if !read.contains(&name) => {
+                        // The last statement gives the block its value, which
+                        // a declaration without one doesn't have
+                        if (last || !is_pure(&value)) && !matches!(*value, Expr::NoExpr) {
+                            kept.push(remove_unused(*value, read));
+                        }
+                    }
+                    stmt => kept.push(remove_unused(stmt, read)),
+                }
+            }
+            Expr::Block(kept, loc)
+        }
+        Expr::If(cond, body, elifs, else_body, loc) => {
+            let elifs = elifs.map(|elifs| elifs.into_iter().map(|elif| remove_unused(elif, read)).collect());
+            let else_body = else_body.map(|else_body| Box::new(remove_unused(*else_body, read)));
+            Expr::If(cond, Box::new(remove_unused(*body, read)), elifs, else_body, loc)
+        }
+        Expr::Elif(cond, body, loc) => Expr::Elif(cond, Box::new(remove_unused(*body, read)), loc),
+        Expr::Else(body, loc) => Expr::Else(Box::new(remove_unused(*body, read)), loc),
+        Expr::While(cond, body, loc) => Expr::While(cond, Box::new(remove_unused(*body, read)), loc),
+        Expr::Lambda(params, body, loc) => Expr::Lambda(params, Box::new(remove_unused(*body, read)), loc),
+        Expr::For(name, name_loc, values, body, loc) => Expr::For(name, name_loc, values, Box::new(remove_unused(*body, read)), loc),
+        Expr::Match(value, arms, loc) => {
+            let arms = arms.into_iter().map(|arm| MatchArm { body: remove_unused(arm.body, read), ..arm }).collect();
+            Expr::Match(value, arms, loc)
+        }
+        expr => expr,
+    }
+}
+
+// Whether evaluating the expression can be skipped without anything changing.
+// Calls could do anything and division can trap.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_, _) | Expr::Bool(_, _) | Expr::String(_, _) | Expr::Var(_, _) => true,
+        Expr::Interpolation(_, _) | Expr::Field(_, _, _) | Expr::Negate(_, _)
+        | Expr::Equality(_, _) | Expr::NotEqual(_, _) | Expr::GreaterThan(_, _) | Expr::LessThan(_, _)
+        | Expr::GreaterThanEqual(_, _) | Expr::LessThanEqual(_, _)
+        | Expr::Addition(_, _) | Expr::Subtraction(_, _) | Expr::Multiplication(_, _) => children(expr).into_iter().all(is_pure),
+        _ => false,
+    }
+}
+
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::NoExpr | Expr::Number(_, _) | Expr::Bool(_, _) | Expr::String(_, _) | Expr::Var(_, _) | Expr::Parameter(_, _)
+        | Expr::Structure(_, _, _) | Expr::Enumeration(_, _, _) | Expr::Import(_, _) | Expr::External(_, _, _, _, _)
+        | Expr::Break(_) | Expr::Continue(_) => vec![],
+        Expr::Block(stmts, _) => stmts.iter().collect(),
+        Expr::List(values, _) | Expr::Interpolation(values, _) => values.iter().collect(),
+        Expr::Call(_, args, _, _) => args.iter().collect(),
+        Expr::Function(_, _, _, body, _) | Expr::Test(_, body, _) => vec![body.as_ref()],
+        Expr::Else(body, _) | Expr::Lambda(_, body, _) => vec![body.as_ref()],
+        Expr::Elif(cond, body, _) | Expr::While(cond, body, _) | Expr::For(_, _, cond, body, _) => vec![cond.as_ref(), body.as_ref()],
+        Expr::If(cond, body, elifs, else_body, _) => {
+            let mut children = vec![cond.as_ref(), body.as_ref()];
+            children.extend(elifs.iter().flatten());
+            children.extend(else_body.iter().map(|else_body| else_body.as_ref()));
+            children
+        }
+        Expr::Match(value, arms, _) => {
+            let mut children = vec![value.as_ref()];
+            children.extend(arms.iter().map(|arm| &arm.body));
+            children
+        }
+        Expr::Assign(_, value, _) | Expr::Reassign(_, value, _) | Expr::Negate(value, _) | Expr::Field(value, _, _) => vec![value.as_ref()],
+        Expr::ReassignField(field, value, _) => vec![field.as_ref(), value.as_ref()],
+        Expr::Equality(lhs, rhs) | Expr::NotEqual(lhs, rhs) | Expr::GreaterThan(lhs, rhs) | Expr::LessThan(lhs, rhs)
+        | Expr::GreaterThanEqual(lhs, rhs) | Expr::LessThanEqual(lhs, rhs)
+        | Expr::Addition(lhs, rhs) | Expr::Subtraction(lhs, rhs) | Expr::Multiplication(lhs, rhs)
+        | Expr::Division(lhs, rhs) | Expr::Power(lhs, rhs) | Expr::Range(lhs, rhs, _) => vec![lhs.as_ref(), rhs.as_ref()],
+    }
+}
+
+fn sexpr(expr: &Expr) -> String {
+    let list = |head: &str, items: Vec<String>| {
+        let mut parts = vec![head.to_owned()];
+        parts.extend(items);
+        ["(", parts.join(" ").as_str(), ")"].join("")
+    };
+    let all = |exprs: Vec<&Expr>| exprs.into_iter().map(sexpr).collect::<Vec<String>>();
+    match expr {
+        Expr::NoExpr => "()".to_owned(),
+        Expr::Number(value, _) => value.to_string(),
+        Expr::Bool(value, _) => value.to_string(),
+        Expr::String(value, _) => format!("{:?}", value),
+        Expr::Var(name, _) => name.to_owned(),
+        Expr::Parameter(param, _) => param.name.to_owned(),
+        Expr::Block(stmts, _) => ["{", all(stmts.iter().collect()).join(" ").as_str(), "}"].join(""),
+        Expr::Interpolation(parts, _) => list("str", all(parts.iter().collect())),
+        Expr::List(values, _) => ["[", all(values.iter().collect()).join(" ").as_str(), "]"].join(""),
+        Expr::Call(name, args, _, _) => list(name, all(args.iter().collect())),
+        Expr::Assign(name, value, _) => list("var", vec![name.to_owned(), sexpr(value)]),
+        Expr::Reassign(name, value, _) => list("=", vec![name.to_owned(), sexpr(value)]),
+        Expr::ReassignField(field, value, _) => list("=", vec![sexpr(field), sexpr(value)]),
+        Expr::Field(record, field, _) => list(".", vec![sexpr(record), field.to_owned()]),
+        Expr::Negate(value, _) => list("neg", vec![sexpr(value)]),
+        Expr::If(..) => list("if", all(children(expr))),
+        Expr::Elif(..) => list("elif", all(children(expr))),
+        Expr::Else(..) => list("else", all(children(expr))),
+        Expr::While(..) => list("while", all(children(expr))),
+        Expr::For(name, _, values, body, _) => list("for", vec![name.to_owned(), sexpr(values), sexpr(body)]),
+        Expr::Range(_, _, false) => list("..", all(children(expr))),
+        Expr::Range(_, _, true) => list("..=", all(children(expr))),
+        Expr::Lambda(params, body, _) => {
+            let params = params.iter().map(|(param, _)| param.name.to_owned()).collect::<Vec<String>>();
+            list("fn", vec![["(", params.join(" ").as_str(), ")"].join(""), sexpr(body)])
+        }
+        Expr::Break(_) => "break".to_owned(),
+        Expr::Continue(_) => "continue".to_owned(),
+        Expr::Match(value, arms, _) => {
+            let mut items = vec![sexpr(value)];
+            items.extend(arms.iter().map(|arm| {
+                let mut pattern = vec![arm.variant.to_owned().unwrap_or_else(|| "else".to_owned())];
+                pattern.extend(arm.bindings.iter().map(|(name, _)| name.to_owned()));
+                list(&pattern.join(" "), vec![sexpr(&arm.body)])
+            }));
+            list("match", items)
+        }
+        Expr::Equality(..) => list("is", all(children(expr))),
+        Expr::NotEqual(..) => list("is-not", all(children(expr))),
+        Expr::GreaterThan(..) => list(">", all(children(expr))),
+        Expr::LessThan(..) => list("<", all(children(expr))),
+        Expr::GreaterThanEqual(..) => list(">=", all(children(expr))),
+        Expr::LessThanEqual(..) => list("<=", all(children(expr))),
+        Expr::Addition(..) => list("+", all(children(expr))),
+        Expr::Subtraction(..) => list("-", all(children(expr))),
+        Expr::Multiplication(..) => list("*", all(children(expr))),
+        Expr::Division(..) => list("/", all(children(expr))),
+        Expr::Power(..) => list("^", all(children(expr))),
+        Expr::Function(name, _, _, _, _) | Expr::Test(name, _, _) | Expr::Structure(name, _, _) | Expr::Enumeration(name, _, _)
+        | Expr::External(name, _, _, _, _) => name.to_owned(),
+        Expr::Import(path, _) => list("uses", vec![path.to_owned()]),
+    }
+}
@@ -0,0 +1,263 @@
+//! Reports a crash of compiled code the way errors in the source are reported.
+//!
+//! While a program runs, the signals that traps and bad memory accesses raise
+//! are handled here. What was recorded while compiling each function tells
+//! which Gold function was running and where in the source it was, and the
+//! return addresses left on the stack give the calls that led there. They are
+//! shown as a diagnostic before the process exits.
+//!
+//! Little is safe to do in a signal handler, so the handler only keeps the
+//! registers and returns into `report_crash` on a stack of its own. The report
+//! is put together there, with the stack of the crashed code left as it was.
+
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, AtomicPtr, AtomicUsize, Ordering};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::{Files, SimpleFiles};
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use cranelift::codegen::ir::{SourceLoc, TrapCode};
+
+use super::source_map::{CompiledFunction, SourceMap};
+
+pub use gold_standard::crash::CRASH_EXIT_CODE;
+
+/// The trap for getting a key a map doesn't have.
+pub const MISSING_KEY: TrapCode = TrapCode::User(0);
+/// The trap for getting an index past the end of a list.
+pub const OUT_OF_BOUNDS: TrapCode = TrapCode::User(1);
+
+// A deep recursion would otherwise fill the screen
+const MAX_FRAMES: usize = 20;
+
+const SIGNALS: [c_int; 4] = [libc::SIGSEGV, libc::SIGBUS, libc::SIGFPE, libc::SIGILL];
+
+// The handler runs on a stack of its own, so a stack overflow can be handled
+// too. Rendering the report needs far more stack than the handler does.
+const SIGNAL_STACK_SIZE: usize = 1 << 16;
+const REPORT_STACK_SIZE: usize = 1 << 20;
+
+// The source map of the program that is running, the highest stack address its
+// frames can be at, and the end of the stack the report is rendered on
+static MAP: AtomicPtr<SourceMap> = AtomicPtr::new(ptr::null_mut());
+static STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+static REPORT_STACK: AtomicUsize = AtomicUsize::new(0);
+
+// The signal and the registers when it was raised, kept by the handler
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+static PC: AtomicUsize = AtomicUsize::new(0);
+static SP: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs compiled code, reporting a crash in it against the source instead of
+/// letting the process die of a signal.
+pub fn guard<R>(map: &SourceMap, run: impl FnOnce() -> R) -> R {
+    let top = 0u8;
+    STACK_TOP.store(&top as *const u8 as usize, Ordering::SeqCst);
+    MAP.store(map as *const SourceMap as *mut SourceMap, Ordering::SeqCst);
+    let report_stack = vec![0u8; REPORT_STACK_SIZE];
+    REPORT_STACK.store(report_stack.as_ptr() as usize + report_stack.len(), Ordering::SeqCst);
+    let mut stack = vec![0u8; SIGNAL_STACK_SIZE];
+    let signal_stack = libc::stack_t {
+        ss_sp: stack.as_mut_ptr() as *mut c_void,
+        ss_flags: 0,
+        ss_size: stack.len(),
+    };
+    let mut previous_stack: libc::stack_t = unsafe { mem::zeroed() };
+    unsafe { libc::sigaltstack(&signal_stack, &mut previous_stack) };
+    let previous = SIGNALS.iter().map(|signal| unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle as extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        let mut previous: libc::sigaction = mem::zeroed();
+        libc::sigaction(*signal, &action, &mut previous);
+        previous
+    }).collect::<Vec<libc::sigaction>>();
+
+    let result = run();
+
+    for (signal, previous) in SIGNALS.iter().zip(previous) {
+        unsafe { libc::sigaction(*signal, &previous, ptr::null_mut()) };
+    }
+    unsafe { libc::sigaltstack(&previous_stack, ptr::null_mut()) };
+    drop(stack);
+    MAP.store(ptr::null_mut(), Ordering::SeqCst);
+    REPORT_STACK.store(0, Ordering::SeqCst);
+    drop(report_stack);
+    result
+}
+
+extern "C" fn handle(signal: c_int, _: *mut libc::siginfo_t, context: *mut c_void) {
+    let report_stack = REPORT_STACK.load(Ordering::SeqCst);
+    if !MAP.load(Ordering::SeqCst).is_null() && report_stack != 0 {
+        if let Some((pc, sp)) = unsafe { registers(context) } {
+            SIGNAL.store(signal, Ordering::SeqCst);
+            PC.store(pc, Ordering::SeqCst);
+            SP.store(sp, Ordering::SeqCst);
+            unsafe { resume_at(context, report_crash as extern "C" fn() -> ! as usize, report_stack) };
+            return;
+        }
+    }
+    // Nothing is known about the code, so it crashes the usual way
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+/// Where the handler returns to, outside of the signal and on the stack kept
+/// for it.
+extern "C" fn report_crash() -> ! {
+    // A crash while reporting one takes the process down the usual way
+    let map = unsafe { &*MAP.swap(ptr::null_mut(), Ordering::SeqCst) };
+    let (signal, pc, sp) = (SIGNAL.load(Ordering::SeqCst), PC.load(Ordering::SeqCst), SP.load(Ordering::SeqCst));
+    let crash = unsafe { Crash::find(map, signal, pc, sp, STACK_TOP.load(Ordering::SeqCst)) };
+    // What the program printed should come before the error
+    use std::io::Write;
+    std::io::stdout().flush().ok();
+    crash.report(map);
+    unsafe { libc::_exit(CRASH_EXIT_CODE) }
+}
+
+// The program counter and stack pointer when the signal was raised
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn registers(context: *mut c_void) -> Option<(usize, usize)> {
+    let context = &*(context as *const libc::ucontext_t);
+    let registers = &context.uc_mcontext.gregs;
+    Some((registers[libc::REG_RIP as usize] as usize, registers[libc::REG_RSP as usize] as usize))
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn registers(context: *mut c_void) -> Option<(usize, usize)> {
+    let context = &*(context as *const libc::ucontext_t);
+    Some((context.uc_mcontext.pc as usize, context.uc_mcontext.sp as usize))
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+unsafe fn registers(_: *mut c_void) -> Option<(usize, usize)> {
+    None
+}
+
+// Makes returning from the handler call a function on another stack, as if
+// it had been called from nowhere
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn resume_at(context: *mut c_void, function: usize, stack_end: usize) {
+    let context = &mut *(context as *mut libc::ucontext_t);
+    let registers = &mut context.uc_mcontext.gregs;
+    registers[libc::REG_RIP as usize] = function as i64;
+    // A call would have pushed a return address onto an aligned stack
+    registers[libc::REG_RSP as usize] = ((stack_end & !15) - mem::size_of::<usize>()) as i64;
+}
+
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn resume_at(context: *mut c_void, function: usize, stack_end: usize) {
+    let context = &mut *(context as *mut libc::ucontext_t);
+    context.uc_mcontext.pc = function as u64;
+    context.uc_mcontext.sp = (stack_end & !15) as u64;
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+unsafe fn resume_at(_: *mut c_void, _: usize, _: usize) {}
+
+struct Frame<'a> {
+    function: &'a CompiledFunction,
+    srcloc: Option<SourceLoc>,
+}
+
+struct Crash<'a> {
+    message: String,
+    // The innermost call first
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Crash<'a> {
+    /// Works out what went wrong and the calls that led there, from the
+    /// registers at the time and the stack between them and `top`.
+    unsafe fn find(map: &'a SourceMap, signal: c_int, pc: usize, sp: usize, top: usize) -> Self {
+        let mut message = match signal {
+            libc::SIGFPE => "arithmetic error",
+            libc::SIGILL => "illegal instruction",
+            _ => "invalid memory access",
+        }.to_owned();
+        let mut frames = Vec::new();
+        match map.function_at(pc) {
+            Some((function, offset)) => {
+                let trap = function.traps.iter().find(|(trap, _, _)| *trap == offset);
+                if let Some((_, srcloc, code)) = trap {
+                    message = trap_message(*code);
+                    frames.push(Frame { function, srcloc: Some(*srcloc).filter(|loc| !loc.is_default()) });
+                } else {
+                    frames.push(Frame { function, srcloc: function.srcloc_at(offset) });
+                }
+            }
+            // A builtin or a C function was called with something it can't handle
+            None => message.push_str(" in a function called from Gold"),
+        }
+
+        // Any word on the stack that is right after a call in compiled code
+        // is taken to be the return address of a call that is still running
+        let mut address = sp & !(mem::size_of::<usize>() - 1);
+        while address < top {
+            let value = ptr::read(address as *const usize);
+            if let Some((function, offset)) = map.function_at(value) {
+                if function.returns.contains(&offset) {
+                    frames.push(Frame { function, srcloc: function.srcloc_at(offset - 1) });
+                }
+            }
+            address += mem::size_of::<usize>();
+        }
+        Crash { message, frames }
+    }
+
+    fn report(&self, map: &SourceMap) {
+        let mut files = SimpleFiles::new();
+        for (name, source) in &map.files {
+            files.add(name.as_str(), source.as_str());
+        }
+        let span = |frame: &Frame| frame.srcloc.and_then(|loc| map.span(loc));
+
+        let mut labels = Vec::new();
+        let mut stack = vec!["call stack:".to_owned()];
+        for (index, frame) in self.frames.iter().enumerate().take(MAX_FRAMES) {
+            let mut line = format!("  {}: {}", index, frame.function.symbol);
+            if let Some((file, span)) = span(frame) {
+                if let Ok(location) = files.location(file, span.start) {
+                    line.push_str(&format!(" at {}:{}:{}", map.files[file].0, location.line_number, location.column_number));
+                }
+                // A recursive call is only labelled once
+                let labelled = labels.iter().any(|label: &Label<usize>| label.file_id == file && label.range == span);
+                if index == 0 {
+                    labels.push(Label::primary(file, span).with_message(format!("in `{}`", frame.function.symbol)));
+                } else if !labelled {
+                    let called = &self.frames[index - 1].function.symbol;
+                    labels.push(Label::secondary(file, span).with_message(format!("`{}` called `{}` here", frame.function.symbol, called)));
+                }
+            }
+            stack.push(line);
+        }
+        if self.frames.len() > MAX_FRAMES {
+            stack.push(format!("  ... and {} more", self.frames.len() - MAX_FRAMES));
+        }
+
+        let diagnostic = Diagnostic::error()
+            .with_message(self.message.as_str())
+            .with_labels(labels)
+            .with_notes(vec![stack.join("\n")]);
+        let writer = StandardStream::stderr(ColorChoice::Always);
+        let config = codespan_reporting::term::Config::default();
+        codespan_reporting::term::emit(&mut writer.lock(), &config, &files, &diagnostic).ok();
+    }
+}
+
+fn trap_message(code: TrapCode) -> String {
+    match code {
+        TrapCode::IntegerDivisionByZero => "division by zero".to_owned(),
+        TrapCode::IntegerOverflow => "integer overflow".to_owned(),
+        TrapCode::StackOverflow => "stack overflow".to_owned(),
+        TrapCode::UnreachableCodeReached => "reached code that should be unreachable".to_owned(),
+        MISSING_KEY => "the map doesn't have the key".to_owned(),
+        OUT_OF_BOUNDS => "the index is past the end of the list".to_owned(),
+        code => format!("trap: {}", code),
+    }
+}
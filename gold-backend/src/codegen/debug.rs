@@ -0,0 +1,754 @@
+//! Debug info for compiled code, so gdb can break on Gold lines and show
+//! Gold variables.
+//!
+//! The DWARF is built from the source map once every function has an address:
+//! a line table from the spans code was tagged with, a subprogram for each
+//! function under the name it was declared with, and its variables wherever
+//! Cranelift kept the values of their slots. Under the JIT, it is wrapped in
+//! an ELF file in memory and handed to gdb through its JIT interface, which gdb
+//! reads when it is attached and nothing reads otherwise. A program compiled
+//! ahead of time gets it as sections of its object file instead, which refer
+//! to the functions by symbol and are linked into the executable.
+
+use std::collections::HashMap;
+use std::env;
+use std::ops::Range;
+use std::ptr;
+use std::sync::Mutex;
+
+use cranelift::codegen::binemit::CodeOffset;
+use cranelift::codegen::ir::{LabelValueLoc, ValueLabel};
+use cranelift::codegen::isa::unwind::UnwindInfo;
+use cranelift::codegen::isa::TargetIsa;
+use cranelift::codegen::Context;
+use cranelift::prelude::EntityRef;
+use cranelift_module::FuncId;
+use gimli::write::{
+    Address, AttributeValue, Dwarf, EndianVec, Expression, FileId, FrameTable, LineProgram, LineString, Location,
+    LocationList, Range as AddressRange, RangeList, Result as WriteResult, Sections, StringTable, Unit, UnitEntryId,
+    Writer,
+};
+use gimli::{constants, Encoding, Format, LineEncoding, LittleEndian, Register, SectionId};
+use target_lexicon::Architecture;
+
+use gold_frontend::frontend::Type;
+
+use super::source_map::{CompiledFunction, SourceMap};
+use crate::lir;
+
+const ENCODING: Encoding = Encoding { format: Format::Dwarf32, version: 4, address_size: 8 };
+
+/// What debug info needs to know about a function besides what the source
+/// map has.
+struct DebugFunction {
+    id: FuncId,
+    name: String,
+    body: Option<Range<usize>>,
+    returns: Type,
+    variables: Vec<Variable>,
+    unwind: Option<UnwindInfo>,
+}
+
+struct Variable {
+    name: String,
+    ty: Type,
+    param: bool,
+    // Where the value of the slot is at each range of code offsets
+    places: Vec<(Range<CodeOffset>, Place)>,
+}
+
+enum Place {
+    /// A DWARF register number
+    Register(u16),
+    /// An offset from the stack pointer
+    Stack(i64),
+}
+
+/// A debug section of an object file, and where the linker has to fill in
+/// what it refers to.
+pub struct DebugSection {
+    pub name: &'static str,
+    pub data: Vec<u8>,
+    pub relocs: Vec<DebugReloc>,
+}
+
+/// The address of a function, or an offset into another debug section, at an
+/// offset into a debug section.
+#[derive(Clone)]
+pub struct DebugReloc {
+    pub offset: u32,
+    /// In bytes
+    pub size: u8,
+    pub target: DebugTarget,
+    pub addend: i64,
+}
+
+#[derive(Clone, Copy)]
+pub enum DebugTarget {
+    Function(FuncId),
+    Section(&'static str),
+}
+
+/// The debug info of every function compiled so far.
+#[derive(Default)]
+pub struct DebugInfo {
+    functions: Vec<DebugFunction>,
+}
+
+impl DebugInfo {
+    /// Records the names and places of a function's variables once it's
+    /// compiled. Cranelift only tracks them when `collect_debug_info` was
+    /// called on the function before building it.
+    pub fn add_function(&mut self, function: &lir::Function, id: FuncId, context: &Context, isa: &dyn TargetIsa) {
+        let result = context.mach_compile_result.as_ref().unwrap();
+        let variables = function.names.iter().zip(&function.slots).enumerate()
+            // The slots the lowering keeps values in on its own have no name
+            .filter(|(_, (name, ty))| **ty != Type::Void && !name.is_empty())
+            .map(|(index, (name, ty))| {
+                let ranges = result.value_labels_ranges.get(&ValueLabel::new(index));
+                let places = ranges.into_iter().flatten().filter_map(|range| {
+                    let place = match range.loc {
+                        LabelValueLoc::Reg(reg) => Place::Register(isa.map_regalloc_reg_to_dwarf(reg).ok()?),
+                        LabelValueLoc::SPOffset(offset) => Place::Stack(offset),
+                        LabelValueLoc::ValueLoc(_) => return None,
+                    };
+                    Some((range.start..range.end, place))
+                }).collect();
+                Variable {
+                    name: name.to_owned(),
+                    ty: ty.clone(),
+                    param: index < function.signature.params.len(),
+                    places,
+                }
+            })
+            .collect();
+        self.functions.push(DebugFunction {
+            id,
+            name: function.name.to_owned(),
+            body: function.body.span.to_owned(),
+            returns: function.signature.returns.clone(),
+            variables,
+            unwind: context.create_unwind_info(isa).ok().flatten(),
+        });
+    }
+
+    /// An ELF file with the DWARF of every function, at the addresses in the
+    /// source map.
+    pub fn object(&self, map: &SourceMap, isa: &dyn TargetIsa) -> Vec<u8> {
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        let functions = self.write(&mut sections, map, isa, |compiled| Address::Constant(compiled.address as u64));
+
+        let mut debug_sections = Vec::new();
+        sections.for_each(|id, data| {
+            if !data.slice().is_empty() {
+                debug_sections.push((id.name(), data.slice().to_vec()));
+            }
+            Ok::<(), ()>(())
+        }).unwrap();
+        let symbols = functions.iter()
+            .map(|compiled| (compiled.symbol.as_str(), compiled.address as u64, compiled.size as u64))
+            .collect::<Vec<_>>();
+        elf(isa.triple().architecture, &debug_sections, &symbols)
+    }
+
+    /// The DWARF of every function as sections of an object file, where the
+    /// functions are at their symbols.
+    pub fn sections(&self, map: &SourceMap, isa: &dyn TargetIsa) -> Vec<DebugSection> {
+        let mut sections = Sections::new(Relocating::default());
+        let symbol = |compiled: &CompiledFunction| Address::Symbol { symbol: compiled.id.as_u32() as usize, addend: 0 };
+        self.write(&mut sections, map, isa, symbol);
+
+        let mut debug_sections = Vec::new();
+        sections.for_each(|id, section| {
+            if section.data.len() > 0 {
+                debug_sections.push(DebugSection {
+                    name: id.name(),
+                    data: section.data.slice().to_vec(),
+                    relocs: section.relocs.clone(),
+                });
+            }
+            Ok::<(), ()>(())
+        }).unwrap();
+        debug_sections
+    }
+
+    // Writes the DWARF of every function in the source map, giving back the
+    // functions it's for
+    fn write<'a, W: Writer>(
+        &self,
+        sections: &mut Sections<W>,
+        map: &'a SourceMap,
+        isa: &dyn TargetIsa,
+        start: impl Fn(&CompiledFunction) -> Address,
+    ) -> Vec<&'a CompiledFunction> {
+        let functions = self.functions.iter()
+            .filter_map(|debug| Some((debug, map.functions.iter().find(|compiled| compiled.id == debug.id)?)))
+            .collect::<Vec<_>>();
+        let stack_pointer = match isa.triple().architecture {
+            Architecture::X86_64 => Some(gimli::X86_64::RSP),
+            // gimli has no names for the registers of aarch64 yet
+            Architecture::Aarch64(_) => Some(Register(31)),
+            _ => None,
+        };
+
+        // A unit for every file, with the functions declared in it
+        let mut dwarf = Dwarf::new();
+        let comp_dir = env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_else(|_| ".".to_owned());
+        for (file, (name, source)) in map.files.iter().enumerate() {
+            let functions = functions.iter().filter(|(_, compiled)| compiled.file == file).collect::<Vec<_>>();
+            if functions.is_empty() {
+                continue;
+            }
+            let lines = Lines::new(source);
+            let program = LineProgram::new(
+                ENCODING,
+                LineEncoding::default(),
+                LineString::String(comp_dir.as_bytes().to_vec()),
+                LineString::String(name.as_bytes().to_vec()),
+                None,
+            );
+            let unit_id = dwarf.units.add(Unit::new(ENCODING, program));
+            let mut unit = UnitWriter {
+                unit: dwarf.units.get_mut(unit_id),
+                strings: &mut dwarf.strings,
+                types: HashMap::new(),
+                stack_pointer,
+            };
+            let file_id = unit.begin(name, &comp_dir);
+            let ranges = functions.iter()
+                .map(|(debug, compiled)| unit.add_function(debug, compiled, start(compiled), map, &lines, file_id))
+                .collect();
+            unit.end(ranges);
+        }
+
+        dwarf.write(sections).unwrap();
+        // Lets gdb find the callers of a Gold function
+        let mut frames = FrameTable::default();
+        if let Some(cie) = isa.create_systemv_cie() {
+            let cie = frames.add_cie(cie);
+            for (debug, compiled) in &functions {
+                if let Some(UnwindInfo::SystemV(unwind)) = &debug.unwind {
+                    frames.add_fde(cie, unwind.to_fde(start(compiled)));
+                }
+            }
+        }
+        frames.write_debug_frame(&mut sections.debug_frame).unwrap();
+        functions.into_iter().map(|(_, compiled)| compiled).collect()
+    }
+}
+
+// An address some way into a function
+fn offset(address: Address, by: u64) -> Address {
+    match address {
+        Address::Constant(address) => Address::Constant(address + by),
+        Address::Symbol { symbol, addend } => Address::Symbol { symbol, addend: addend + by as i64 },
+    }
+}
+
+// Writes a section of an object file, keeping track of the addresses and
+// offsets the linker has to fill in. Addresses are only ever at the symbol of
+// a function, which is its id.
+#[derive(Clone)]
+struct Relocating {
+    data: EndianVec<LittleEndian>,
+    relocs: Vec<DebugReloc>,
+}
+
+impl Default for Relocating {
+    fn default() -> Self {
+        Relocating { data: EndianVec::new(LittleEndian), relocs: Vec::new() }
+    }
+}
+
+impl Writer for Relocating {
+    type Endian = LittleEndian;
+
+    fn endian(&self) -> LittleEndian {
+        LittleEndian
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> WriteResult<()> {
+        self.data.write(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> WriteResult<()> {
+        self.data.write_at(offset, bytes)
+    }
+
+    fn write_address(&mut self, address: Address, size: u8) -> WriteResult<()> {
+        match address {
+            Address::Constant(address) => self.write_udata(address, size),
+            Address::Symbol { symbol, addend } => {
+                let target = DebugTarget::Function(FuncId::from_u32(symbol as u32));
+                self.relocs.push(DebugReloc { offset: self.len() as u32, size, target, addend });
+                self.write_udata(0, size)
+            }
+        }
+    }
+
+    // The linker puts the sections of every object file together, so offsets
+    // into them are only known then
+    fn write_offset(&mut self, value: usize, section: SectionId, size: u8) -> WriteResult<()> {
+        let target = DebugTarget::Section(section.name());
+        self.relocs.push(DebugReloc { offset: self.len() as u32, size, target, addend: value as i64 });
+        self.write_udata(value as u64, size)
+    }
+
+    fn write_offset_at(&mut self, offset: usize, value: usize, section: SectionId, size: u8) -> WriteResult<()> {
+        let target = DebugTarget::Section(section.name());
+        self.relocs.push(DebugReloc { offset: offset as u32, size, target, addend: value as i64 });
+        self.write_udata_at(offset, value as u64, size)
+    }
+}
+
+// Where each line of a file starts
+struct Lines(Vec<usize>);
+
+impl Lines {
+    fn new(source: &str) -> Self {
+        Lines(Some(0).into_iter().chain(source.match_indices('\n').map(|(index, _)| index + 1)).collect())
+    }
+
+    /// The line and column of an offset into the file, both counted from one.
+    fn location(&self, offset: usize) -> (u64, u64) {
+        let line = self.0.partition_point(|start| *start <= offset);
+        (line as u64, (offset - self.0[line - 1] + 1) as u64)
+    }
+}
+
+struct UnitWriter<'a> {
+    unit: &'a mut Unit,
+    strings: &'a mut StringTable,
+    // Types are described once per unit, by name
+    types: HashMap<String, UnitEntryId>,
+    stack_pointer: Option<Register>,
+}
+
+impl<'a> UnitWriter<'a> {
+    // Describes the file the unit is for, giving its entry in the line table
+    fn begin(&mut self, name: &str, comp_dir: &str) -> FileId {
+        let root = self.unit.root();
+        let producer = self.strings.add("gold");
+        let name_id = self.strings.add(name);
+        let comp_dir = self.strings.add(comp_dir);
+        let entry = self.unit.get_mut(root);
+        entry.set(constants::DW_AT_producer, AttributeValue::StringRef(producer));
+        // The closest language gdb knows, so variables can be printed
+        entry.set(constants::DW_AT_language, AttributeValue::Language(constants::DW_LANG_C99));
+        entry.set(constants::DW_AT_name, AttributeValue::StringRef(name_id));
+        entry.set(constants::DW_AT_comp_dir, AttributeValue::StringRef(comp_dir));
+        let directory = self.unit.line_program.default_directory();
+        self.unit.line_program.add_file(LineString::String(name.as_bytes().to_vec()), directory, None)
+    }
+
+    // Gives the unit the code of all of its functions
+    fn end(&mut self, ranges: Vec<AddressRange>) {
+        let ranges = self.unit.ranges.add(RangeList(ranges));
+        let root = self.unit.root();
+        let entry = self.unit.get_mut(root);
+        entry.set(constants::DW_AT_low_pc, AttributeValue::Address(Address::Constant(0)));
+        entry.set(constants::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+    }
+
+    // Adds the lines and the subprogram of a function, giving the code it
+    // takes up
+    fn add_function(&mut self, debug: &DebugFunction, compiled: &CompiledFunction, address: Address, map: &SourceMap, lines: &Lines, file: FileId) -> AddressRange {
+        let program = &mut self.unit.line_program;
+        program.begin_sequence(Some(address));
+        let mut row = |offset: CodeOffset, (line, column): (u64, u64)| {
+            let row = program.row();
+            row.address_offset = offset as u64;
+            row.file = file;
+            row.line = line;
+            row.column = column;
+            program.generate_row();
+        };
+        // The prologue belongs to the line the function starts on, so gdb
+        // stops after it on a breakpoint for the function
+        let start = debug.body.as_ref().map(|body| lines.location(body.start));
+        if let Some(start) = start {
+            row(0, start);
+        }
+        for (range, srcloc) in &compiled.srclocs {
+            if let Some((_, span)) = map.span(*srcloc) {
+                row(range.start, lines.location(span.start));
+            }
+        }
+        program.end_sequence(compiled.size as u64);
+
+        let root = self.unit.root();
+        let id = self.unit.add(root, constants::DW_TAG_subprogram);
+        let name = self.strings.add(debug.name.as_str());
+        let returns = self.type_id(&debug.returns);
+        let entry = self.unit.get_mut(id);
+        entry.set(constants::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(constants::DW_AT_external, AttributeValue::Flag(true));
+        entry.set(constants::DW_AT_low_pc, AttributeValue::Address(address));
+        entry.set(constants::DW_AT_high_pc, AttributeValue::Udata(compiled.size as u64));
+        let mut frame_base = Expression::new();
+        frame_base.op(constants::DW_OP_call_frame_cfa);
+        entry.set(constants::DW_AT_frame_base, AttributeValue::Exprloc(frame_base));
+        if let Some((line, _)) = start {
+            entry.set(constants::DW_AT_decl_file, AttributeValue::FileIndex(Some(file)));
+            entry.set(constants::DW_AT_decl_line, AttributeValue::Udata(line));
+        }
+        if let Some(returns) = returns {
+            entry.set(constants::DW_AT_type, AttributeValue::UnitRef(returns));
+        }
+        // Functions from used modules are compiled under a qualified name
+        if compiled.symbol != debug.name {
+            let symbol = self.strings.add(compiled.symbol.as_str());
+            self.unit.get_mut(id).set(constants::DW_AT_linkage_name, AttributeValue::StringRef(symbol));
+        }
+
+        for variable in &debug.variables {
+            self.add_variable(id, variable, address);
+        }
+        AddressRange::StartLength { begin: address, length: compiled.size as u64 }
+    }
+
+    fn add_variable(&mut self, function: UnitEntryId, variable: &Variable, address: Address) {
+        let tag = if variable.param { constants::DW_TAG_formal_parameter } else { constants::DW_TAG_variable };
+        let id = self.unit.add(function, tag);
+        let name = self.strings.add(variable.name.as_str());
+        let ty = self.type_id(&variable.ty);
+        let locations = variable.places.iter().filter_map(|(range, place)| {
+            let mut expression = Expression::new();
+            match place {
+                Place::Register(register) => expression.op_reg(Register(*register)),
+                Place::Stack(offset) => expression.op_breg(self.stack_pointer?, *offset),
+            }
+            Some(Location::StartLength {
+                begin: offset(address, range.start as u64),
+                length: (range.end - range.start) as u64,
+                data: expression,
+            })
+        }).collect::<Vec<_>>();
+        // A variable without a location is shown as optimized out
+        let locations = if locations.is_empty() { None } else { Some(self.unit.locations.add(LocationList(locations))) };
+
+        let entry = self.unit.get_mut(id);
+        entry.set(constants::DW_AT_name, AttributeValue::StringRef(name));
+        if let Some(ty) = ty {
+            entry.set(constants::DW_AT_type, AttributeValue::UnitRef(ty));
+        }
+        if let Some(locations) = locations {
+            entry.set(constants::DW_AT_location, AttributeValue::LocationListRef(locations));
+        }
+    }
+
+    // The entry for a type, or `None` for `Void`
+    fn type_id(&mut self, ty: &Type) -> Option<UnitEntryId> {
+        let name = ty.to_string();
+        if let Some(id) = self.types.get(&name) {
+            return Some(*id);
+        }
+        let id = match ty {
+            Type::Void => return None,
+            Type::Int => self.base_type(&name, constants::DW_ATE_signed, 8),
+            Type::Float => self.base_type(&name, constants::DW_ATE_float, 4),
+            Type::Bool => self.base_type(&name, constants::DW_ATE_boolean, 1),
+            // Pointers to characters, which gdb shows as the text
+            Type::String | Type::CString => {
+                let char = self.base_type("char", constants::DW_ATE_signed_char, 1);
+                let pointer = self.pointer_type(Some(char));
+                self.typedef(&name, pointer)
+            }
+            // Structures, enums and collections are only shown as where they are
+            _ => {
+                let pointer = self.pointer_type(None);
+                self.typedef(&name, pointer)
+            }
+        };
+        self.types.insert(name, id);
+        Some(id)
+    }
+
+    fn base_type(&mut self, name: &str, encoding: constants::DwAte, size: u8) -> UnitEntryId {
+        let root = self.unit.root();
+        let id = self.unit.add(root, constants::DW_TAG_base_type);
+        let name = self.strings.add(name);
+        let entry = self.unit.get_mut(id);
+        entry.set(constants::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(constants::DW_AT_encoding, AttributeValue::Encoding(encoding));
+        entry.set(constants::DW_AT_byte_size, AttributeValue::Data1(size));
+        id
+    }
+
+    fn pointer_type(&mut self, pointee: Option<UnitEntryId>) -> UnitEntryId {
+        let root = self.unit.root();
+        let id = self.unit.add(root, constants::DW_TAG_pointer_type);
+        let entry = self.unit.get_mut(id);
+        entry.set(constants::DW_AT_byte_size, AttributeValue::Data1(ENCODING.address_size));
+        if let Some(pointee) = pointee {
+            entry.set(constants::DW_AT_type, AttributeValue::UnitRef(pointee));
+        }
+        id
+    }
+
+    fn typedef(&mut self, name: &str, ty: UnitEntryId) -> UnitEntryId {
+        let root = self.unit.root();
+        let id = self.unit.add(root, constants::DW_TAG_typedef);
+        let name = self.strings.add(name);
+        let entry = self.unit.get_mut(id);
+        entry.set(constants::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(constants::DW_AT_type, AttributeValue::UnitRef(ty));
+        id
+    }
+}
+
+/// Wraps debug sections in an ELF file, along with a `.text` section and
+/// symbols for the functions. The code itself is already in memory, so the
+/// `.text` section only says where it is.
+fn elf(architecture: Architecture, debug_sections: &[(&str, Vec<u8>)], symbols: &[(&str, u64, u64)]) -> Vec<u8> {
+    let text = symbols.iter().map(|(_, address, _)| *address).min().unwrap_or(0)
+        ..symbols.iter().map(|(_, address, size)| address + size).max().unwrap_or(0);
+
+    let mut strings = vec![0u8];
+    // Starts with the null symbol
+    let mut symbol_table = vec![0u8; 24];
+    for (symbol, address, size) in symbols {
+        symbol_table.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        strings.extend_from_slice(symbol.as_bytes());
+        strings.push(0);
+        // A global function in .text
+        symbol_table.extend_from_slice(&[0x12, 0]);
+        symbol_table.extend_from_slice(&1u16.to_le_bytes());
+        symbol_table.extend_from_slice(&address.to_le_bytes());
+        symbol_table.extend_from_slice(&size.to_le_bytes());
+    }
+
+    let mut elf = Elf::default();
+    elf.section(".text", Section { kind: SHT_NOBITS, flags: SHF_ALLOC | SHF_EXECINSTR, address: text.start, size: Some(text.end - text.start), ..Section::default() }, &[]);
+    for (name, contents) in debug_sections {
+        elf.section(name, Section::default(), contents);
+    }
+    let strtab = 3 + debug_sections.len() as u32;
+    // Every symbol after the null one is global
+    elf.section(".symtab", Section { kind: SHT_SYMTAB, link: strtab, info: 1, entry_size: 24, ..Section::default() }, &symbol_table);
+    elf.section(".strtab", Section { kind: SHT_STRTAB, ..Section::default() }, &strings);
+    elf.finish(architecture, text)
+}
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+const HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const SECTION_HEADER_SIZE: usize = 64;
+
+struct Section {
+    kind: u32,
+    flags: u64,
+    address: u64,
+    // The size of the contents when `None`
+    size: Option<u64>,
+    link: u32,
+    info: u32,
+    entry_size: u64,
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        Section { kind: SHT_PROGBITS, flags: 0, address: 0, size: None, link: 0, info: 0, entry_size: 0 }
+    }
+}
+
+// A little-endian 64-bit ELF file that is written section by section. The
+// headers are filled in at the start once everything else is there.
+struct Elf {
+    data: Vec<u8>,
+    section_headers: Vec<u8>,
+    names: Vec<u8>,
+}
+
+impl Default for Elf {
+    fn default() -> Self {
+        Elf {
+            data: vec![0; HEADER_SIZE + PROGRAM_HEADER_SIZE],
+            // Starts with the null section
+            section_headers: vec![0; SECTION_HEADER_SIZE],
+            names: vec![0],
+        }
+    }
+}
+
+impl Elf {
+    fn section(&mut self, name: &str, section: Section, contents: &[u8]) {
+        let name_offset = self.names.len() as u32;
+        self.names.extend_from_slice(name.as_bytes());
+        self.names.push(0);
+        self.align();
+        let offset = self.data.len() as u64;
+        self.data.extend_from_slice(contents);
+
+        let header = &mut self.section_headers;
+        header.extend_from_slice(&name_offset.to_le_bytes());
+        header.extend_from_slice(&section.kind.to_le_bytes());
+        header.extend_from_slice(&section.flags.to_le_bytes());
+        header.extend_from_slice(&section.address.to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        header.extend_from_slice(&section.size.unwrap_or(contents.len() as u64).to_le_bytes());
+        header.extend_from_slice(&section.link.to_le_bytes());
+        header.extend_from_slice(&section.info.to_le_bytes());
+        header.extend_from_slice(&1u64.to_le_bytes());
+        header.extend_from_slice(&section.entry_size.to_le_bytes());
+    }
+
+    fn align(&mut self) {
+        while !self.data.len().is_multiple_of(8) {
+            self.data.push(0);
+        }
+    }
+
+    // Adds the section names and the section headers, and fills in the
+    // headers for a shared object loaded where the code is
+    fn finish(mut self, architecture: Architecture, text: Range<u64>) -> Vec<u8> {
+        // Has its own name at the end, where adding the section puts it
+        let names = [&self.names[..], b".shstrtab\0"].concat();
+        self.section(".shstrtab", Section { kind: SHT_STRTAB, ..Section::default() }, &names);
+        self.align();
+        let section_headers = self.data.len() as u64;
+        let section_count = (self.section_headers.len() / SECTION_HEADER_SIZE) as u16;
+        self.data.extend_from_slice(&self.section_headers);
+
+        let machine: u16 = match architecture {
+            Architecture::Aarch64(_) => 183,
+            _ => 62,
+        };
+        let mut header = Vec::with_capacity(HEADER_SIZE + PROGRAM_HEADER_SIZE);
+        header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // ET_DYN
+        header.extend_from_slice(&3u16.to_le_bytes());
+        header.extend_from_slice(&machine.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        header.extend_from_slice(&section_headers.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+        header.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+        header.extend_from_slice(&section_count.to_le_bytes());
+        // .shstrtab is the last section
+        header.extend_from_slice(&(section_count - 1).to_le_bytes());
+        // PT_LOAD, readable and executable, covering the code
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&5u32.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&text.start.to_le_bytes());
+        header.extend_from_slice(&text.start.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        header.extend_from_slice(&(text.end - text.start).to_le_bytes());
+        header.extend_from_slice(&16u64.to_le_bytes());
+        self.data[..header.len()].copy_from_slice(&header);
+        self.data
+    }
+}
+
+// The interface gdb has for code that is compiled while a program runs. gdb
+// puts a breakpoint on the function and reads the entry it's pointed at
+// whenever that is called.
+
+#[repr(C)]
+struct CodeEntry {
+    next: *mut CodeEntry,
+    prev: *mut CodeEntry,
+    object: *const u8,
+    size: u64,
+}
+
+#[repr(C)]
+struct Descriptor {
+    version: u32,
+    action: u32,
+    relevant: *mut CodeEntry,
+    first: *mut CodeEntry,
+}
+
+const REGISTER: u32 = 1;
+const UNREGISTER: u32 = 2;
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+static mut __jit_debug_descriptor: Descriptor = Descriptor {
+    version: 1,
+    action: 0,
+    relevant: ptr::null_mut(),
+    first: ptr::null_mut(),
+};
+
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // Keeps the call from being left out
+    std::hint::black_box(());
+}
+
+// Only one change to the list can be made at a time
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Debug info handed to gdb, which forgets about it when this is dropped.
+pub struct Registration {
+    entry: Box<CodeEntry>,
+    object: Vec<u8>,
+}
+
+impl Registration {
+    pub fn new(object: Vec<u8>) -> Self {
+        let mut entry = Box::new(CodeEntry {
+            next: ptr::null_mut(),
+            prev: ptr::null_mut(),
+            object: object.as_ptr(),
+            size: object.len() as u64,
+        });
+        let _lock = LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = ptr::addr_of_mut!(__jit_debug_descriptor);
+            entry.next = (*descriptor).first;
+            if !entry.next.is_null() {
+                (*entry.next).prev = &mut *entry;
+            }
+            (*descriptor).first = &mut *entry;
+            (*descriptor).relevant = &mut *entry;
+            (*descriptor).action = REGISTER;
+            __jit_debug_register_code();
+        }
+        Registration { entry, object }
+    }
+
+    /// The ELF file gdb was given.
+    pub fn object(&self) -> &[u8] {
+        &self.object
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let _lock = LOCK.lock().unwrap();
+        unsafe {
+            let descriptor = ptr::addr_of_mut!(__jit_debug_descriptor);
+            let entry = &mut *self.entry;
+            if entry.prev.is_null() {
+                (*descriptor).first = entry.next;
+            } else {
+                (*entry.prev).next = entry.next;
+            }
+            if !entry.next.is_null() {
+                (*entry.next).prev = entry.prev;
+            }
+            (*descriptor).relevant = entry;
+            (*descriptor).action = UNREGISTER;
+            __jit_debug_register_code();
+        }
+    }
+}
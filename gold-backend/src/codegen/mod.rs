@@ -1,321 +1,951 @@
-use std::{any::Any, collections::HashMap, mem, ops::Range};
+use std::{collections::HashMap, mem, slice};
 
-use cranelift::{codegen::{Context, ir::function}, codegen, frontend::{FunctionBuilder, FunctionBuilderContext, Variable}, prelude::{AbiParam, Block, EntityRef, InstBuilder, IntCC, Signature, Value, types}};
-use cranelift::codegen::binemit::NullStackMapSink;
+use cranelift::{codegen, frontend::{FunctionBuilder, FunctionBuilderContext, Variable}, prelude::{AbiParam, EntityRef, isa::TargetIsa, FloatCC, InstBuilder, IntCC, JumpTableData, MemFlags, TrapCode, Value, types}};
+use cranelift::codegen::binemit::{NullStackMapSink, NullTrapSink};
+use cranelift::codegen::ir::{SourceLoc, ValueLabel};
 use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{DataContext, Linkage, Module};
+use cranelift_module::{DataContext, FuncId, Linkage, Module};
 
-use gold_frontend::frontend::{Expr, Parameter, Type};
-use gold_standard::{declare_functions, load_symbols};
+use gold_frontend::frontend::Type;
+use gold_standard::{declare_functions, ffi, load_symbols};
+use gold_standard::load::{ABS_FLOAT_SYMBOL, ABS_INT_SYMBOL, ALLOC_SYMBOL, ALLOC_WIDE_SYMBOL, CATCH_CRASHES_SYMBOL, CEIL_SYMBOL, CLAMP_FLOAT_SYMBOL, CLAMP_INT_SYMBOL, EXPORT_PREFIX, FLOOR_SYMBOL, FLUSH_SYMBOL, LIST_GET_SYMBOL, LIST_LEN_SYMBOL, MAP_CONTAINS_SYMBOL, MAP_GET_SYMBOL, MAX_FLOAT_SYMBOL, MAX_INT_SYMBOL, MIN_FLOAT_SYMBOL, MIN_INT_SYMBOL, PRINT_RESULT_FLOAT_SYMBOL, PRINT_RESULT_INT_SYMBOL, SQRT_SYMBOL, SYMBOLS, TO_FLOAT_SYMBOL, TO_INT_SYMBOL};
 
-use crate::analysis::lower::{Analyzer, Lower, VarSig};
-use std::cell::RefCell;
-use std::borrow::BorrowMut;
+use crate::lir::{self, BinaryOp, Callee, Kind, Node, Signature};
+use crate::lir::config::Config;
+
+use self::crash::{MISSING_KEY, OUT_OF_BOUNDS};
+use self::debug::{DebugInfo, Registration};
+use self::object::ObjectModule;
+use self::refcount::counted;
+use self::source_map::{SourceMap, Traps};
+
+pub mod crash;
+pub mod debug;
+pub mod object;
+pub mod refcount;
+pub mod source_map;
 
 pub struct Compilation {
-    variables: HashMap<String, Variable>,
-    variable_index: usize,
+    program: lir::Module,
 
-    uncompiled_functions: Vec<Expr>,
-    syntax_analyzer: Analyzer,
+    // Modules used by the program, compiled before it
+    modules: Vec<lir::Module>,
 
     data_context: DataContext,
+    builtins: HashMap<&'static str, FuncId>,
+
+    // The slot types of the function being compiled
+    slots: Vec<Type>,
+    // Set while generating a store whose value isn't used, so it isn't
+    // retained for nothing
+    unused: bool,
+    // Where `continue` and `break` jump to, for each loop around the node
+    // being generated
+    loops: Vec<(cranelift::prelude::Block, cranelift::prelude::Block)>,
+
+    // Functions provided by the host application
+    symbols: Vec<(String, *const u8)>,
+
+    config: Config,
+    // Runs this function instead of the usual entry point
+    entry: Option<String>,
+    // The instruction set assembly is emitted for, along with what has been
+    // emitted so far
+    assembly: Option<(Box<dyn TargetIsa>, String)>,
+
+    source_map: SourceMap,
+    // The file of the module being compiled, in the source map, and the
+    // source location instructions are tagged with
+    file: usize,
+    srcloc: SourceLoc,
+    // Collected while compiling when the config asks for debug info
+    debug: Option<DebugInfo>,
+}
+
+/// A compiled program, ready to be called into.
+pub struct Compiled {
+    module: JITModule,
+    functions: HashMap<String, (FuncId, Signature)>,
+    // The function the program starts at
+    entry: Option<String>,
+    source_map: SourceMap,
+    debug: Option<Registration>,
+}
+
+impl Compiled {
+    /// The address and signature of a function declared in the program
+    /// itself, rather than in a module it uses. The address stays valid for as
+    /// long as this is around.
+    pub fn function(&self, name: &str) -> Option<(*const u8, &Signature)> {
+        let (id, sig) = self.functions.get(name)?;
+        Some((self.module.get_finalized_function(*id), sig))
+    }
+
+    /// The ELF file with the debug info gdb was given, when it was asked for.
+    pub fn debug_object(&self) -> Option<&[u8]> {
+        self.debug.as_ref().map(Registration::object)
+    }
+
+    /// Runs the entry point, printing what it returns. A crash is reported
+    /// with the Gold functions that were running, and exits with
+    /// `crash::CRASH_EXIT_CODE`.
+    pub fn run(&self) -> Result<(), String> {
+        if let Some(name) = &self.entry {
+            let (code, signature) = self.function(name).unwrap();
+            signature.check_entry(name)?;
+            // Parameters it doesn't take are ignored
+            match signature.returns {
+                Type::Void => {
+                    let code_fn = unsafe { mem::transmute::<*const u8, fn(i64, i64)>(code) };
+                    crash::guard(&self.source_map, || code_fn(1, 2));
+                }
+                Type::Float => {
+                    let code_fn = unsafe { mem::transmute::<*const u8, fn(i64, i64) -> f32>(code) };
+                    println!("{}", crash::guard(&self.source_map, || code_fn(1, 2)));
+                }
+                Type::Bool => {
+                    let code_fn = unsafe { mem::transmute::<*const u8, fn(i64, i64) -> bool>(code) };
+                    println!("{}", crash::guard(&self.source_map, || code_fn(1, 2)) as i64);
+                }
+                _ => {
+                    let code_fn = unsafe { mem::transmute::<*const u8, fn(i64, i64) -> i64>(code) };
+                    println!("{}", crash::guard(&self.source_map, || code_fn(1, 2)));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Compilation {
-    pub fn new(analyzer: Analyzer, functions: Vec<Expr>) -> Self {
+    pub fn new(program: lir::Module) -> Self {
         Self {
-            variables: HashMap::new(),
-            variable_index: 0,
-            uncompiled_functions: functions,
-            syntax_analyzer: analyzer,
-            data_context: DataContext::new()
+            program,
+            modules: Vec::new(),
+            data_context: DataContext::new(),
+            builtins: HashMap::new(),
+            slots: Vec::new(),
+            unused: false,
+            loops: Vec::new(),
+            symbols: Vec::new(),
+            config: Config::default(),
+            entry: None,
+            assembly: None,
+            source_map: SourceMap::default(),
+            file: 0,
+            srcloc: SourceLoc::default(),
+            debug: None,
         }
     }
 
-    pub fn gen_expr(&mut self,
-                scope_index: &usize,
-                expr: &Expr,
-                module: &mut JITModule,
-                builder: &mut FunctionBuilder) -> Value {
-        match expr {
-            Expr::Number(lit, _) => {
-                builder.ins().iconst(types::I64, *lit)
-            }
-            Expr::String(lit, _) => {
-                self.data_context.define(lit.to_string().into_boxed_str().into());
-                let data = module.declare_data(lit, Linkage::Export, true, false).unwrap();
+    /// Changes how the program is compiled. The JIT always compiles for the
+    /// host, so another target only matters for `emit_assembly` and
+    /// `emit_object`.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Runs a function of the program, such as a test, instead of its entry
+    /// point.
+    pub fn set_entry(&mut self, name: &str) {
+        self.entry = Some(name.to_owned());
+    }
+
+    /// Adds a module used by the program. Modules have to be added after
+    /// everything they use.
+    pub fn add_module(&mut self, module: lir::Module) {
+        self.modules.push(module);
+    }
+
+    pub fn gen_node(&mut self, node: &Node, module: &mut dyn Module, builder: &mut FunctionBuilder) -> Option<Value> {
+        // The instructions of a node are tagged with where it is in the
+        // source, until a node inside it says otherwise
+        let span = match &node.span {
+            Some(span) => span.to_owned(),
+            None => return self.gen_kind(node, module, builder),
+        };
+        let outer = self.srcloc;
+        self.srcloc = self.source_map.srcloc(self.file, span);
+        builder.set_srcloc(self.srcloc);
+        let value = self.gen_kind(node, module, builder);
+        self.srcloc = outer;
+        builder.set_srcloc(outer);
+        value
+    }
+
+    fn gen_kind(&mut self, node: &Node, module: &mut dyn Module, builder: &mut FunctionBuilder) -> Option<Value> {
+        let value = match &node.kind {
+            Kind::Int(lit) => builder.ins().iconst(types::I64, *lit),
+            Kind::Bool(lit) => builder.ins().bconst(types::B1, *lit),
+            Kind::String(lit) => {
+                // The runtime works with C strings, so keep the nul terminator
+                let mut bytes = lit.as_bytes().to_vec();
+                bytes.push(0);
+                self.data_context.define(bytes.into_boxed_slice());
+                let data = module.declare_anonymous_data(false, false).unwrap();
                 module.define_data(data, &self.data_context).unwrap();
                 self.data_context.clear();
-                module.finalize_definitions();
 
                 let data_id = module.declare_data_in_func(data, builder.func);
 
                 let string = module.target_config().pointer_type();
                 builder.ins().symbol_value(string, data_id)
             }
-            Expr::Var(name, _) => {
-                let var_sig = self.syntax_analyzer.variables.get(*scope_index, name.to_owned());
-                let var = self.variables.entry(name.to_string()).or_insert({
-                    let var = Variable::new(self.variable_index);
-                    builder.declare_var(var, var_sig.ty.into());
-                    var
-                });
-                builder.use_var(*var)
+            Kind::Address(symbol, signature) => {
+                let sig = make_signature(signature, module);
+                let func = module.declare_function(symbol, Linkage::Import, &sig).unwrap();
+                let func_ref = module.declare_func_in_func(func, builder.func);
+                builder.ins().func_addr(module.target_config().pointer_type(), func_ref)
+            }
+            Kind::Load(_) if node.ty == Type::Void => return None,
+            Kind::Load(slot) => {
+                let value = builder.use_var(Variable::new(slot.0));
+                self.retain(value, &node.ty, module, builder);
+                value
+            }
+            Kind::Store(slot, value) => {
+                let unused = mem::take(&mut self.unused);
+                let ty = &value.ty;
+                let value = self.gen_node(value, module, builder)?;
+                let previous = builder.use_var(Variable::new(slot.0));
+                def_slot(slot.0, value, builder);
+                self.release(previous, ty, module, builder);
+                if !unused {
+                    self.retain(value, ty, module, builder);
+                }
+                value
+            }
+            Kind::Binary(op, lhs, rhs) => {
+                let mut owned = Vec::new();
+                let left = self.gen_borrowed(lhs, slice::from_ref(rhs), &mut owned, module, builder)?;
+                let right = self.gen_borrowed(rhs, &[], &mut owned, module, builder)?;
+                let value = gen_binary(*op, &lhs.ty, left, right, builder);
+                self.release_owned(owned, module, builder);
+                value
+            }
+            Kind::Negate(value) => {
+                let value = self.gen_node(value, module, builder)?;
+                match node.ty {
+                    Type::Float => builder.ins().fneg(value),
+                    _ => builder.ins().ineg(value),
+                }
+            }
+            Kind::Call(callee, args) => {
+                let mut owned = Vec::new();
+                let args = args.iter().enumerate()
+                    .map(|(index, arg)| self.gen_borrowed(arg, &args[index + 1..], &mut owned, module, builder).unwrap())
+                    .collect::<Vec<Value>>();
+                let value = match callee {
+                    Callee::Builtin(name) => match gen_intrinsic(name, &args, builder) {
+                        Some(value) => Some(value),
+                        None => {
+                            self.check_present(name, &args, module, builder);
+                            self.call_builtin(name, &args, module, builder).map(|value| from_slot(value, &node.ty, builder))
+                        }
+                    },
+                    Callee::Function(symbol) => {
+                        let mut fn_sig = module.make_signature();
+                        for arg in &args {
+                            fn_sig.params.push(cranelift::prelude::AbiParam::new(builder.func.dfg.value_type(*arg)));
+                        }
+                        if node.ty != Type::Void {
+                            fn_sig.returns.push(node.ty.clone().into());
+                        }
+                        let func = module.declare_function(symbol, Linkage::Import, &fn_sig).unwrap();
+                        let func_ref = module.declare_func_in_func(func, builder.func);
+                        let call = builder.ins().call(func_ref, &args);
+                        builder.inst_results(call).first().copied()
+                    }
+                    Callee::Closure => {
+                        let mut fn_sig = module.make_signature();
+                        for arg in &args {
+                            fn_sig.params.push(cranelift::prelude::AbiParam::new(builder.func.dfg.value_type(*arg)));
+                        }
+                        if node.ty != Type::Void {
+                            fn_sig.returns.push(node.ty.clone().into());
+                        }
+                        let sig_ref = builder.import_signature(fn_sig);
+                        let function = load_field(args[0], 0, &Type::Pointer, builder);
+                        let call = builder.ins().call_indirect(sig_ref, function, &args);
+                        builder.inst_results(call).first().copied()
+                    }
+                };
+                self.release_owned(owned, module, builder);
+                return value;
             }
-            Expr::Assign(name, value, _) => {
-                let val = self.gen_expr(scope_index, value, module, builder);
-                let var_sig = self.syntax_analyzer.variables.get(*scope_index, name.to_owned());
-                let var = self.variables.entry(name.to_string()).or_insert({
-                    let var = Variable::new(self.variable_index);
-                    builder.declare_var(var, var_sig.ty.into());
-                    var
-                });
-                builder.def_var(*var, val);
-                val
+            Kind::Allocate(tag, fields) => {
+                // Every field gets its own slot, in the order they were declared,
+                // after the tag when there is one
+                let first = tag.is_some() as usize;
+                let size = builder.ins().iconst(types::I64, FIELD_SIZE * (fields.len() + first) as i64);
+                // The runtime releases the fields that are counted along with it,
+                // which it's told about a word of 64 fields at a time
+                let mut pointers = vec![0u64; (fields.len() + first).div_ceil(64)];
+                for (index, _) in fields.iter().enumerate().filter(|(_, field)| counted(&field.ty)) {
+                    pointers[(index + first) / 64] |= 1 << ((index + first) % 64);
+                }
+                let record = if pointers.len() <= 1 {
+                    let pointers = builder.ins().iconst(types::I64, pointers.first().copied().unwrap_or(0) as i64);
+                    self.call_builtin(ALLOC_SYMBOL, &[size, pointers], module, builder).unwrap()
+                } else {
+                    let bytes = pointers.iter().flat_map(|word| word.to_ne_bytes().to_vec()).collect::<Vec<u8>>();
+                    self.data_context.define(bytes.into_boxed_slice());
+                    // The runtime reads it a word at a time
+                    self.data_context.set_align(8);
+                    let data = module.declare_anonymous_data(false, false).unwrap();
+                    module.define_data(data, &self.data_context).unwrap();
+                    self.data_context.clear();
+                    let data_id = module.declare_data_in_func(data, builder.func);
+                    let pointers = builder.ins().symbol_value(module.target_config().pointer_type(), data_id);
+                    self.call_builtin(ALLOC_WIDE_SYMBOL, &[size, pointers], module, builder).unwrap()
+                };
+                if let Some(tag) = tag {
+                    let tag = builder.ins().iconst(types::I64, *tag as i64);
+                    store_field(record, 0, tag, builder);
+                }
+                for (index, field) in fields.iter().enumerate() {
+                    let value = self.gen_node(field, module, builder)?;
+                    store_field(record, index + first, value, builder);
+                }
+                record
             }
-            Expr::Reassign(name, value, _) => {
-                let val = self.gen_expr(scope_index, value, module, builder);
-                let var = self.variables.get(name).unwrap();
-                builder.def_var(*var, val);
-                val
+            Kind::GetField(record, index) => {
+                let mut owned = Vec::new();
+                let record = self.gen_borrowed(record, &[], &mut owned, module, builder)?;
+                let value = load_field(record, *index, &node.ty, builder);
+                self.retain(value, &node.ty, module, builder);
+                self.release_owned(owned, module, builder);
+                value
             }
-            Expr::Block(stmts, _) => {
-                for pos in 0..stmts.len() - 1 {
-                    let stmt = stmts.get(pos).unwrap();
-                    self.gen_expr(scope_index, stmt, module, builder);
+            Kind::SetField(record, index, value) => {
+                let unused = mem::take(&mut self.unused);
+                let ty = &value.ty;
+                let mut owned = Vec::new();
+                let record = self.gen_borrowed(record, slice::from_ref(value), &mut owned, module, builder)?;
+                let value = self.gen_node(value, module, builder)?;
+                if counted(ty) {
+                    let previous = load_field(record, *index, ty, builder);
+                    store_field(record, *index, value, builder);
+                    self.release(previous, ty, module, builder);
+                } else {
+                    store_field(record, *index, value, builder);
+                }
+                self.release_owned(owned, module, builder);
+                if !unused {
+                    self.retain(value, ty, module, builder);
                 }
-                self.gen_expr(scope_index, stmts.get(stmts.len() - 1).unwrap(), module, builder)
+                value
             }
-            Expr::Else(_, _) => todo!(),
-            Expr::Elif(_, _, _) => todo!(),
-            Expr::If(_, _, _, _, _) => todo!(),
-            Expr::Call(name, args, _, _) => {
-                let mut fn_sig = module.make_signature();
-                let mut arg_values = Vec::new();
-                for arg in args {
-                    arg_values.push(self.gen_expr(scope_index, arg, module, builder));
-                    fn_sig.params.push(arg.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into());
+            Kind::If(cond, body, otherwise) => {
+                let condition = self.gen_node(cond, module, builder)?;
+                let then_block = builder.create_block();
+                let else_block = builder.create_block();
+                let merge = builder.create_block();
+                if node.ty != Type::Void {
+                    builder.append_block_param(merge, node.ty.clone().into());
+                }
+                builder.ins().brz(condition, else_block, &[]);
+                builder.ins().jump(then_block, &[]);
+
+                for (block, branch) in [(then_block, body), (else_block, otherwise)] {
+                    builder.switch_to_block(block);
+                    builder.seal_block(block);
+                    let value = self.gen_node(branch, module, builder);
+                    self.jump_with(merge, &node.ty, value, &branch.ty, module, builder);
                 }
-                fn_sig.returns.push(self.syntax_analyzer.functions.get(name).unwrap().return_type.into());
 
-                let func = module.declare_function(&name, Linkage::Import, &fn_sig).unwrap();
-                let func_ref = module.declare_func_in_func(func, &mut builder.func);
-                let call = builder.ins().call(func_ref, &arg_values);
-                builder.inst_results(call)[0]
+                builder.switch_to_block(merge);
+                builder.seal_block(merge);
+                return builder.block_params(merge).first().copied();
             }
-            Expr::While(cond, block, _) => {
-                let cond_block = builder.create_block();
-                let while_body = builder.create_block();
+            Kind::Loop(cond, body, step) => {
+                let header = builder.create_block();
+                let loop_body = builder.create_block();
+                let next = builder.create_block();
                 let exit = builder.create_block();
-                
-                let condition = self.gen_expr(scope_index, cond, module, builder);
-
-                builder.ins().jump(cond_block, &[]);
-                builder.switch_to_block(cond_block);
+                builder.ins().jump(header, &[]);
 
-                // If cond = false jump to exit else go to while block
+                // The condition is checked again every time around
+                builder.switch_to_block(header);
+                let condition = self.gen_node(cond, module, builder)?;
                 builder.ins().brz(condition, exit, &[]);
-                builder.ins().jump(while_body, &[]);
+                builder.ins().jump(loop_body, &[]);
 
-                builder.switch_to_block(while_body);
-                builder.seal_block(while_body);
-                self.gen_expr(scope_index, block, module, builder);
+                builder.switch_to_block(loop_body);
+                builder.seal_block(loop_body);
+                self.loops.push((next, exit));
+                if let Some(value) = self.gen_node(body, module, builder) {
+                    self.release(value, &body.ty, module, builder);
+                }
+                self.loops.pop();
+                builder.ins().jump(next, &[]);
+
+                // Reached from the end of the body and from every `continue`
+                builder.switch_to_block(next);
+                builder.seal_block(next);
+                if let Some(value) = self.gen_node(step, module, builder) {
+                    self.release(value, &step.ty, module, builder);
+                }
+                builder.ins().jump(header, &[]);
+                builder.seal_block(header);
 
-                builder.ins().jump(cond_block, &[]);
                 builder.switch_to_block(exit);
-
-                builder.seal_block(cond_block);
                 builder.seal_block(exit);
-                builder.ins().iconst(types::I64, 0)
+                return None;
             }
-            Expr::List(_, _) => todo!(),
+            Kind::Break | Kind::Continue => {
+                let (next, exit) = *self.loops.last().unwrap();
+                let target = if matches!(node.kind, Kind::Break) { exit } else { next };
+                builder.ins().jump(target, &[]);
+                // Anything after it in the block can't be reached, but still
+                // needs a block to go in
+                let unreachable = builder.create_block();
+                builder.switch_to_block(unreachable);
+                builder.seal_block(unreachable);
+                return None;
+            }
+            Kind::Match(value, arms, variants) => {
+                let mut owned = Vec::new();
+                let value = self.gen_borrowed(value, &[], &mut owned, module, builder)?;
+                let tag = builder.ins().load(types::I64, MemFlags::trusted(), value, field_offset(0));
+                // Jump tables are indexed with 32 bit values
+                let tag = builder.ins().ireduce(types::I32, tag);
+
+                let merge = builder.create_block();
+                if node.ty != Type::Void {
+                    builder.append_block_param(merge, node.ty.clone().into());
+                }
+                let arm_blocks = arms.iter().map(|_| builder.create_block()).collect::<Vec<_>>();
+
+                // The analyzer has checked the match is exhaustive, so without an
+                // else arm the default can never be taken
+                let else_arm = arms.iter().position(|arm| arm.tag.is_none());
+                let default = match else_arm {
+                    Some(index) => arm_blocks[index],
+                    None => builder.create_block(),
+                };
 
+                // Jump straight to the arm for each tag
+                let mut jump_table = JumpTableData::new();
+                for tag in 0..*variants {
+                    let arm = arms.iter().position(|arm| arm.tag == Some(tag));
+                    jump_table.push_entry(arm.map(|index| arm_blocks[index]).unwrap_or(default));
+                }
+                let jump_table = builder.create_jump_table(jump_table);
+                builder.ins().br_table(tag, default, jump_table);
+                if else_arm.is_none() {
+                    builder.switch_to_block(default);
+                    builder.seal_block(default);
+                    builder.ins().trap(TrapCode::UnreachableCodeReached);
+                }
 
-            Expr::Equality(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::Equal, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::NotEqual(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::NotEqual, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::GreaterThan(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::SignedGreaterThan, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::LessThan(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::SignedLessThan, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::GreaterThanEqual(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::LessThanEqual(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                let compare = builder.ins().icmp(IntCC::SignedLessThanOrEqual, left, right);
-                builder.ins().bint(
-                    lhs.get_type(&self.syntax_analyzer.functions, &self.syntax_analyzer.variables.scopes.get(*scope_index).unwrap()).into(),
-                        compare
-                )
-            }
-            Expr::Addition(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                builder.ins().iadd(left, right)
+                for (arm, block) in arms.iter().zip(arm_blocks) {
+                    builder.switch_to_block(block);
+                    builder.seal_block(block);
+                    for (slot, index) in &arm.bindings {
+                        let ty = self.slots[slot.0].clone();
+                        let field = load_field(value, *index, &ty, builder);
+                        self.retain(field, &ty, module, builder);
+                        let previous = builder.use_var(Variable::new(slot.0));
+                        def_slot(slot.0, field, builder);
+                        self.release(previous, &ty, module, builder);
+                    }
+                    // The fields are all read, so the value matched on isn't needed
+                    self.release_owned(owned.clone(), module, builder);
+                    let arm_value = self.gen_node(&arm.body, module, builder);
+                    self.jump_with(merge, &node.ty, arm_value, &arm.body.ty, module, builder);
+                }
+
+                builder.switch_to_block(merge);
+                builder.seal_block(merge);
+                return builder.block_params(merge).first().copied();
             }
-            Expr::Subtraction(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                builder.ins().isub(left, right)
+            Kind::Block(nodes) => {
+                let (last, statements) = nodes.split_last()?;
+                // Only the value of the last node is used
+                for statement in statements {
+                    let stores = matches!(statement.kind, Kind::Store(..) | Kind::SetField(..));
+                    self.unused = stores;
+                    let value = self.gen_node(statement, module, builder);
+                    if let (Some(value), false) = (value, stores) {
+                        self.release(value, &statement.ty, module, builder);
+                    }
+                }
+                return self.gen_node(last, module, builder);
             }
-            Expr::Multiplication(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                builder.ins().imul(left, right)
+        };
+        Some(value)
+    }
+
+    /// Leaves a branch for the block where the branches join, passing on its
+    /// value when the whole expression has one and releasing it otherwise.
+    fn jump_with(&self, merge: cranelift::prelude::Block, ty: &Type, value: Option<Value>, branch_ty: &Type, module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        match value {
+            Some(value) if *ty != Type::Void => builder.ins().jump(merge, &[value]),
+            Some(value) => {
+                self.release(value, branch_ty, module, builder);
+                builder.ins().jump(merge, &[])
             }
-            Expr::Division(lhs, rhs) => {
-                let left = self.gen_expr(scope_index, lhs, module, builder);
-                let right = self.gen_expr(scope_index, rhs, module, builder);
-                builder.ins().udiv(left, right)
+            None => builder.ins().jump(merge, &[]),
+        };
+    }
+
+    /// Calls one of the runtime functions declared by `gold_standard`. Its
+    /// floats are 64 bits wide and it takes booleans as integers, so
+    /// arguments and floats it gives back are converted to match.
+    fn call_builtin(&self, name: &str, args: &[Value], module: &mut dyn Module, builder: &mut FunctionBuilder) -> Option<Value> {
+        let id = self.builtins[name];
+        let params = module.declarations().get_function_decl(id).signature.params.to_owned();
+        let args = args.iter().zip(params).map(|(arg, param)| {
+            let arg_type = builder.func.dfg.value_type(*arg);
+            match param.value_type {
+                types::F64 if arg_type == types::F32 => builder.ins().fpromote(types::F64, *arg),
+                types::I64 if arg_type.is_bool() => builder.ins().bint(types::I64, *arg),
+                // Lists and maps keep a float in 8 bytes, like a field
+                types::I64 if arg_type == types::F32 => {
+                    let bits = builder.ins().bitcast(types::I32, *arg);
+                    builder.ins().uextend(types::I64, bits)
+                }
+                _ => *arg,
             }
-            Expr::Power(lhs, rhs) => todo!(),
-            _ => todo!()
+        }).collect::<Vec<Value>>();
+        let func = module.declare_func_in_func(id, builder.func);
+        let call = builder.ins().call(func, &args);
+        let result = builder.inst_results(call).first().copied()?;
+        match builder.func.dfg.value_type(result) {
+            types::F64 => Some(builder.ins().fdemote(types::F32, result)),
+            _ => Some(result),
         }
     }
 
-    pub fn gen_fn(&mut self, function: Expr) {
-        let mut builder = JITBuilder::new(cranelift_module::default_libcall_names());
+    /// Stops the program before it gets a key a map doesn't have or an index
+    /// past the end of a list, which is reported as a crash where it happened.
+    fn check_present(&self, name: &str, args: &[Value], module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        let (present, code) = match name {
+            MAP_GET_SYMBOL => (self.call_builtin(MAP_CONTAINS_SYMBOL, args, module, builder).unwrap(), MISSING_KEY),
+            LIST_GET_SYMBOL => {
+                let len = self.call_builtin(LIST_LEN_SYMBOL, &args[..1], module, builder).unwrap();
+                // A negative index is past the end too when it's unsigned
+                (builder.ins().icmp(IntCC::UnsignedLessThan, args[1], len), OUT_OF_BOUNDS)
+            }
+            _ => return,
+        };
+        builder.ins().trapz(present, code);
+    }
+
+    /// Makes a function of the host application callable from the program.
+    /// It still has to be declared to the analyzer of every module that uses it.
+    pub fn add_symbol(&mut self, name: &str, address: *const u8) {
+        self.symbols.push((name.to_owned(), address));
+    }
+
+    /// Compiles the program and every module it uses into a single module.
+    /// Code that Cranelift rejects is an error rather than a crash.
+    pub fn compile(&mut self) -> Result<Compiled, String> {
+        let isa = self.config.host().isa().unwrap();
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
 
         // Declare all compiler builtins
         load_symbols(&mut builder);
+        for (name, address) in &self.symbols {
+            builder.symbol(name, *address);
+        }
+
+        // External functions were all found while checking the program
+        for external in self.modules.iter().chain(Some(&self.program)).flat_map(|module| &module.externals) {
+            if let Some(address) = ffi::resolve(&external.library, &external.symbol) {
+                builder.symbol(&external.symbol, address);
+            }
+        }
 
         let mut module = JITModule::new(builder);
-        let instrinsics = declare_functions(&mut module);
+        // Assembly is only printed, so nothing could be debugged
+        if self.config.debug_info && self.assembly.is_none() {
+            self.debug = Some(DebugInfo::default());
+        }
+
+        let (program, ids) = self.gen_program(&mut module)?;
+        module.finalize_definitions();
+        let mut source_map = mem::take(&mut self.source_map);
+        source_map.finalize(&module);
+        let debug = self.debug.take().map(|debug| Registration::new(debug.object(&source_map, module.isa())));
+
+        let entry = self.entry_of(&program).map(|entry| entry.name.to_owned());
+        Ok(Compiled {
+            module,
+            functions: program.functions.into_iter().zip(ids)
+                .map(|(function, id)| (function.name, (id, function.signature)))
+                .collect(),
+            entry,
+            source_map,
+            debug,
+        })
+    }
 
+    /// Compiles the program and runs its entry point.
+    pub fn run(&mut self) -> Result<(), String> {
+        self.compile()?.run()
+    }
+
+    /// The C libraries the externals of the program and the modules it uses
+    /// are from, which a program compiled ahead of time is linked against.
+    pub fn libraries(&self) -> Vec<String> {
+        let mut libraries = Vec::new();
+        for external in self.modules.iter().chain(Some(&self.program)).flat_map(|module| &module.externals) {
+            if !libraries.contains(&external.library) {
+                libraries.push(external.library.to_owned());
+            }
+        }
+        libraries
+    }
+
+    /// Compiles the program and every module it uses for the configured
+    /// target into an object file, with a C `main` that runs the entry point
+    /// and prints what it gives back like `Compiled::run` does. Linked with
+    /// the static library of `gold_standard`, it's a program of its own. The
+    /// debug info the config asks for goes in the object file too.
+    pub fn emit_object(&mut self) -> Result<Vec<u8>, String> {
+        let mut module = ObjectModule::new(self.config.object_isa()?);
+        for (name, _) in SYMBOLS.iter() {
+            module.rename(name, [EXPORT_PREFIX, name].concat());
+        }
+        if self.config.debug_info {
+            self.debug = Some(DebugInfo::default());
+        }
+
+        let (program, _) = self.gen_program(&mut module)?;
+        let entry = self.entry_of(&program)
+            .ok_or_else(|| format!("{} has no function to start at", program.file))?;
+        entry.signature.check_entry(&entry.name)?;
+        self.gen_main(entry, &mut module)?;
+        if let Some(debug) = self.debug.take() {
+            module.add_debug_sections(debug.sections(&self.source_map, module.isa()));
+        }
+        module.finish()
+    }
+
+    /// Compiles every module the program uses and then the program itself,
+    /// giving back the program and the ids of its functions.
+    fn gen_program(&mut self, module: &mut dyn Module) -> Result<(lir::Module, Vec<FuncId>), String> {
+        self.builtins = declare_functions(module);
+        for used in mem::take(&mut self.modules) {
+            self.file = self.source_map.add_file(&used.file, &used.source);
+            self.gen_functions(&used.functions, module)?;
+        }
+
+        let program = mem::take(&mut self.program);
+        self.file = self.source_map.add_file(&program.file, &program.source);
+        let ids = self.gen_functions(&program.functions, module)?;
+        Ok((program, ids))
+    }
+
+    // The function the program starts at, which can be one of its tests
+    fn entry_of<'a>(&self, program: &'a lir::Module) -> Option<&'a lir::Function> {
+        match &self.entry {
+            Some(name) => program.functions.iter().find(|function| &function.name == name),
+            None => program.entry(),
+        }
+    }
+
+    /// Generates the C `main` of a program compiled ahead of time, which
+    /// calls the entry point with `1` and `2` for the parameters it takes.
+    /// A crash ends it with `CRASH_EXIT_CODE`, like it does under the JIT.
+    fn gen_main(&mut self, entry: &lir::Function, module: &mut ObjectModule) -> Result<(), String> {
+        let mut signature = module.make_signature();
+        signature.params.push(AbiParam::new(types::I32));
+        signature.params.push(AbiParam::new(module.target_config().pointer_type()));
+        signature.returns.push(AbiParam::new(types::I32));
+        let id = module.declare_anonymous_function(&signature).unwrap();
+
+        let mut context = module.make_context();
+        context.func.signature = signature;
+        let mut function_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut function_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+
+        self.call_builtin(CATCH_CRASHES_SYMBOL, &[], module, &mut builder);
+        let callee = module.declare_function(&entry.symbol, Linkage::Local, &make_signature(&entry.signature, module)).unwrap();
+        let callee = module.declare_func_in_func(callee, builder.func);
+        let args = (1..=entry.signature.params.len() as i64)
+            .map(|arg| builder.ins().iconst(types::I64, arg))
+            .collect::<Vec<Value>>();
+        let call = builder.ins().call(callee, &args);
+        if let Some(value) = builder.inst_results(call).first().copied() {
+            let print = if entry.signature.returns == Type::Float { PRINT_RESULT_FLOAT_SYMBOL } else { PRINT_RESULT_INT_SYMBOL };
+            self.call_builtin(print, &[value], module, &mut builder);
+        }
+        self.call_builtin(FLUSH_SYMBOL, &[], module, &mut builder);
+        let status = builder.ins().iconst(types::I32, 0);
+        builder.ins().return_(&[status]);
+        builder.finalize();
+
+        module.define_function(id, &mut context, &mut NullTrapSink {}, &mut NullStackMapSink {})
+            .map_err(|error| compile_error("main", error))?;
+        module.export(id, "main");
+        Ok(())
+    }
+
+    /// Compiles the program and every module it uses for the configured
+    /// target, giving back the assembly of each function.
+    pub fn emit_assembly(&mut self) -> Result<String, String> {
+        self.assembly = Some((self.config.isa().unwrap(), String::new()));
+        self.compile()?;
+        Ok(self.assembly.take().unwrap().1)
+    }
+
+    /// Compiles the functions of one module, in the order they were declared.
+    fn gen_functions(&mut self, functions: &[lir::Function], module: &mut dyn Module) -> Result<Vec<FuncId>, String> {
+        functions.iter().map(|function| self.gen_fn(function, module)).collect()
+    }
+
+    pub fn gen_fn(&mut self, function: &lir::Function, module: &mut dyn Module) -> Result<FuncId, String> {
         let mut codegen_ctx = module.make_context();
         let mut function_ctx = FunctionBuilderContext::new();
 
+        // Cranelift Function Signature
+        let returns = &function.signature.returns;
+        let fn_signature = make_signature(&function.signature, module);
 
-        match function {
-            Expr::Function(name, params, ty, mut function_body, _) => {
+        let function_id = module
+            .declare_function(&function.symbol, Linkage::Local, &fn_signature)
+            .unwrap();
 
-                // Cranelift Function Signature
-                let mut fn_signature = module.make_signature();
-                fn_signature.returns.push(ty.into());
+        codegen_ctx.func.signature = fn_signature;
+        if self.debug.is_some() {
+            codegen_ctx.func.collect_debug_info();
+        }
 
-                for p in &params {
-                    fn_signature.params.push(p.0.typename.into());
-                }
+        //===================== Code generation =====================
 
+        // Setup the function builder
+        let mut builder = FunctionBuilder::new(&mut codegen_ctx.func, &mut function_ctx);
 
-                let function_id = module
-                    .declare_function(name.as_str(), Linkage::Local, &fn_signature)
-                    .unwrap();
+        // Create entry block into the function and paramters
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
 
-                codegen_ctx.func.signature = fn_signature;
+        // Start codegen at the entry block and seal it to tell Cranelift
+        // that we have no blocks previous to this one.
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
 
-                //===================== Code generation =====================
+        // Every slot is a Cranelift variable, and parameters take the first ones
+        for (index, ty) in function.slots.iter().enumerate() {
+            if *ty != Type::Void {
+                builder.declare_var(Variable::new(index), ty.clone().into());
+            }
+        }
+        self.slots = function.slots.to_owned();
+        for (index, ty) in function.signature.params.iter().enumerate() {
+            let value = builder.block_params(entry)[index];
+            def_slot(index, value, &mut builder);
+            self.retain(value, ty, module, &mut builder);
+        }
 
-                // Setup the function builder
-                let mut function_builder = FunctionBuilder::new(&mut codegen_ctx.func, &mut function_ctx);
-                let function_builder = RefCell::new(function_builder);
+        // The value of the body is returned, which the analyzer has checked
+        // against the declared return type
+        let value = self.gen_node(&function.body, module, &mut builder);
+        self.release_slots(module, &mut builder);
+        match value {
+            Some(value) if *returns != Type::Void => {
+                builder.ins().return_(&[value]);
+            }
+            // An if without an else can end a function that returns something,
+            // which then gives back zero when no branch is taken
+            None if *returns != Type::Void => {
+                let value = zero(returns, &mut builder);
+                builder.ins().return_(&[value]);
+            }
+            Some(value) => {
+                self.release(value, &function.body.ty, module, &mut builder);
+                builder.ins().return_(&[]);
+            }
+            None => {
+                builder.ins().return_(&[]);
+            }
+        }
+        builder.finalize();
 
-                // Create entry block into the function and paramters
-                let entry = function_builder.borrow_mut().create_block();
-                function_builder.borrow_mut().append_block_params_for_function_params(entry);
+        if let Some((isa, text)) = &mut self.assembly {
+            let disassembly = disassemble(&codegen_ctx.func, isa.as_ref())
+                .map_err(|error| compile_error(&function.name, error))?;
+            text.push_str(&format!("{}:\n{}\n", function.symbol, disassembly));
+        }
 
-                // Start codegen at the entry block and seal it to tell Cranelift 
-                // that we have no blocks previous to this one.
-                function_builder.borrow_mut().switch_to_block(entry);
-                function_builder.borrow_mut().seal_block(entry);
+        let mut traps = Traps::default();
+        let compiled = module.define_function(
+            function_id,
+            &mut codegen_ctx,
+            &mut traps,
+            &mut NullStackMapSink {},
+        ).map_err(|error| compile_error(&function.name, error))?;
+        self.source_map.add_function(&function.symbol, self.file, function_id, compiled.size, &codegen_ctx, traps);
+        if let Some(debug) = &mut self.debug {
+            debug.add_function(function, function_id, &codegen_ctx, module.isa());
+        }
 
-                //========================================================================================
-                let mut define_variable = |ty, name: &String| {
-                    let var = Variable::new(self.variable_index);
-                    if let None = self.variables.get(name) {
-                        self.variables.insert(name.to_owned(), var);
-                        self.variable_index += 1;
-                        function_builder.borrow_mut().declare_var(var, ty);
-                    }
-                    var
-                };
-                //========================================================================================
+        module.clear_context(&mut codegen_ctx);
+        Ok(function_id)
+    }
+}
 
+// What is reported when Cranelift rejects the code of a function, which is a
+// bug in the compiler rather than in the program
+fn compile_error(name: &str, error: impl std::fmt::Display) -> String {
+    format!("internal compiler error in '{}': {}", name, error)
+}
 
-                for (pos, param) in params.iter().enumerate() {
-                    let val = function_builder.borrow_mut().block_params(entry)[pos];
-                    let var =  define_variable(param.0.typename.into(), &param.0.name.to_owned());
-                    function_builder.borrow_mut().def_var(var, val);
-                }
+// Sets the variable of a slot, telling Cranelift which slot the value is in
+// for debug info
+fn def_slot(slot: usize, value: Value, builder: &mut FunctionBuilder) {
+    builder.def_var(Variable::new(slot), value);
+    builder.set_val_label(value, ValueLabel::new(slot));
+}
 
+/// Compiles a copy of a function for another instruction set, which may call
+/// functions differently from the host.
+fn disassemble(function: &codegen::ir::Function, isa: &dyn TargetIsa) -> Result<String, codegen::CodegenError> {
+    let mut function = function.clone();
+    let call_conv = isa.default_call_conv();
+    function.signature.call_conv = call_conv;
+    for (_, signature) in function.dfg.signatures.iter_mut() {
+        signature.call_conv = call_conv;
+    }
+    let mut context = codegen::Context::for_function(function);
+    context.want_disasm = true;
+    context.compile(isa)?;
+    Ok(context.mach_compile_result.unwrap().disasm.unwrap())
+}
 
-                let zero = function_builder.borrow_mut().ins().iconst(types::I64, 21);
-                let return_variable = define_variable(types::I64, &"why".to_owned());
-                function_builder.borrow_mut().def_var(return_variable, zero);
+fn gen_binary(op: BinaryOp, ty: &Type, left: Value, right: Value, builder: &mut FunctionBuilder) -> Value {
+    match ty {
+        Type::Float => match op {
+            BinaryOp::Add => builder.ins().fadd(left, right),
+            BinaryOp::Subtract => builder.ins().fsub(left, right),
+            BinaryOp::Multiply => builder.ins().fmul(left, right),
+            BinaryOp::Divide => builder.ins().fdiv(left, right),
+            BinaryOp::Equal => builder.ins().fcmp(FloatCC::Equal, left, right),
+            BinaryOp::NotEqual => builder.ins().fcmp(FloatCC::NotEqual, left, right),
+            BinaryOp::GreaterThan => builder.ins().fcmp(FloatCC::GreaterThan, left, right),
+            BinaryOp::LessThan => builder.ins().fcmp(FloatCC::LessThan, left, right),
+            BinaryOp::GreaterThanEqual => builder.ins().fcmp(FloatCC::GreaterThanOrEqual, left, right),
+            BinaryOp::LessThanEqual => builder.ins().fcmp(FloatCC::LessThanOrEqual, left, right),
+        },
+        // Booleans can't be compared directly, so they're compared as integers
+        Type::Bool => {
+            let left = builder.ins().bint(types::I64, left);
+            let right = builder.ins().bint(types::I64, right);
+            gen_binary(op, &Type::Int, left, right, builder)
+        }
+        _ => match op {
+            BinaryOp::Add => builder.ins().iadd(left, right),
+            BinaryOp::Subtract => builder.ins().isub(left, right),
+            BinaryOp::Multiply => builder.ins().imul(left, right),
+            BinaryOp::Divide => builder.ins().sdiv(left, right),
+            BinaryOp::Equal => builder.ins().icmp(IntCC::Equal, left, right),
+            BinaryOp::NotEqual => builder.ins().icmp(IntCC::NotEqual, left, right),
+            BinaryOp::GreaterThan => builder.ins().icmp(IntCC::SignedGreaterThan, left, right),
+            BinaryOp::LessThan => builder.ins().icmp(IntCC::SignedLessThan, left, right),
+            BinaryOp::GreaterThanEqual => builder.ins().icmp(IntCC::SignedGreaterThanOrEqual, left, right),
+            BinaryOp::LessThanEqual => builder.ins().icmp(IntCC::SignedLessThanOrEqual, left, right),
+        },
+    }
+}
 
-                // Generate Cranelift IR for function body
-                let variable_index = self.syntax_analyzer.functions.get(&name).unwrap().scope_index;
-                match function_body.as_mut() {
-                    Expr::Block(stmts, _) => {
-                        for stmt in stmts {
-                            self.gen_expr(&variable_index, stmt, &mut module, function_builder.borrow_mut().borrow_mut());
-                        }
-                    }
-                    _ => unreachable!()
-                }
+/// Runtime functions Cranelift has instructions for, which are used instead
+/// of calling them. `round` isn't one, since `nearest` rounds halfway cases to
+/// the even number rather than away from zero.
+fn gen_intrinsic(name: &str, args: &[Value], builder: &mut FunctionBuilder) -> Option<Value> {
+    let value = match name {
+        SQRT_SYMBOL => builder.ins().sqrt(args[0]),
+        FLOOR_SYMBOL => builder.ins().floor(args[0]),
+        CEIL_SYMBOL => builder.ins().ceil(args[0]),
+        ABS_FLOAT_SYMBOL => builder.ins().fabs(args[0]),
+        ABS_INT_SYMBOL => {
+            let negated = builder.ins().ineg(args[0]);
+            let negative = builder.ins().icmp_imm(IntCC::SignedLessThan, args[0], 0);
+            builder.ins().select(negative, negated, args[0])
+        }
+        MIN_INT_SYMBOL | MIN_FLOAT_SYMBOL | MAX_INT_SYMBOL | MAX_FLOAT_SYMBOL => {
+            let op = if name.starts_with("min") { BinaryOp::LessThan } else { BinaryOp::GreaterThan };
+            let ty = if name.ends_with("float") { Type::Float } else { Type::Int };
+            let first = gen_binary(op, &ty, args[0], args[1], builder);
+            builder.ins().select(first, args[0], args[1])
+        }
+        CLAMP_INT_SYMBOL | CLAMP_FLOAT_SYMBOL => {
+            let ty = if name == CLAMP_FLOAT_SYMBOL { Type::Float } else { Type::Int };
+            let above = gen_binary(BinaryOp::GreaterThan, &ty, args[0], args[2], builder);
+            let below = gen_binary(BinaryOp::LessThan, &ty, args[0], args[1], builder);
+            let value = builder.ins().select(above, args[2], args[0]);
+            builder.ins().select(below, args[1], value)
+        }
+        TO_FLOAT_SYMBOL => builder.ins().fcvt_from_sint(types::F32, args[0]),
+        TO_INT_SYMBOL => builder.ins().fcvt_to_sint_sat(types::I64, args[0]),
+        _ => return None,
+    };
+    Some(value)
+}
 
-                let return_var = self.variables.get(&"why".to_owned()).unwrap();
-                let return_val = function_builder.borrow_mut().use_var(*return_var);
+/// Converts a value of a list or map, which the runtime gives back in 8 bytes
+/// whatever its type, to the type it is.
+fn from_slot(value: Value, ty: &Type, builder: &mut FunctionBuilder) -> Value {
+    match (builder.func.dfg.value_type(value), ty) {
+        (types::I64, Type::Float) => {
+            let bits = builder.ins().ireduce(types::I32, value);
+            builder.ins().bitcast(types::F32, bits)
+        }
+        (types::I64, Type::Bool) => builder.ins().icmp_imm(IntCC::NotEqual, value, 0),
+        _ => value,
+    }
+}
 
-                function_builder.borrow_mut().ins().return_(&[return_val]);
-                function_builder.borrow_mut().finalize();
+fn zero(ty: &Type, builder: &mut FunctionBuilder) -> Value {
+    match ty {
+        Type::Float => builder.ins().f32const(0.0),
+        Type::Bool => builder.ins().bconst(types::B1, false),
+        ty => builder.ins().iconst(ty.clone().into(), 0),
+    }
+}
 
-                module.define_function(
-                    function_id,
-                    &mut codegen_ctx,
-                    &mut codegen::binemit::NullTrapSink {},
-                    &mut NullStackMapSink {},
-                ).unwrap();
+// Fields are stored in 8 byte slots, which is enough for any value and keeps
+// every field aligned. Structures and enums are only ever handled by pointer,
+// and an enum keeps its tag in the first slot.
+const FIELD_SIZE: i64 = 8;
 
-                module.clear_context(&mut codegen_ctx);
-                module.finalize_definitions();
+fn field_offset(index: usize) -> i32 {
+    (index as i64 * FIELD_SIZE) as i32
+}
 
-                let code = module.get_finalized_function(function_id);
-                let code_fn = unsafe {
-                    mem::transmute::<_, fn(i64, i64) -> i64>(code)
-                };
-                let v = code_fn(1, 2);
-                println!("{}", v);
-            }
-            _ => unreachable!()
-        };
+fn make_signature(signature: &lir::Signature, module: &dyn Module) -> cranelift::prelude::Signature {
+    let mut fn_signature = module.make_signature();
+    if signature.returns != Type::Void {
+        fn_signature.returns.push(signature.returns.clone().into());
+    }
+    for param in &signature.params {
+        fn_signature.params.push(param.clone().into());
+    }
+    fn_signature
+}
+
+fn store_field(record: Value, index: usize, value: Value, builder: &mut FunctionBuilder) {
+    // Booleans can't be stored directly, so they're widened to an integer
+    let value = if builder.func.dfg.value_type(value).is_bool() {
+        builder.ins().bint(types::I64, value)
+    } else {
+        value
+    };
+    builder.ins().store(MemFlags::trusted(), value, record, field_offset(index));
+}
+
+fn load_field(record: Value, index: usize, ty: &Type, builder: &mut FunctionBuilder) -> Value {
+    match ty {
+        Type::Bool => {
+            let value = builder.ins().load(types::I64, MemFlags::trusted(), record, field_offset(index));
+            builder.ins().icmp_imm(IntCC::NotEqual, value, 0)
+        }
+        ty => builder.ins().load(ty.clone().into(), MemFlags::trusted(), record, field_offset(index)),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,341 @@
+//! Object files for programs compiled ahead of time, which `gold build` links
+//! into executables.
+//!
+//! This is a Cranelift module like the JIT, except that the code and data it
+//! is given are kept along with the places they refer to other symbols, and
+//! written out as an object file once everything is defined. The linker then
+//! does what the JIT does when it finalizes functions.
+
+use std::collections::HashMap;
+
+use cranelift::codegen::binemit::{Addend, CodeOffset, Reloc, RelocSink, StackMapSink, TrapSink};
+use cranelift::codegen::ir::{self, ConstantOffset, ExternalName, JumpTable, SourceLoc};
+use cranelift::codegen::isa::TargetIsa;
+use cranelift::codegen::Context;
+use super::debug::{DebugSection, DebugTarget};
+
+use cranelift_module::{
+    DataContext, DataId, FuncId, Init, Linkage, Module, ModuleCompiledFunction, ModuleDeclarations, ModuleError,
+    ModuleResult, RelocRecord,
+};
+use object::write::{Object, Relocation, StandardSection, Symbol, SymbolSection};
+use object::{RelocationEncoding, RelocationKind, SectionKind, SymbolFlags, SymbolKind, SymbolScope};
+use target_lexicon::{Architecture, BinaryFormat, Endianness};
+
+// Enough for any instruction set Cranelift compiles for
+const FUNCTION_ALIGNMENT: u64 = 16;
+// Data that doesn't ask for an alignment gets the one the JIT gives it
+const WRITABLE_DATA_ALIGNMENT: u64 = 8;
+const READONLY_DATA_ALIGNMENT: u64 = 1;
+
+struct DataObject {
+    contents: Vec<u8>,
+    writable: bool,
+    align: u64,
+    relocs: Vec<RelocRecord>,
+}
+
+pub struct ObjectModule {
+    isa: Box<dyn TargetIsa>,
+    declarations: ModuleDeclarations,
+    functions: HashMap<FuncId, (Vec<u8>, Vec<RelocRecord>)>,
+    data: HashMap<DataId, DataObject>,
+    // Declarations that are written under another symbol
+    renamed: HashMap<String, String>,
+    exported: HashMap<FuncId, String>,
+    debug_sections: Vec<DebugSection>,
+}
+
+impl ObjectModule {
+    pub fn new(isa: Box<dyn TargetIsa>) -> Self {
+        Self {
+            isa,
+            declarations: ModuleDeclarations::default(),
+            functions: HashMap::new(),
+            data: HashMap::new(),
+            renamed: HashMap::new(),
+            exported: HashMap::new(),
+            debug_sections: Vec::new(),
+        }
+    }
+
+    /// Writes what is declared under a name as another symbol, such as a
+    /// runtime function exported with a prefix.
+    pub fn rename(&mut self, name: &str, symbol: String) {
+        self.renamed.insert(name.to_owned(), symbol);
+    }
+
+    /// Makes an anonymous function a global symbol, which none of the
+    /// functions of the program can clash with, since they're declared under
+    /// their own names.
+    pub fn export(&mut self, id: FuncId, symbol: &str) {
+        self.exported.insert(id, symbol.to_owned());
+    }
+
+    /// Adds the sections of the program's debug info.
+    pub fn add_debug_sections(&mut self, sections: Vec<DebugSection>) {
+        self.debug_sections.extend(sections);
+    }
+
+    /// Writes everything declared to an object file for the target, with
+    /// whatever wasn't defined left for the linker to find.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        let triple = self.isa.triple();
+        let format = match triple.binary_format {
+            BinaryFormat::Elf => object::BinaryFormat::Elf,
+            BinaryFormat::Macho => object::BinaryFormat::MachO,
+            BinaryFormat::Coff => object::BinaryFormat::Coff,
+            format => return Err(format!("object files can't be written in the {} format", format)),
+        };
+        if !self.debug_sections.is_empty() && format != object::BinaryFormat::Elf {
+            return Err(format!("debug info can't be written in the {} format yet", triple.binary_format));
+        }
+        let architecture = match triple.architecture {
+            Architecture::X86_64 => object::Architecture::X86_64,
+            Architecture::Aarch64(_) => object::Architecture::Aarch64,
+            architecture => return Err(format!("object files can't be written for {}", architecture)),
+        };
+        let endian = match triple.endianness() {
+            Ok(Endianness::Big) => object::Endianness::Big,
+            _ => object::Endianness::Little,
+        };
+        let mut object = Object::new(format, architecture, endian);
+
+        // Every declaration gets a symbol, which is defined along with its
+        // contents when there are any
+        let mut function_symbols = HashMap::new();
+        for (id, declaration) in self.declarations.get_functions() {
+            let exported = self.exported.get(&id);
+            let name = exported.or_else(|| self.renamed.get(&declaration.name)).unwrap_or(&declaration.name);
+            let linkage = if exported.is_some() { Linkage::Export } else { declaration.linkage };
+            let symbol = object.add_symbol(symbol(name, SymbolKind::Text, linkage));
+            function_symbols.insert(id, symbol);
+        }
+        let mut data_symbols = HashMap::new();
+        for (id, declaration) in self.declarations.get_data_objects() {
+            let name = self.renamed.get(&declaration.name).unwrap_or(&declaration.name);
+            let symbol = object.add_symbol(symbol(name, SymbolKind::Data, declaration.linkage));
+            data_symbols.insert(id, symbol);
+        }
+
+        let text = object.section_id(StandardSection::Text);
+        let mut relocations = Vec::new();
+        let mut functions = self.functions.into_iter().collect::<Vec<_>>();
+        functions.sort_by_key(|(id, _)| *id);
+        for (id, (code, relocs)) in functions {
+            let symbol = function_symbols[&id];
+            let offset = object.add_symbol_data(symbol, text, &code, FUNCTION_ALIGNMENT);
+            relocations.extend(relocs.into_iter().map(|reloc| (text, offset, reloc)));
+        }
+        let mut data = self.data.into_iter().collect::<Vec<_>>();
+        data.sort_by_key(|(id, _)| *id);
+        for (id, data) in data {
+            let section = object.section_id(if data.writable { StandardSection::Data } else { StandardSection::ReadOnlyData });
+            let symbol = data_symbols[&id];
+            let offset = object.add_symbol_data(symbol, section, &data.contents, data.align);
+            relocations.extend(data.relocs.into_iter().map(|reloc| (section, offset, reloc)));
+        }
+
+        // Debug info only refers to functions and to other debug sections,
+        // which are all there once its own sections are
+        let debug_sections = self.debug_sections.iter()
+            .map(|section| {
+                let id = object.add_section(Vec::new(), section.name.as_bytes().to_vec(), SectionKind::Debug);
+                object.append_section_data(id, &section.data, 1);
+                (section.name, id)
+            })
+            .collect::<HashMap<_, _>>();
+        for section in &self.debug_sections {
+            for reloc in &section.relocs {
+                let symbol = match reloc.target {
+                    DebugTarget::Function(id) => function_symbols[&id],
+                    DebugTarget::Section(name) => object.section_symbol(debug_sections[name]),
+                };
+                object.add_relocation(debug_sections[section.name], Relocation {
+                    offset: reloc.offset as u64,
+                    size: reloc.size * 8,
+                    kind: RelocationKind::Absolute,
+                    encoding: RelocationEncoding::Generic,
+                    symbol,
+                    addend: reloc.addend,
+                }).map_err(|error| error.to_string())?;
+            }
+        }
+
+        for (section, offset, reloc) in relocations {
+            let (kind, encoding, size) = relocation_kind(reloc.reloc)?;
+            let symbol = match &reloc.name {
+                ExternalName::LibCall(libcall) => {
+                    // Cranelift calls these on its own, and they're found in
+                    // the C runtime the same as the JIT finds them
+                    let name = cranelift_module::default_libcall_names()(*libcall);
+                    match object.symbol_id(name.as_bytes()) {
+                        Some(symbol) => symbol,
+                        None => object.add_symbol(symbol(&name, SymbolKind::Text, Linkage::Import)),
+                    }
+                }
+                name if ModuleDeclarations::is_function(name) => function_symbols[&FuncId::from_name(name)],
+                name => data_symbols[&DataId::from_name(name)],
+            };
+            object.add_relocation(section, Relocation {
+                offset: offset + reloc.offset as u64,
+                size,
+                kind,
+                encoding,
+                symbol,
+                addend: reloc.addend,
+            }).map_err(|error| error.to_string())?;
+        }
+        object.write().map_err(|error| error.to_string())
+    }
+}
+
+// An undefined symbol for an import, and otherwise one that is defined once
+// its contents are added
+fn symbol(name: &str, kind: SymbolKind, linkage: Linkage) -> Symbol {
+    let (scope, weak) = match linkage {
+        Linkage::Import => (SymbolScope::Unknown, false),
+        Linkage::Local => (SymbolScope::Compilation, false),
+        Linkage::Preemptible => (SymbolScope::Dynamic, true),
+        Linkage::Hidden => (SymbolScope::Linkage, false),
+        Linkage::Export => (SymbolScope::Dynamic, false),
+    };
+    Symbol {
+        name: name.as_bytes().to_vec(),
+        value: 0,
+        size: 0,
+        kind,
+        scope,
+        weak,
+        section: SymbolSection::Undefined,
+        flags: SymbolFlags::None,
+    }
+}
+
+// How the object file spells each relocation Cranelift makes
+fn relocation_kind(reloc: Reloc) -> Result<(RelocationKind, RelocationEncoding, u8), String> {
+    Ok(match reloc {
+        Reloc::Abs4 => (RelocationKind::Absolute, RelocationEncoding::Generic, 32),
+        Reloc::Abs8 => (RelocationKind::Absolute, RelocationEncoding::Generic, 64),
+        Reloc::X86PCRel4 => (RelocationKind::Relative, RelocationEncoding::Generic, 32),
+        Reloc::X86CallPCRel4 => (RelocationKind::Relative, RelocationEncoding::X86Branch, 32),
+        Reloc::X86CallPLTRel4 => (RelocationKind::PltRelative, RelocationEncoding::X86Branch, 32),
+        Reloc::X86GOTPCRel4 => (RelocationKind::GotRelative, RelocationEncoding::Generic, 32),
+        Reloc::Arm64Call => (RelocationKind::Relative, RelocationEncoding::AArch64Call, 26),
+        reloc => return Err(format!("object files can't have {:?} relocations", reloc)),
+    })
+}
+
+impl Module for ObjectModule {
+    fn isa(&self) -> &dyn TargetIsa {
+        &*self.isa
+    }
+
+    fn declarations(&self) -> &ModuleDeclarations {
+        &self.declarations
+    }
+
+    fn declare_function(&mut self, name: &str, linkage: Linkage, signature: &ir::Signature) -> ModuleResult<FuncId> {
+        let (id, _) = self.declarations.declare_function(name, linkage, signature)?;
+        Ok(id)
+    }
+
+    fn declare_anonymous_function(&mut self, signature: &ir::Signature) -> ModuleResult<FuncId> {
+        self.declarations.declare_anonymous_function(signature)
+    }
+
+    fn declare_data(&mut self, name: &str, linkage: Linkage, writable: bool, tls: bool) -> ModuleResult<DataId> {
+        let (id, _) = self.declarations.declare_data(name, linkage, writable, tls)?;
+        Ok(id)
+    }
+
+    fn declare_anonymous_data(&mut self, writable: bool, tls: bool) -> ModuleResult<DataId> {
+        self.declarations.declare_anonymous_data(writable, tls)
+    }
+
+    fn define_function(
+        &mut self,
+        id: FuncId,
+        context: &mut Context,
+        trap_sink: &mut dyn TrapSink,
+        stack_map_sink: &mut dyn StackMapSink,
+    ) -> ModuleResult<ModuleCompiledFunction> {
+        let size = context.compile(&*self.isa)?.total_size;
+        if let Some(error) = self.definition_error(id) {
+            return Err(error);
+        }
+        let mut code = vec![0; size as usize];
+        let mut relocs = Relocs::default();
+        unsafe {
+            context.emit_to_memory(&*self.isa, code.as_mut_ptr(), &mut relocs, trap_sink, stack_map_sink);
+        }
+        self.functions.insert(id, (code, relocs.0));
+        Ok(ModuleCompiledFunction { size })
+    }
+
+    fn define_function_bytes(&mut self, id: FuncId, bytes: &[u8], relocs: &[RelocRecord]) -> ModuleResult<ModuleCompiledFunction> {
+        if let Some(error) = self.definition_error(id) {
+            return Err(error);
+        }
+        self.functions.insert(id, (bytes.to_vec(), relocs.to_vec()));
+        Ok(ModuleCompiledFunction { size: bytes.len() as CodeOffset })
+    }
+
+    fn define_data(&mut self, id: DataId, data_context: &DataContext) -> ModuleResult<()> {
+        let declaration = self.declarations.get_data_decl(id);
+        if !declaration.linkage.is_definable() {
+            return Err(ModuleError::InvalidImportDefinition(declaration.name.to_owned()));
+        }
+        if self.data.contains_key(&id) {
+            return Err(ModuleError::DuplicateDefinition(declaration.name.to_owned()));
+        }
+        let description = data_context.description();
+        let contents = match &description.init {
+            Init::Uninitialized => panic!("data '{}' was defined without contents", declaration.name),
+            Init::Zeros { size } => vec![0; *size],
+            Init::Bytes { contents } => contents.to_vec(),
+        };
+        let pointer = match self.isa.triple().pointer_width() {
+            Ok(target_lexicon::PointerWidth::U32) => Reloc::Abs4,
+            _ => Reloc::Abs8,
+        };
+        let align = if declaration.writable { WRITABLE_DATA_ALIGNMENT } else { READONLY_DATA_ALIGNMENT };
+        self.data.insert(id, DataObject {
+            contents,
+            writable: declaration.writable,
+            align: description.align.unwrap_or(align),
+            relocs: description.all_relocs(pointer).collect(),
+        });
+        Ok(())
+    }
+}
+
+impl ObjectModule {
+    // Why a function can't be defined, if it can't
+    fn definition_error(&self, id: FuncId) -> Option<ModuleError> {
+        let declaration = self.declarations.get_function_decl(id);
+        if !declaration.linkage.is_definable() {
+            Some(ModuleError::InvalidImportDefinition(declaration.name.to_owned()))
+        } else if self.functions.contains_key(&id) {
+            Some(ModuleError::DuplicateDefinition(declaration.name.to_owned()))
+        } else {
+            None
+        }
+    }
+}
+
+// Collects where compiled code refers to other symbols
+#[derive(Default)]
+struct Relocs(Vec<RelocRecord>);
+
+impl RelocSink for Relocs {
+    fn reloc_external(&mut self, offset: CodeOffset, _: SourceLoc, reloc: Reloc, name: &ExternalName, addend: Addend) {
+        self.0.push(RelocRecord { offset, reloc, name: name.clone(), addend });
+    }
+
+    // Jump tables and constants are kept with the code of the function, and
+    // referred to relative to it
+    fn reloc_constant(&mut self, _: CodeOffset, _: Reloc, _: ConstantOffset) {}
+
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {}
+}
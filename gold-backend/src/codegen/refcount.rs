@@ -0,0 +1,101 @@
+//! Reference counting of strings, structures, enums and collections, which
+//! the runtime frees once nothing refers to them (see `gold_standard::mem`).
+//!
+//! Compiled code keeps to these rules:
+//!
+//! - A node gives back a reference of its own to its value, which whatever
+//!   uses the value takes over. Loading a slot retains the value in it.
+//! - Slots own their values. Storing to a slot releases what was in it, and
+//!   every slot is released when the function returns.
+//! - Structures and enums own the values in their fields.
+//! - Calls borrow their arguments, which the caller releases afterwards if it
+//!   owned them. A function retains its parameters, as they're kept in slots.
+//! - A value nothing uses, like that of a statement, is released.
+
+use cranelift::frontend::{FunctionBuilder, Variable};
+use cranelift::prelude::{EntityRef, Value};
+use cranelift_module::Module;
+
+use gold_frontend::frontend::Type;
+use gold_standard::load::{RELEASE_SYMBOL, RETAIN_SYMBOL};
+
+use crate::lir::{Kind, Node, Slot};
+
+use super::Compilation;
+
+/// Whether values of a type are reference counted.
+pub(crate) fn counted(ty: &Type) -> bool {
+    matches!(ty, Type::String | Type::Named(_) | Type::List(_) | Type::Map(..) | Type::Function(..))
+}
+
+impl Compilation {
+    pub(super) fn retain(&self, value: Value, ty: &Type, module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        if counted(ty) {
+            self.call_builtin(RETAIN_SYMBOL, &[value], module, builder);
+        }
+    }
+
+    pub(super) fn release(&self, value: Value, ty: &Type, module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        if counted(ty) {
+            self.call_builtin(RELEASE_SYMBOL, &[value], module, builder);
+        }
+    }
+
+    /// Generates a value that is only needed for a moment, like an argument.
+    /// A slot keeps its value alive, so it's used without being retained
+    /// unless one of the nodes run `after` it could store to the slot.
+    /// Anything else is added to `owned`, to be released once it's used.
+    pub(super) fn gen_borrowed(&mut self, node: &Node, after: &[Node], owned: &mut Vec<Value>, module: &mut dyn Module, builder: &mut FunctionBuilder) -> Option<Value> {
+        match node.kind {
+            Kind::Load(slot) if counted(&node.ty) && !after.iter().any(|node| stores_to(node, slot)) => {
+                Some(builder.use_var(Variable::new(slot.0)))
+            }
+            _ => {
+                let value = self.gen_node(node, module, builder)?;
+                if counted(&node.ty) {
+                    owned.push(value);
+                }
+                Some(value)
+            }
+        }
+    }
+
+    /// Releases the values `gen_borrowed` gave a reference to.
+    pub(super) fn release_owned(&self, owned: Vec<Value>, module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        for value in owned {
+            self.call_builtin(RELEASE_SYMBOL, &[value], module, builder);
+        }
+    }
+
+    /// Releases the values of every slot, before the function returns.
+    pub(super) fn release_slots(&self, module: &mut dyn Module, builder: &mut FunctionBuilder) {
+        for (index, ty) in self.slots.iter().enumerate() {
+            if counted(ty) {
+                let value = builder.use_var(Variable::new(index));
+                self.call_builtin(RELEASE_SYMBOL, &[value], module, builder);
+            }
+        }
+    }
+}
+
+/// Whether running a node could change what is in a slot.
+fn stores_to(node: &Node, slot: Slot) -> bool {
+    let any = |nodes: &[Node]| nodes.iter().any(|node| stores_to(node, slot));
+    match &node.kind {
+        Kind::Int(_) | Kind::Bool(_) | Kind::String(_) | Kind::Load(_) | Kind::Address(..) | Kind::Break | Kind::Continue => false,
+        Kind::Store(stored, value) => *stored == slot || stores_to(value, slot),
+        Kind::Negate(value) | Kind::GetField(value, _) => stores_to(value, slot),
+        Kind::Binary(_, lhs, rhs) | Kind::SetField(lhs, _, rhs) => {
+            stores_to(lhs, slot) || stores_to(rhs, slot)
+        }
+        Kind::If(first, second, third) | Kind::Loop(first, second, third) => {
+            stores_to(first, slot) || stores_to(second, slot) || stores_to(third, slot)
+        }
+        Kind::Match(value, arms, _) => {
+            stores_to(value, slot) || arms.iter().any(|arm| {
+                arm.bindings.iter().any(|(bound, _)| *bound == slot) || stores_to(&arm.body, slot)
+            })
+        }
+        Kind::Call(_, nodes) | Kind::Allocate(_, nodes) | Kind::Block(nodes) => any(nodes),
+    }
+}
@@ -0,0 +1,161 @@
+//! Maps compiled code back to the Gold source it came from.
+//!
+//! Every instruction is tagged with a Cranelift `SourceLoc`, which is an index
+//! into the spans kept here. Once a function is compiled, the ranges of code
+//! each location ended up at are recorded, along with where it traps and where
+//! its calls return to. Crash reports and debug info are both built from this.
+
+use std::ops::Range;
+
+use cranelift::codegen::binemit::{Addend, CodeOffset, CodeSink, Reloc, TrapSink};
+use cranelift::codegen::ir::{ConstantOffset, ExternalName, Function, JumpTable, Opcode, SourceLoc, TrapCode};
+use cranelift::codegen::isa::TargetIsa;
+use cranelift::codegen::Context;
+use cranelift::prelude::Value;
+use cranelift_jit::JITModule;
+use cranelift_module::FuncId;
+
+/// What was recorded about a compiled function.
+pub(crate) struct CompiledFunction {
+    pub(crate) symbol: String,
+    /// The file the function is declared in
+    pub(crate) file: usize,
+    pub(crate) id: FuncId,
+    pub(crate) address: usize,
+    pub(crate) size: usize,
+    /// Ranges of code offsets and the source location of the code in them
+    pub(crate) srclocs: Vec<(Range<CodeOffset>, SourceLoc)>,
+    pub(crate) traps: Vec<(CodeOffset, SourceLoc, TrapCode)>,
+    /// The offsets right after each call, which are what return addresses
+    /// on the stack point to
+    pub(crate) returns: Vec<CodeOffset>,
+}
+
+/// The files of a program, the spans in them that code was tagged with, and
+/// the functions compiled from them.
+#[derive(Default)]
+pub struct SourceMap {
+    pub(crate) files: Vec<(String, String)>,
+    spans: Vec<(usize, Range<usize>)>,
+    pub(crate) functions: Vec<CompiledFunction>,
+}
+
+impl SourceMap {
+    /// Adds a file that spans can point into.
+    pub fn add_file(&mut self, name: &str, source: &str) -> usize {
+        self.files.push((name.to_owned(), source.to_owned()));
+        self.files.len() - 1
+    }
+
+    /// The source location for a span of one of the files.
+    pub fn srcloc(&mut self, file: usize, span: Range<usize>) -> SourceLoc {
+        self.spans.push((file, span));
+        SourceLoc::new(self.spans.len() as u32 - 1)
+    }
+
+    /// The file and span a source location stands for.
+    pub fn span(&self, srcloc: SourceLoc) -> Option<(usize, Range<usize>)> {
+        self.spans.get(srcloc.bits() as usize).cloned()
+    }
+
+    /// Records where the code of a function came from once it's compiled.
+    pub fn add_function(&mut self, symbol: &str, file: usize, id: FuncId, size: CodeOffset, context: &Context, traps: Traps) {
+        let result = context.mach_compile_result.as_ref().unwrap();
+        let srclocs = result.buffer.get_srclocs_sorted().iter()
+            .filter(|srcloc| !srcloc.loc.is_default())
+            .map(|srcloc| (srcloc.start..srcloc.end, srcloc.loc))
+            .collect();
+        let mut returns = CallReturns::default();
+        result.buffer.emit(&mut returns);
+        self.functions.push(CompiledFunction {
+            symbol: symbol.to_owned(),
+            file,
+            id,
+            address: 0,
+            size: size as usize,
+            srclocs,
+            traps: traps.0,
+            returns: returns.returns,
+        });
+    }
+
+    /// Looks up where every function ended up, once they're all finalized.
+    pub fn finalize(&mut self, module: &JITModule) {
+        for function in &mut self.functions {
+            function.address = module.get_finalized_function(function.id) as usize;
+        }
+    }
+
+    /// The function with code at an address, and the offset into it.
+    pub(crate) fn function_at(&self, address: usize) -> Option<(&CompiledFunction, CodeOffset)> {
+        self.functions.iter()
+            .find(|function| (function.address..function.address + function.size).contains(&address))
+            .map(|function| (function, (address - function.address) as CodeOffset))
+    }
+}
+
+impl CompiledFunction {
+    pub(crate) fn srcloc_at(&self, offset: CodeOffset) -> Option<SourceLoc> {
+        self.srclocs.iter().find(|(range, _)| range.contains(&offset)).map(|(_, loc)| *loc)
+    }
+}
+
+/// Collects where a function traps and why.
+#[derive(Default)]
+pub struct Traps(Vec<(CodeOffset, SourceLoc, TrapCode)>);
+
+impl TrapSink for Traps {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        self.0.push((offset, srcloc, code));
+    }
+}
+
+// Only keeps track of where calls return to, as the compiled code is written
+// out again
+#[derive(Default)]
+struct CallReturns {
+    offset: CodeOffset,
+    returns: Vec<CodeOffset>,
+}
+
+impl CodeSink for CallReturns {
+    fn offset(&self) -> CodeOffset {
+        self.offset
+    }
+
+    fn put1(&mut self, _: u8) {
+        self.offset += 1;
+    }
+
+    fn put2(&mut self, _: u16) {
+        self.offset += 2;
+    }
+
+    fn put4(&mut self, _: u32) {
+        self.offset += 4;
+    }
+
+    fn put8(&mut self, _: u64) {
+        self.offset += 8;
+    }
+
+    fn reloc_external(&mut self, _: SourceLoc, _: Reloc, _: &ExternalName, _: Addend) {}
+
+    fn reloc_constant(&mut self, _: Reloc, _: ConstantOffset) {}
+
+    fn reloc_jt(&mut self, _: Reloc, _: JumpTable) {}
+
+    fn trap(&mut self, _: TrapCode, _: SourceLoc) {}
+
+    fn begin_jumptables(&mut self) {}
+
+    fn begin_rodata(&mut self) {}
+
+    fn end_codegen(&mut self) {}
+
+    fn add_stack_map(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa) {}
+
+    fn add_call_site(&mut self, _: Opcode, _: SourceLoc) {
+        self.returns.push(self.offset);
+    }
+}
@@ -0,0 +1,546 @@
+//! Runs LIR directly, without Cranelift. Builtins are the same functions of
+//! `gold_standard` the compiled code calls, so a program behaves the same
+//! either way and each backend can be checked against the other. Lists and
+//! maps are the exception: they hold interpreter values, so they are kept here.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::rc::Rc;
+
+use gold_frontend::frontend::Type;
+use gold_standard::ffi;
+use gold_standard::load::{ABS_FLOAT_SYMBOL, ABS_INT_SYMBOL, ACOS_SYMBOL, ASIN_SYMBOL, ASSERT_EQ_BOOL_SYMBOL, ASSERT_EQ_FLOAT_SYMBOL, ASSERT_EQ_INT_SYMBOL, ASSERT_EQ_STRING_SYMBOL, ASSERT_SYMBOL, ATAN2_SYMBOL, ATAN_SYMBOL, CEIL_SYMBOL, CLAMP_FLOAT_SYMBOL, CLAMP_INT_SYMBOL, CLOSE_FILE_SYMBOL, COS_SYMBOL, EPRINTLN_SYMBOL, EPRINT_SYMBOL, EXP_SYMBOL, FLOOR_SYMBOL, GCD_SYMBOL, IO_ERROR_SYMBOL, IPOWI_SYMBOL, LIST_APPEND_SYMBOL, LIST_GET_SYMBOL, LIST_LEN_SYMBOL, LIST_NEW_SYMBOL, LIST_PUSH_SYMBOL, LN_SYMBOL, LOG10_SYMBOL, LOG2_SYMBOL, MAP_CONTAINS_SYMBOL, MAP_GET_SYMBOL, MAP_INSERT_SYMBOL, MAP_KEYS_SYMBOL, MAP_LEN_SYMBOL, MAP_NEW_SYMBOL, MAP_REMOVE_SYMBOL, MAX_FLOAT_SYMBOL, MAX_INT_SYMBOL, MIN_FLOAT_SYMBOL, MIN_INT_SYMBOL, OPEN_FILE_SYMBOL, POWF_SYMBOL, PRINTLN_SYMBOL, PRINT_FLOAT_SYMBOL, PRINT_INT_SYMBOL, PRINT_SYMBOL, RANDOM_FLOAT_SYMBOL, RANDOM_INT_SYMBOL, READ_FILE_SYMBOL, READ_INT_SYMBOL, READ_LINE_SYMBOL, RELEASE_SYMBOL, ROUND_SYMBOL, SEED_RANDOM_SYMBOL, SIN_SYMBOL, SQRT_SYMBOL, STRCMP_SYMBOL, STRING_CHARS_SYMBOL, STRING_CONCAT_SYMBOL, STRING_FROM_BOOL_SYMBOL, STRING_FROM_C_SYMBOL, STRING_FROM_FLOAT_DIGITS_SYMBOL, STRING_FROM_FLOAT_SYMBOL, STRING_FROM_INT_SYMBOL, SYMBOLS, TAN_SYMBOL, TO_FLOAT_SYMBOL, TO_INT_SYMBOL, WRITE_FILE_SYMBOL};
+
+use crate::lir::{self, BinaryOp, Callee, Function, Kind, Node};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f32),
+    Bool(bool),
+    String(Rc<CString>),
+    /// A structure, or an enum value with its tag in the first field
+    Record(Rc<RefCell<Vec<Value>>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    /// A map, with its entries in the order they were inserted
+    Map(Rc<RefCell<Vec<(Value, Value)>>>),
+    Pointer(*mut c_void),
+    /// The address of a function, by its symbol
+    Function(String),
+    Void,
+}
+
+impl Value {
+    /// What a slot holds before anything is stored in it, which is what the
+    /// compiled code reads from one: zero, or a null pointer.
+    pub fn zero(ty: &Type) -> Value {
+        match ty {
+            Type::Int => Value::Int(0),
+            Type::Float => Value::Float(0.0),
+            Type::Bool => Value::Bool(false),
+            Type::Void => Value::Void,
+            _ => Value::Pointer(std::ptr::null_mut()),
+        }
+    }
+
+    fn int(&self) -> i64 {
+        match self {
+            Value::Int(n) => *n,
+            Value::Bool(b) => *b as i64,
+            Value::Pointer(p) => *p as i64,
+            Value::String(s) => s.as_ptr() as i64,
+            Value::Record(record) | Value::List(record) => record.as_ptr() as i64,
+            Value::Map(map) => map.as_ptr() as i64,
+            _ => unreachable!()
+        }
+    }
+
+    fn float(&self) -> f32 {
+        match self {
+            Value::Float(f) => *f,
+            _ => unreachable!()
+        }
+    }
+
+    fn bool(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            _ => unreachable!()
+        }
+    }
+
+    fn string(&self) -> *mut c_char {
+        match self {
+            Value::String(s) => s.as_ptr() as *mut c_char,
+            Value::Pointer(p) => *p as *mut c_char,
+            _ => unreachable!()
+        }
+    }
+
+    fn record(self) -> Result<Rc<RefCell<Vec<Value>>>, Error> {
+        match self {
+            Value::Record(record) => Ok(record),
+            // Nothing was stored in the slot it came from, which the compiled
+            // code crashes on
+            Value::Pointer(p) if p.is_null() => Err(Error::Trap("invalid memory access".to_owned())),
+            _ => unreachable!()
+        }
+    }
+}
+
+/// Why the interpreter stopped before the end of the program.
+#[derive(Debug)]
+pub enum Error {
+    /// Something the compiled program would crash on, such as dividing by zero
+    Trap(String),
+    /// Something the interpreter can't do, such as calling the entry point
+    /// with a `String`
+    Unsupported(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Trap(message) | Error::Unsupported(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+fn trap(message: &str) -> Error {
+    Error::Trap(message.to_owned())
+}
+
+// Set by `break` and `continue`, until the loop they're in gets to it
+#[derive(Clone, Copy, PartialEq)]
+enum Jump {
+    Break,
+    Continue,
+}
+
+pub struct Interpreter<'a> {
+    // Every function by the symbol it's called by
+    functions: HashMap<&'a str, &'a Function>,
+    entry: Option<&'a Function>,
+    externals: HashMap<&'a str, *const u8>,
+    jump: Cell<Option<Jump>>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Takes modules the way `Compilation` does, with the program last.
+    pub fn new(modules: &'a [lir::Module]) -> Self {
+        let functions = modules.iter()
+            .flat_map(|module| &module.functions)
+            .map(|function| (function.symbol.as_str(), function))
+            .collect();
+        let entry = modules.last().and_then(lir::Module::entry);
+        let externals = modules.iter()
+            .flat_map(|module| &module.externals)
+            .filter_map(|external| Some((external.symbol.as_str(), ffi::resolve(&external.library, &external.symbol)?)))
+            .collect();
+        Self { functions, entry, externals, jump: Cell::new(None) }
+    }
+
+    /// Runs the entry point, printing what it returns the way the compiled
+    /// program would.
+    pub fn run(&self) -> Result<(), Error> {
+        if let Some(entry) = self.entry {
+            entry.signature.check_entry(&entry.name).map_err(Error::Unsupported)?;
+            let args = entry.signature.params.iter().zip(1..).map(|(_, n)| Value::Int(n)).collect();
+            match self.call(&entry.symbol, args)? {
+                Value::Void => {}
+                Value::Float(f) => println!("{}", f),
+                value => println!("{}", value.int()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls a function of the program or of a module it uses.
+    pub fn call(&self, symbol: &str, args: Vec<Value>) -> Result<Value, Error> {
+        let function = self.functions.get(symbol)
+            .ok_or_else(|| Error::Unsupported(format!("the function '{}' does not exist", symbol)))?;
+        let mut slots = function.slots.iter().map(Value::zero).collect::<Vec<Value>>();
+        for (slot, arg) in slots.iter_mut().zip(args) {
+            *slot = arg;
+        }
+        let value = self.eval(&function.body, &mut slots)?;
+        Ok(match (&function.signature.returns, value) {
+            (Type::Void, _) => Value::Void,
+            // An if without an else can end a function that returns something,
+            // which then gives back zero when no branch is taken
+            (ty, Value::Void) => Value::zero(ty),
+            (_, value) => value,
+        })
+    }
+
+    fn eval(&self, node: &Node, slots: &mut Vec<Value>) -> Result<Value, Error> {
+        Ok(match &node.kind {
+            Kind::Int(n) => Value::Int(*n),
+            Kind::Bool(b) => Value::Bool(*b),
+            Kind::String(s) => Value::String(Rc::new(CString::new(s.as_str()).unwrap())),
+            Kind::Load(slot) => slots[slot.0].clone(),
+            Kind::Address(symbol, _) => Value::Function(symbol.to_owned()),
+            Kind::Store(slot, value) => {
+                let value = self.eval(value, slots)?;
+                slots[slot.0] = value.clone();
+                value
+            }
+            Kind::Binary(op, lhs, rhs) => {
+                let lhs_value = self.eval(lhs, slots)?;
+                let rhs_value = self.eval(rhs, slots)?;
+                binary(*op, &lhs.ty, lhs_value, rhs_value)?
+            }
+            Kind::Negate(value) => match self.eval(value, slots)? {
+                Value::Float(f) => Value::Float(-f),
+                value => Value::Int(value.int().wrapping_neg()),
+            },
+            Kind::Call(callee, args) => {
+                let args = args.iter().map(|arg| self.eval(arg, slots)).collect::<Result<Vec<Value>, Error>>()?;
+                match callee {
+                    Callee::Builtin(name) => match collection(name, &args) {
+                        Some(value) => value?,
+                        None => builtin(name, &args),
+                    },
+                    Callee::Function(symbol) => match self.externals.get(symbol.as_str()) {
+                        Some(address) => call_external(symbol, *address, &args, &node.ty)?,
+                        None => self.call(symbol, args)?,
+                    },
+                    Callee::Closure => {
+                        let symbol = match &args[0].clone().record()?.borrow()[0] {
+                            Value::Function(symbol) => symbol.to_owned(),
+                            _ => unreachable!()
+                        };
+                        self.call(&symbol, args)?
+                    }
+                }
+            }
+            Kind::Allocate(tag, fields) => {
+                let mut values = Vec::new();
+                if let Some(tag) = tag {
+                    values.push(Value::Int(*tag as i64));
+                }
+                for field in fields {
+                    values.push(self.eval(field, slots)?);
+                }
+                Value::Record(Rc::new(RefCell::new(values)))
+            }
+            Kind::GetField(record, index) => self.eval(record, slots)?.record()?.borrow()[*index].clone(),
+            Kind::SetField(record, index, value) => {
+                let record = self.eval(record, slots)?.record()?;
+                let value = self.eval(value, slots)?;
+                record.borrow_mut()[*index] = value.clone();
+                value
+            }
+            Kind::If(cond, body, otherwise) => {
+                let value = match self.eval(cond, slots)?.bool() {
+                    true => self.eval(body, slots)?,
+                    false => self.eval(otherwise, slots)?,
+                };
+                if node.ty == Type::Void { Value::Void } else { value }
+            }
+            Kind::Loop(cond, body, step) => {
+                while self.eval(cond, slots)?.bool() {
+                    self.eval(body, slots)?;
+                    if self.jump.take() == Some(Jump::Break) {
+                        break;
+                    }
+                    self.eval(step, slots)?;
+                }
+                Value::Void
+            }
+            Kind::Break => {
+                self.jump.set(Some(Jump::Break));
+                Value::Void
+            }
+            Kind::Continue => {
+                self.jump.set(Some(Jump::Continue));
+                Value::Void
+            }
+            Kind::Match(value, arms, _) => {
+                let record = self.eval(value, slots)?.record()?;
+                let tag = record.borrow()[0].int() as usize;
+                let arm = arms.iter().find(|arm| arm.tag == Some(tag))
+                    .or_else(|| arms.iter().find(|arm| arm.tag.is_none()))
+                    .ok_or_else(|| trap("no arm of the match was taken"))?;
+                for (slot, index) in &arm.bindings {
+                    slots[slot.0] = record.borrow()[*index].clone();
+                }
+                let value = self.eval(&arm.body, slots)?;
+                if node.ty == Type::Void { Value::Void } else { value }
+            }
+            Kind::Block(nodes) => {
+                let mut last = Value::Void;
+                for node in nodes {
+                    last = self.eval(node, slots)?;
+                    // The rest is skipped on the way to the loop
+                    if self.jump.get().is_some() {
+                        return Ok(Value::Void);
+                    }
+                }
+                last
+            }
+        })
+    }
+}
+
+fn binary(op: BinaryOp, ty: &Type, lhs: Value, rhs: Value) -> Result<Value, Error> {
+    if *ty == Type::Float {
+        let (a, b) = (lhs.float(), rhs.float());
+        return Ok(match op {
+            BinaryOp::Add => Value::Float(a + b),
+            BinaryOp::Subtract => Value::Float(a - b),
+            BinaryOp::Multiply => Value::Float(a * b),
+            BinaryOp::Divide => Value::Float(a / b),
+            BinaryOp::Equal => Value::Bool(a == b),
+            BinaryOp::NotEqual => Value::Bool(a != b),
+            BinaryOp::GreaterThan => Value::Bool(a > b),
+            BinaryOp::LessThan => Value::Bool(a < b),
+            BinaryOp::GreaterThanEqual => Value::Bool(a >= b),
+            BinaryOp::LessThanEqual => Value::Bool(a <= b),
+        });
+    }
+    // Everything else is compared as an integer, and by address when it's
+    // behind a pointer
+    let (a, b) = (lhs.int(), rhs.int());
+    Ok(match op {
+        BinaryOp::Add => Value::Int(a.wrapping_add(b)),
+        BinaryOp::Subtract => Value::Int(a.wrapping_sub(b)),
+        BinaryOp::Multiply => Value::Int(a.wrapping_mul(b)),
+        BinaryOp::Divide if b == 0 => return Err(trap("division by zero")),
+        BinaryOp::Divide => Value::Int(a.checked_div(b).ok_or_else(|| trap("integer overflow"))?),
+        BinaryOp::Equal => Value::Bool(a == b),
+        BinaryOp::NotEqual => Value::Bool(a != b),
+        BinaryOp::GreaterThan => Value::Bool(a > b),
+        BinaryOp::LessThan => Value::Bool(a < b),
+        BinaryOp::GreaterThanEqual => Value::Bool(a >= b),
+        BinaryOp::LessThanEqual => Value::Bool(a <= b),
+    })
+}
+
+/// Runs a function on a list or map, or gives `None` for any other builtin.
+fn collection(name: &str, args: &[Value]) -> Option<Result<Value, Error>> {
+    let entry = |map: &[(Value, Value)], key: &Value| map.iter().position(|(k, _)| k == key);
+    Some(Ok(match (name, args) {
+        (LIST_NEW_SYMBOL, _) => Value::List(Rc::default()),
+        (MAP_NEW_SYMBOL, _) => Value::Map(Rc::default()),
+        (LIST_PUSH_SYMBOL, [Value::List(list), item]) => {
+            list.borrow_mut().push(item.clone());
+            Value::Void
+        }
+        (LIST_APPEND_SYMBOL, [Value::List(list), item]) => {
+            list.borrow_mut().push(item.clone());
+            Value::List(list.clone())
+        }
+        (LIST_GET_SYMBOL, [Value::List(list), index]) => {
+            let list = list.borrow();
+            // A negative index is past the end too, as it is in compiled code
+            let item = list.get(index.int() as usize);
+            return Some(item.cloned().ok_or_else(|| trap("the index is past the end of the list")));
+        }
+        (LIST_LEN_SYMBOL, [Value::List(list)]) => Value::Int(list.borrow().len() as i64),
+        (STRING_CHARS_SYMBOL, [Value::String(string)]) => {
+            let chars = string.to_string_lossy().chars()
+                .map(|c| Value::String(Rc::new(CString::new(c.to_string()).unwrap())))
+                .collect();
+            Value::List(Rc::new(RefCell::new(chars)))
+        }
+        (MAP_INSERT_SYMBOL, [Value::Map(map), key, value]) => {
+            let mut map = map.borrow_mut();
+            match entry(&map, key) {
+                Some(index) => map[index].1 = value.clone(),
+                None => map.push((key.clone(), value.clone())),
+            }
+            Value::Void
+        }
+        (MAP_GET_SYMBOL, [Value::Map(map), key]) => {
+            let map = map.borrow();
+            let value = entry(&map, key).map(|index| map[index].1.clone());
+            return Some(value.ok_or_else(|| trap("the map doesn't have the key")));
+        }
+        (MAP_CONTAINS_SYMBOL, [Value::Map(map), key]) => Value::Bool(entry(&map.borrow(), key).is_some()),
+        (MAP_REMOVE_SYMBOL, [Value::Map(map), key]) => {
+            let mut map = map.borrow_mut();
+            let index = entry(&map, key);
+            Value::Bool(index.map(|index| map.remove(index)).is_some())
+        }
+        (MAP_LEN_SYMBOL, [Value::Map(map)]) => Value::Int(map.borrow().len() as i64),
+        // In order, the way the runtime gives them
+        (MAP_KEYS_SYMBOL, [Value::Map(map)]) => {
+            let mut keys = map.borrow().iter().map(|(key, _)| key.clone()).collect::<Vec<Value>>();
+            keys.sort_by(|a, b| match (a, b) {
+                (Value::String(a), Value::String(b)) => a.cmp(b),
+                (a, b) => a.int().cmp(&b.int()),
+            });
+            Value::List(Rc::new(RefCell::new(keys)))
+        }
+        _ => return None,
+    }))
+}
+
+fn address(name: &str) -> *const u8 {
+    SYMBOLS.iter().find(|(symbol, _)| *symbol == name).unwrap().1
+}
+
+/// Calls one of the runtime functions, with the signature `gold_standard`
+/// declares it with.
+fn builtin(name: &str, args: &[Value]) -> Value {
+    let code = address(name);
+    // Strings made by the runtime are copied, and the runtime's reference to
+    // them released
+    let owned = |string: *mut c_char| unsafe {
+        let copy = CStr::from_ptr(string).to_owned();
+        mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char)>(address(RELEASE_SYMBOL))(string);
+        Value::String(Rc::new(copy))
+    };
+    unsafe {
+        match name {
+            PRINT_SYMBOL | PRINTLN_SYMBOL | EPRINT_SYMBOL | EPRINTLN_SYMBOL => {
+                let print = mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char) -> i64>(code);
+                Value::Int(print(args[0].string()))
+            }
+            PRINT_INT_SYMBOL => {
+                mem::transmute::<*const u8, unsafe extern "C" fn(i64)>(code)(args[0].int());
+                Value::Void
+            }
+            PRINT_FLOAT_SYMBOL => {
+                mem::transmute::<*const u8, unsafe extern "C" fn(f64)>(code)(args[0].float() as f64);
+                Value::Void
+            }
+            READ_LINE_SYMBOL | IO_ERROR_SYMBOL => {
+                owned(mem::transmute::<*const u8, unsafe extern "C" fn() -> *mut c_char>(code)())
+            }
+            READ_INT_SYMBOL => Value::Int(mem::transmute::<*const u8, unsafe extern "C" fn() -> i64>(code)()),
+            OPEN_FILE_SYMBOL => {
+                let open = mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char, *mut c_char) -> i64>(code);
+                Value::Int(open(args[0].string(), args[1].string()))
+            }
+            READ_FILE_SYMBOL => {
+                let read = mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> *mut c_char>(code);
+                owned(read(args[0].int()))
+            }
+            WRITE_FILE_SYMBOL => {
+                let write = mem::transmute::<*const u8, unsafe extern "C" fn(i64, *mut c_char) -> i64>(code);
+                Value::Int(write(args[0].int(), args[1].string()))
+            }
+            CLOSE_FILE_SYMBOL => {
+                let close = mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> i64>(code);
+                Value::Int(close(args[0].int()))
+            }
+            IPOWI_SYMBOL => {
+                let ipowi = mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64) -> i64>(code);
+                Value::Int(ipowi(args[0].int(), args[1].int()))
+            }
+            ABS_INT_SYMBOL => Value::Int(mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> i64>(code)(args[0].int())),
+            MIN_INT_SYMBOL | MAX_INT_SYMBOL | GCD_SYMBOL | RANDOM_INT_SYMBOL => {
+                let compute = mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64) -> i64>(code);
+                Value::Int(compute(args[0].int(), args[1].int()))
+            }
+            CLAMP_INT_SYMBOL => {
+                let clamp = mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, i64) -> i64>(code);
+                Value::Int(clamp(args[0].int(), args[1].int(), args[2].int()))
+            }
+            ABS_FLOAT_SYMBOL | SQRT_SYMBOL | FLOOR_SYMBOL | CEIL_SYMBOL | ROUND_SYMBOL | SIN_SYMBOL | COS_SYMBOL | TAN_SYMBOL
+            | ASIN_SYMBOL | ACOS_SYMBOL | ATAN_SYMBOL | EXP_SYMBOL | LN_SYMBOL | LOG10_SYMBOL | LOG2_SYMBOL => {
+                let compute = mem::transmute::<*const u8, unsafe extern "C" fn(f64) -> f64>(code);
+                Value::Float(compute(args[0].float() as f64) as f32)
+            }
+            MIN_FLOAT_SYMBOL | MAX_FLOAT_SYMBOL | ATAN2_SYMBOL | POWF_SYMBOL => {
+                let compute = mem::transmute::<*const u8, unsafe extern "C" fn(f64, f64) -> f64>(code);
+                Value::Float(compute(args[0].float() as f64, args[1].float() as f64) as f32)
+            }
+            CLAMP_FLOAT_SYMBOL => {
+                let clamp = mem::transmute::<*const u8, unsafe extern "C" fn(f64, f64, f64) -> f64>(code);
+                Value::Float(clamp(args[0].float() as f64, args[1].float() as f64, args[2].float() as f64) as f32)
+            }
+            TO_FLOAT_SYMBOL => Value::Float(mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> f64>(code)(args[0].int()) as f32),
+            TO_INT_SYMBOL => Value::Int(mem::transmute::<*const u8, unsafe extern "C" fn(f64) -> i64>(code)(args[0].float() as f64)),
+            SEED_RANDOM_SYMBOL => {
+                mem::transmute::<*const u8, unsafe extern "C" fn(i64)>(code)(args[0].int());
+                Value::Void
+            }
+            RANDOM_FLOAT_SYMBOL => Value::Float(mem::transmute::<*const u8, unsafe extern "C" fn() -> f64>(code)() as f32),
+            STRCMP_SYMBOL => {
+                let compare = mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char, *mut c_char) -> i64>(code);
+                Value::Int(compare(args[0].string(), args[1].string()))
+            }
+            STRING_CONCAT_SYMBOL => {
+                let concat = mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char, *mut c_char) -> *mut c_char>(code);
+                owned(concat(args[0].string(), args[1].string()))
+            }
+            STRING_FROM_INT_SYMBOL | STRING_FROM_BOOL_SYMBOL => {
+                let convert = mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> *mut c_char>(code);
+                owned(convert(args[0].int()))
+            }
+            STRING_FROM_FLOAT_SYMBOL => {
+                let convert = mem::transmute::<*const u8, unsafe extern "C" fn(f64) -> *mut c_char>(code);
+                owned(convert(args[0].float() as f64))
+            }
+            STRING_FROM_FLOAT_DIGITS_SYMBOL => {
+                let convert = mem::transmute::<*const u8, unsafe extern "C" fn(f64, i64) -> *mut c_char>(code);
+                owned(convert(args[0].float() as f64, args[1].int()))
+            }
+            STRING_FROM_C_SYMBOL => {
+                let copy = mem::transmute::<*const u8, unsafe extern "C" fn(*const c_char) -> *mut c_char>(code);
+                let string = match &args[0] {
+                    Value::Pointer(p) => *p as *const c_char,
+                    value => value.string(),
+                };
+                owned(copy(string))
+            }
+            ASSERT_SYMBOL => {
+                let assert = mem::transmute::<*const u8, unsafe extern "C" fn(i64, *mut c_char, i64, i64)>(code);
+                assert(args[0].int(), args[1].string(), args[2].int(), args[3].int());
+                Value::Void
+            }
+            ASSERT_EQ_INT_SYMBOL | ASSERT_EQ_BOOL_SYMBOL => {
+                let assert = mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, *mut c_char, i64, i64)>(code);
+                assert(args[0].int(), args[1].int(), args[2].string(), args[3].int(), args[4].int());
+                Value::Void
+            }
+            ASSERT_EQ_FLOAT_SYMBOL => {
+                let assert = mem::transmute::<*const u8, unsafe extern "C" fn(f64, f64, *mut c_char, i64, i64)>(code);
+                assert(args[0].float() as f64, args[1].float() as f64, args[2].string(), args[3].int(), args[4].int());
+                Value::Void
+            }
+            ASSERT_EQ_STRING_SYMBOL => {
+                let assert = mem::transmute::<*const u8, unsafe extern "C" fn(*mut c_char, *mut c_char, *mut c_char, i64, i64)>(code);
+                assert(args[0].string(), args[1].string(), args[2].string(), args[3].int(), args[4].int());
+                Value::Void
+            }
+            _ => unreachable!()
+        }
+    }
+}
+
+/// Calls a C function. Integers, pointers and strings are all passed in
+/// integer registers, so any mix of them can go through the same signature.
+fn call_external(symbol: &str, code: *const u8, args: &[Value], returns: &Type) -> Result<Value, Error> {
+    if args.iter().any(|arg| matches!(arg, Value::Float(_))) || *returns == Type::Float {
+        return Err(Error::Unsupported(format!("the interpreter can't pass a Float to or from the external function '{}'", symbol)));
+    }
+    let a = args.iter().map(Value::int).collect::<Vec<i64>>();
+    // What a function without a result leaves in the return register is
+    // never looked at
+    let result = unsafe {
+        match a.len() {
+            0 => mem::transmute::<*const u8, unsafe extern "C" fn() -> i64>(code)(),
+            1 => mem::transmute::<*const u8, unsafe extern "C" fn(i64) -> i64>(code)(a[0]),
+            2 => mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64) -> i64>(code)(a[0], a[1]),
+            3 => mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, i64) -> i64>(code)(a[0], a[1], a[2]),
+            4 => mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, i64, i64) -> i64>(code)(a[0], a[1], a[2], a[3]),
+            5 => mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, i64, i64, i64) -> i64>(code)(a[0], a[1], a[2], a[3], a[4]),
+            6 => mem::transmute::<*const u8, unsafe extern "C" fn(i64, i64, i64, i64, i64, i64) -> i64>(code)(a[0], a[1], a[2], a[3], a[4], a[5]),
+            _ => return Err(Error::Unsupported(format!("the interpreter can't pass more than 6 arguments to the external function '{}'", symbol))),
+        }
+    };
+    Ok(match returns {
+        Type::Int => Value::Int(result),
+        Type::Void => Value::Void,
+        // Strings are copied by a call to `string_from_c` around this one
+        _ => Value::Pointer(result as *mut c_void),
+    })
+}
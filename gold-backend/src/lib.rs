@@ -1,3 +1,4 @@
 pub mod lir;
 pub mod analysis;
-pub mod codegen;
\ No newline at end of file
+pub mod codegen;
+pub mod interp;
\ No newline at end of file
@@ -0,0 +1,129 @@
+//! How LIR is turned into machine code: how hard Cranelift optimizes, whether
+//! it checks its own output, which CPU the code is for and whether debuggers
+//! are told about it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use cranelift::prelude::isa::{self, TargetIsa};
+use cranelift::prelude::settings::{self, Configurable};
+use target_lexicon::Triple;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OptLevel {
+    None,
+    Speed,
+    SpeedAndSize,
+}
+
+impl OptLevel {
+    /// The name Cranelift's `opt_level` setting and the command line use.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed_and_size",
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(OptLevel::None),
+            "speed" => Ok(OptLevel::Speed),
+            "speed_and_size" => Ok(OptLevel::SpeedAndSize),
+            _ => Err(format!("unknown optimization level '{}', expected none, speed or speed_and_size", s)),
+        }
+    }
+}
+
+impl fmt::Display for OptLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub opt_level: OptLevel,
+    /// Runs Cranelift's verifier on every function before compiling it
+    pub verify: bool,
+    /// Lets the code use every feature of the CPU it is compiled on, rather
+    /// than only what every CPU of the architecture has. Ignored for other
+    /// targets.
+    pub host_features: bool,
+    /// Another target to compile for, whose code can't run here, so it's only
+    /// emitted as assembly or an object file. `None` is the host.
+    pub target: Option<Triple>,
+    /// Describes the compiled code to gdb, so it can show the Gold source
+    /// and variables of the program it's debugging
+    pub debug_info: bool,
+}
+
+impl Default for Config {
+    /// The settings Cranelift's JIT uses on its own.
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevel::None,
+            verify: true,
+            host_features: true,
+            target: None,
+            debug_info: false,
+        }
+    }
+}
+
+impl Config {
+    /// Parses a target triple such as `aarch64-unknown-linux-gnu`, checking
+    /// that Cranelift can compile for it.
+    pub fn parse_target(triple: &str) -> Result<Triple, String> {
+        let triple = Triple::from_str(triple).map_err(|e| format!("invalid target '{}': {}", triple, e))?;
+        isa::lookup(triple.clone()).map_err(|e| format!("unsupported target '{}': {}", triple, e))?;
+        // Gold values are laid out for the pointer size of the host
+        if triple.pointer_width() != Triple::host().pointer_width() {
+            return Err(format!("unsupported target '{}': pointers have to be as wide as on this machine", triple));
+        }
+        Ok(triple)
+    }
+
+    /// The instruction set to compile for, with these settings.
+    pub fn isa(&self) -> Result<Box<dyn TargetIsa>, String> {
+        // Same as the JIT sets up, since functions aren't linked together
+        // ahead of time
+        self.isa_with_pic(false)
+    }
+
+    /// The instruction set to compile object files for, whose code can be
+    /// loaded anywhere, as executables usually are.
+    pub fn object_isa(&self) -> Result<Box<dyn TargetIsa>, String> {
+        self.isa_with_pic(true)
+    }
+
+    fn isa_with_pic(&self, pic: bool) -> Result<Box<dyn TargetIsa>, String> {
+        let mut flags = settings::builder();
+        flags.set("opt_level", self.opt_level.as_str()).unwrap();
+        flags.set("enable_verifier", if self.verify { "true" } else { "false" }).unwrap();
+        flags.set("use_colocated_libcalls", "false").unwrap();
+        flags.set("is_pic", if pic { "true" } else { "false" }).unwrap();
+
+        let builder = match &self.target {
+            Some(triple) => isa::lookup(triple.clone()).map_err(|e| e.to_string())?,
+            None => cranelift_native::builder_with_options(isa::BackendVariant::Any, self.host_features)?,
+        };
+        Ok(builder.finish(settings::Flags::new(flags)))
+    }
+
+    /// Whether the code is for another machine than the one the compiler
+    /// runs on.
+    pub fn cross_compiling(&self) -> bool {
+        self.target.as_ref().is_some_and(|target| *target != Triple::host())
+    }
+
+    /// The same settings for the machine the compiler runs on.
+    pub fn host(&self) -> Config {
+        Config { target: None, ..self.clone() }
+    }
+}
@@ -0,0 +1,668 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use gold_frontend::frontend::{Expr, Parameter, Type};
+use gold_standard::load::{ABS_FLOAT_SYMBOL, ABS_INT_SYMBOL, ABS_SYMBOL, ASSERT_EQ_BOOL_SYMBOL, ASSERT_EQ_FLOAT_SYMBOL, ASSERT_EQ_INT_SYMBOL, ASSERT_EQ_STRING_SYMBOL, ASSERT_EQ_SYMBOL, ASSERT_SYMBOL, CLAMP_FLOAT_SYMBOL, CLAMP_INT_SYMBOL, CLAMP_SYMBOL, CONTAINS_SYMBOL, FILTER_SYMBOL, FOLD_SYMBOL, GET_SYMBOL, INSERT_SYMBOL, IPOWI_SYMBOL, KEYS_SYMBOL, LEN_SYMBOL, LIST_APPEND_SYMBOL, LIST_GET_SYMBOL, LIST_LEN_SYMBOL, LIST_NEW_SYMBOL, LIST_PUSH_SYMBOL, LIST_SYMBOL, MAP_CONTAINS_SYMBOL, MAP_GET_SYMBOL, MAP_INSERT_SYMBOL, MAP_KEYS_SYMBOL, MAP_FN_SYMBOL, MAP_LEN_SYMBOL, MAP_NEW_SYMBOL, MAP_REMOVE_SYMBOL, MAP_SYMBOL, MAX_FLOAT_SYMBOL, MAX_INT_SYMBOL, MAX_SYMBOL, MIN_FLOAT_SYMBOL, MIN_INT_SYMBOL, MIN_SYMBOL, POWF_SYMBOL, PRINTF_SYMBOL, PRINT_SYMBOL, PUSH_SYMBOL, REMOVE_SYMBOL, STRCMP_SYMBOL, STRING_CHARS_SYMBOL, STRING_CONCAT_SYMBOL, STRING_FROM_BOOL_SYMBOL, STRING_FROM_C_SYMBOL, STRING_FROM_FLOAT_DIGITS_SYMBOL, STRING_FROM_FLOAT_SYMBOL, STRING_FROM_INT_SYMBOL};
+
+use crate::analysis::collections;
+use crate::analysis::format::{self, Piece};
+use crate::analysis::lower::{lambda_name, Analyzer, Lower, VarSig};
+use crate::codegen::refcount::counted;
+use crate::lir::{Arm, BinaryOp, Callee, External, Function, Kind, Module, Node, Signature, Slot};
+
+impl Analyzer {
+    /// Lowers the items of a checked module, which has to be the one this
+    /// analyzer checked. Functions of a module used by the program are
+    /// compiled as `namespace.function`.
+    pub fn lower(&self, items: &[Expr], namespace: Option<&str>) -> Module {
+        let local_functions = items.iter().filter_map(|item| match item {
+            Expr::Function(name, _, _, _, _) | Expr::Test(name, _, _) => Some(name.as_str()),
+            _ => None,
+        }).collect::<HashSet<&str>>();
+
+        let mut functions = Vec::new();
+        let mut lifted = Vec::new();
+        let mut externals = Vec::new();
+        for item in items {
+            match item {
+                Expr::Function(name, params, ty, body, _) => {
+                    let params = params.iter().map(|(param, _)| (param.name.as_str(), param.typename.clone())).collect();
+                    let (function, anonymous) = self.lower_function(name, params, ty.clone(), body, &local_functions, namespace);
+                    functions.push(function);
+                    lifted.extend(anonymous);
+                }
+                Expr::Test(name, body, _) => {
+                    let (mut test, anonymous) = self.lower_function(name, Vec::new(), Type::Void, body, &local_functions, namespace);
+                    test.test = true;
+                    functions.push(test);
+                    lifted.extend(anonymous);
+                }
+                Expr::External(name, _, _, library, _) => externals.push(External {
+                    symbol: name.to_owned(),
+                    library: library.to_owned(),
+                }),
+                _ => {}
+            }
+        }
+        // A function used as a value in several places gets the same wrapper
+        // each time, which only needs compiling once
+        let mut symbols = HashSet::new();
+        functions.extend(lifted.into_iter().filter(|function| symbols.insert(function.symbol.to_owned())));
+        Module { functions, externals, file: self.filename.to_owned(), source: self.source.to_owned() }
+    }
+
+    /// Lowers a function, along with the functions lifted out of it for its
+    /// anonymous functions and the functions it uses as values.
+    fn lower_function(&self, name: &str, params: Vec<(&str, Type)>, returns: Type, body: &Expr, local_functions: &HashSet<&str>, namespace: Option<&str>) -> (Function, Vec<Function>) {
+        let mut lowering = FunctionLowering {
+            analyzer: self,
+            scope: HashMap::new(),
+            local_functions,
+            namespace,
+            function_name: name.to_owned(),
+            function_symbol: String::new(),
+            slots: HashMap::new(),
+            slot_types: Vec::new(),
+            slot_names: Vec::new(),
+            captured: Vec::new(),
+            lambdas: 0,
+            lifted: Vec::new(),
+        };
+        lowering.function_symbol = lowering.symbol(name);
+        for (param, ty) in &params {
+            lowering.declare(param, ty.clone());
+        }
+        let body = lowering.lower(body);
+        let function = Function {
+            name: name.to_owned(),
+            symbol: lowering.function_symbol,
+            signature: Signature {
+                params: params.into_iter().map(|(_, ty)| ty).collect(),
+                returns,
+            },
+            slots: lowering.slot_types,
+            names: lowering.slot_names,
+            body,
+            test: false,
+        };
+        (function, lowering.lifted)
+    }
+}
+
+struct FunctionLowering<'a> {
+    analyzer: &'a Analyzer,
+    // The variables in scope at the point being lowered, which changes the
+    // same way it did while the function was checked
+    scope: HashMap<String, VarSig>,
+    local_functions: &'a HashSet<&'a str>,
+    namespace: Option<&'a str>,
+    // The function being lowered, which its anonymous functions are named after
+    function_name: String,
+    function_symbol: String,
+    // The slot of each variable in scope. Every declaration gets a slot of
+    // its own, so variables of different blocks can share a name but not a type.
+    slots: HashMap<String, Slot>,
+    slot_types: Vec<Type>,
+    slot_names: Vec<String>,
+    // The variables from around an anonymous function that its body uses
+    captured: Vec<Slot>,
+    // How many anonymous functions have been lifted out of this one so far,
+    // and the functions lifted out of it
+    lambdas: usize,
+    lifted: Vec<Function>,
+}
+
+impl<'a> FunctionLowering<'a> {
+    fn ty(&self, expr: &Expr) -> Type {
+        expr.get_type(&self.analyzer.functions, &self.scope)
+    }
+
+    /// The slot of the variable a name refers to here. A variable that hasn't
+    /// been declared comes from around the anonymous function being lowered,
+    /// and gets a slot the first time it's used.
+    fn slot(&mut self, name: &str) -> Slot {
+        if let Some(slot) = self.slots.get(name) {
+            return *slot;
+        }
+        let slot = self.declare(name, self.scope[name].ty.clone());
+        self.captured.push(slot);
+        slot
+    }
+
+    /// Gives a new variable a slot of its own, hiding any variable of the
+    /// same name until the end of its block.
+    fn declare(&mut self, name: &str, ty: Type) -> Slot {
+        let slot = Slot(self.slot_types.len());
+        self.slot_types.push(ty.clone());
+        self.slot_names.push(name.to_owned());
+        self.slots.insert(name.to_owned(), slot);
+        self.scope.insert(name.to_owned(), VarSig { ty });
+        slot
+    }
+
+    // The name a function is compiled under, so functions with the same name
+    // in different modules don't clash
+    fn symbol(&self, name: &str) -> String {
+        match self.namespace {
+            Some(namespace) if self.local_functions.contains(name) => [namespace, name].join("."),
+            _ => name.to_owned(),
+        }
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Node {
+        let mut node = self.lower_expr(expr);
+        node.span.get_or_insert_with(|| expr.expression_range());
+        node
+    }
+
+    fn lower_expr(&mut self, expr: &Expr) -> Node {
+        // A variable of a branch the optimizer dropped, which is never stored
+        // to and so stays zero
+        if let Expr::Assign(name, value, loc) = expr {
+            if let Expr::NoExpr = value.as_ref() {
+                self.declare(name, self.analyzer.declarations[loc].clone());
+                return Node::new(Type::Void, Kind::Block(Vec::new()));
+            }
+        }
+        let ty = self.ty(expr);
+        let kind = match expr {
+            Expr::Number(n, _) => Kind::Int(*n),
+            Expr::Bool(b, _) => Kind::Bool(*b),
+            Expr::String(s, _) => Kind::String(s.to_owned()),
+            Expr::Interpolation(parts, _) => {
+                let mut pieces = parts.iter().map(|part| {
+                    let part = self.lower(part);
+                    to_string(part)
+                }).collect::<Vec<Node>>().into_iter();
+                let first = pieces.next().unwrap_or_else(|| Node::new(Type::String, Kind::String(String::new())));
+                return pieces.fold(first, |joined, piece| builtin(STRING_CONCAT_SYMBOL, Type::String, vec![joined, piece]));
+            }
+            Expr::Var(name, _) if self.scope.contains_key(name) => Kind::Load(self.slot(name)),
+            // Not a variable, so the analyzer has made sure it's a variant
+            // without values or a function
+            Expr::Var(name, _) if self.analyzer.functions[name].tag.is_some() => Kind::Allocate(self.analyzer.functions[name].tag, Vec::new()),
+            Expr::Var(name, _) => self.function_value(name, &ty),
+            Expr::Lambda(params, body, loc) => self.lambda(params, body, loc, &ty),
+            Expr::Assign(name, value, _) => {
+                let value = self.lower(value);
+                Kind::Store(self.declare(name, value.ty.clone()), Box::new(value))
+            }
+            Expr::Reassign(name, value, _) => {
+                let value = self.lower(value);
+                Kind::Store(self.slot(name), Box::new(value))
+            }
+            Expr::Block(stmts, _) => {
+                let stmts = stmts.iter().map(|stmt| self.lower(stmt)).collect::<Vec<Node>>();
+                let ty = stmts.last().map_or(Type::Void, |stmt| stmt.ty.clone());
+                return Node::new(ty, Kind::Block(stmts));
+            }
+            Expr::If(cond, body, elifs, else_body, _) => {
+                // Each elif is an if in the else branch of the one before it
+                let mut otherwise = match else_body.as_deref() {
+                    Some(Expr::Else(body, _)) => self.lower(body),
+                    _ => Node::new(Type::Void, Kind::Block(Vec::new())),
+                };
+                for elif in elifs.iter().flatten().rev() {
+                    if let Expr::Elif(cond, body, _) = elif {
+                        otherwise = self.branch(cond, body, otherwise);
+                    }
+                }
+                return self.branch(cond, body, otherwise);
+            }
+            Expr::Call(name, args, _, _) if self.scope.contains_key(name) => {
+                let mut values = vec![Node::new(self.scope[name].ty.clone(), Kind::Load(self.slot(name)))];
+                values.extend(args.iter().map(|arg| self.lower(arg)));
+                Kind::Call(Callee::Closure, values)
+            }
+            Expr::Call(name, args, loc, args_loc) if name == ASSERT_SYMBOL || name == ASSERT_EQ_SYMBOL => {
+                let mut args = args.iter().map(|arg| self.lower(arg)).collect::<Vec<Node>>();
+                let symbol = match args[0].ty {
+                    _ if name == ASSERT_SYMBOL => ASSERT_SYMBOL,
+                    Type::Float => ASSERT_EQ_FLOAT_SYMBOL,
+                    Type::Bool => ASSERT_EQ_BOOL_SYMBOL,
+                    Type::String => ASSERT_EQ_STRING_SYMBOL,
+                    _ => ASSERT_EQ_INT_SYMBOL,
+                };
+                // A failed assertion points at the whole call, up to its closing parenthesis
+                let source = &self.analyzer.source;
+                let end = source[args_loc.end..].find(')').map_or(args_loc.end, |close| args_loc.end + close + 1);
+                args.push(Node::new(Type::String, Kind::String(self.analyzer.filename.to_owned())));
+                args.push(Node::new(Type::Int, Kind::Int(loc.start as i64)));
+                args.push(Node::new(Type::Int, Kind::Int(end as i64)));
+                Kind::Call(Callee::Builtin(symbol.to_owned()), args)
+            }
+            Expr::Call(name, args, _, _) if name == PRINTF_SYMBOL => {
+                // The analyzer has checked the format, which is printed after
+                // the values are put in it
+                let pieces = match &args[0] {
+                    Expr::String(format, _) => format::parse(format).unwrap(),
+                    _ => unreachable!(),
+                };
+                let mut values = args[1..].iter().map(|arg| self.lower(arg));
+                let mut pieces = pieces.into_iter().map(|piece| match piece {
+                    Piece::Text(text) => Node::new(Type::String, Kind::String(text)),
+                    Piece::Value(Type::Float, Some(digits)) => {
+                        let digits = Node::new(Type::Int, Kind::Int(digits));
+                        builtin(STRING_FROM_FLOAT_DIGITS_SYMBOL, Type::String, vec![values.next().unwrap(), digits])
+                    }
+                    Piece::Value(..) => to_string(values.next().unwrap()),
+                }).collect::<Vec<Node>>().into_iter();
+                let first = pieces.next().unwrap_or_else(|| Node::new(Type::String, Kind::String(String::new())));
+                let formatted = pieces.fold(first, |joined, piece| builtin(STRING_CONCAT_SYMBOL, Type::String, vec![joined, piece]));
+                Kind::Call(Callee::Builtin(PRINT_SYMBOL.to_owned()), vec![formatted])
+            }
+            Expr::Call(name, args, _, _) if collections::is_collection_call(name, &self.analyzer.functions) => {
+                return self.collection_call(name, args, ty);
+            }
+            Expr::Call(name, args, _, _) => {
+                let args = args.iter().map(|arg| self.lower(arg)).collect::<Vec<Node>>();
+                let sig = &self.analyzer.functions[name];
+                if sig.tag.is_some() {
+                    Kind::Allocate(sig.tag, args)
+                } else if sig.is_constructor(name) {
+                    Kind::Allocate(None, args)
+                } else if sig.runtime {
+                    Kind::Call(Callee::Builtin(runtime_symbol(name, &args).to_owned()), args)
+                } else if sig.library.is_some() {
+                    // External functions keep their C name, even when called through a module
+                    let symbol = name.rsplit('.').next().unwrap().to_owned();
+                    let call = Node::new(ty.clone(), Kind::Call(Callee::Function(symbol), args));
+                    if ty != Type::String {
+                        return call;
+                    }
+                    // The string belongs to the C library, so Gold gets its own copy
+                    Kind::Call(Callee::Builtin(STRING_FROM_C_SYMBOL.to_owned()), vec![call])
+                } else {
+                    Kind::Call(Callee::Function(self.symbol(name)), args)
+                }
+            }
+            Expr::While(cond, body, _) => {
+                let cond = self.lower(cond);
+                let body = self.lower(body);
+                let step = Node::new(Type::Void, Kind::Block(Vec::new()));
+                return Node::new(Type::Void, Kind::Loop(Box::new(cond), Box::new(body), Box::new(step)));
+            }
+            Expr::For(name, _, values, body, _) => return self.for_loop(name, values, body),
+            Expr::Break(_) => Kind::Break,
+            Expr::Continue(_) => Kind::Continue,
+            Expr::Match(value, arms, _) => {
+                let variants = match self.ty(value) {
+                    Type::Named(name) => &self.analyzer.enums[&name],
+                    _ => unreachable!()
+                };
+                let count = variants.len();
+                let value = self.lower(value);
+                let arms = arms.iter().map(|arm| {
+                    let tag = arm.variant.as_ref().map(|variant| variants.iter().position(|v| v == variant).unwrap());
+                    let fields = arm.variant.as_ref().map_or(Vec::new(), |variant| self.analyzer.functions[variant].param_types.to_owned());
+                    // The tag takes the first slot
+                    let bindings = arm.bindings.iter().zip(fields).enumerate()
+                        .map(|(index, ((binding, _), (ty, _)))| (self.declare(binding, ty), index + 1))
+                        .collect();
+                    Arm { tag, bindings, body: self.lower(&arm.body) }
+                }).collect::<Vec<Arm>>();
+                // The arms can use their bindings, which only exist now
+                let ty = arms.first().map_or(Type::Void, |arm| arm.body.ty.clone());
+                return Node::new(ty, Kind::Match(Box::new(value), arms, count));
+            }
+            Expr::Field(record, field, _) => {
+                let index = self.field(record, field);
+                Kind::GetField(Box::new(self.lower(record)), index)
+            }
+            Expr::ReassignField(field, value, _) => match field.as_ref() {
+                Expr::Field(record, field, _) => {
+                    let index = self.field(record, field);
+                    Kind::SetField(Box::new(self.lower(record)), index, Box::new(self.lower(value)))
+                }
+                _ => unreachable!()
+            },
+            Expr::Equality(lhs, rhs) => return self.binary(BinaryOp::Equal, lhs, rhs),
+            Expr::NotEqual(lhs, rhs) => return self.binary(BinaryOp::NotEqual, lhs, rhs),
+            Expr::GreaterThan(lhs, rhs) => return self.binary(BinaryOp::GreaterThan, lhs, rhs),
+            Expr::LessThan(lhs, rhs) => return self.binary(BinaryOp::LessThan, lhs, rhs),
+            Expr::GreaterThanEqual(lhs, rhs) => return self.binary(BinaryOp::GreaterThanEqual, lhs, rhs),
+            Expr::LessThanEqual(lhs, rhs) => return self.binary(BinaryOp::LessThanEqual, lhs, rhs),
+            Expr::Addition(lhs, rhs) => return self.binary(BinaryOp::Add, lhs, rhs),
+            Expr::Subtraction(lhs, rhs) => return self.binary(BinaryOp::Subtract, lhs, rhs),
+            Expr::Multiplication(lhs, rhs) => return self.binary(BinaryOp::Multiply, lhs, rhs),
+            Expr::Division(lhs, rhs) => return self.binary(BinaryOp::Divide, lhs, rhs),
+            Expr::Power(lhs, rhs) if ty == Type::Int => {
+                Kind::Call(Callee::Builtin(IPOWI_SYMBOL.to_owned()), vec![self.lower(lhs), self.lower(rhs)])
+            }
+            Expr::Power(lhs, rhs) => {
+                Kind::Call(Callee::Builtin(POWF_SYMBOL.to_owned()), vec![self.lower(lhs), self.lower(rhs)])
+            }
+            Expr::Negate(value, _) => Kind::Negate(Box::new(self.lower(value))),
+            Expr::List(values, _) => {
+                // Each item is added to the new list, which is given back
+                let item = match &ty {
+                    Type::List(item) => item,
+                    _ => unreachable!()
+                };
+                let mut list = builtin(LIST_NEW_SYMBOL, ty.clone(), vec![flag(counted(item))]);
+                for value in values {
+                    let value = self.lower(value);
+                    list = builtin(LIST_APPEND_SYMBOL, ty.clone(), vec![list, value]);
+                }
+                return list;
+            }
+            // Only found at the top level, or as part of an if
+            _ => unreachable!()
+        };
+        Node::new(ty, kind)
+    }
+
+    /// An if with a single else branch, which only has a value when both
+    /// branches give one of the same type.
+    fn branch(&mut self, cond: &Expr, body: &Expr, otherwise: Node) -> Node {
+        let cond = self.lower(cond);
+        let body = self.lower(body);
+        let ty = if body.ty == otherwise.ty { body.ty.clone() } else { Type::Void };
+        Node::new(ty, Kind::If(Box::new(cond), Box::new(body), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> Node {
+        let lhs = self.lower(lhs);
+        let rhs = self.lower(rhs);
+        match (lhs.ty.clone(), op) {
+            (Type::String, BinaryOp::Add) => builtin(STRING_CONCAT_SYMBOL, Type::String, vec![lhs, rhs]),
+            // Strings are compared by what they hold, so the comparison is on
+            // what `string_compare` gives back
+            (Type::String, op) if op.is_comparison() => {
+                let compared = builtin(STRCMP_SYMBOL, Type::Int, vec![lhs, rhs]);
+                let zero = Node::new(Type::Int, Kind::Int(0));
+                Node::new(Type::Bool, Kind::Binary(op, Box::new(compared), Box::new(zero)))
+            }
+            (ty, op) => {
+                let ty = if op.is_comparison() { Type::Bool } else { ty };
+                Node::new(ty, Kind::Binary(op, Box::new(lhs), Box::new(rhs)))
+            }
+        }
+    }
+
+    /// A call to one of the functions of lists and maps, which goes to the one
+    /// of the runtime for the collection it's given. Making one tells the
+    /// runtime whether it holds strings, and values to keep a reference to.
+    fn collection_call(&mut self, name: &str, args: &[Expr], ty: Type) -> Node {
+        match (name, &ty) {
+            (LIST_SYMBOL, Type::List(item)) => return builtin(LIST_NEW_SYMBOL, ty.clone(), vec![flag(counted(item))]),
+            (MAP_SYMBOL, Type::Map(key, value)) => {
+                let args = vec![flag(**key == Type::String), flag(counted(value))];
+                return builtin(MAP_NEW_SYMBOL, ty.clone(), args);
+            }
+            (MAP_FN_SYMBOL | FILTER_SYMBOL | FOLD_SYMBOL, _) => return self.list_function(name, args, ty),
+            _ => {}
+        }
+        let args = args.iter().map(|arg| self.lower(arg)).collect::<Vec<Node>>();
+        let list = matches!(args[0].ty, Type::List(_));
+        let symbol = match name {
+            GET_SYMBOL if list => LIST_GET_SYMBOL,
+            GET_SYMBOL => MAP_GET_SYMBOL,
+            LEN_SYMBOL if list => LIST_LEN_SYMBOL,
+            LEN_SYMBOL => MAP_LEN_SYMBOL,
+            PUSH_SYMBOL => LIST_PUSH_SYMBOL,
+            INSERT_SYMBOL => MAP_INSERT_SYMBOL,
+            CONTAINS_SYMBOL => MAP_CONTAINS_SYMBOL,
+            REMOVE_SYMBOL => MAP_REMOVE_SYMBOL,
+            KEYS_SYMBOL => MAP_KEYS_SYMBOL,
+            _ => unreachable!()
+        };
+        builtin(symbol, ty, args)
+    }
+
+    /// A for loop, which counts through a range or the indexes of a list. A
+    /// map is gone through by a list of its keys, and a string by a list of its
+    /// characters. What the loop counts with is kept in slots of its own, so
+    /// the body can change the variable without changing where the loop is.
+    fn for_loop(&mut self, name: &str, values: &Expr, body: &Expr) -> Node {
+        let load = |slot: Slot, ty: &Type| Node::new(ty.clone(), Kind::Load(slot));
+        let store = |slot: Slot, value: Node| Node::new(value.ty.clone(), Kind::Store(slot, Box::new(value)));
+        let (count, cond, item, mut stmts) = match values {
+            Expr::Range(start, end, inclusive) => {
+                let (count, last) = (self.hidden_slot(Type::Int), self.hidden_slot(Type::Int));
+                let op = if *inclusive { BinaryOp::LessThanEqual } else { BinaryOp::LessThan };
+                let cond = Node::new(Type::Bool, Kind::Binary(op, Box::new(load(count, &Type::Int)), Box::new(load(last, &Type::Int))));
+                let stmts = vec![store(count, self.lower(start)), store(last, self.lower(end))];
+                (count, cond, load(count, &Type::Int), stmts)
+            }
+            _ => {
+                let values = self.lower(values);
+                let list = match values.ty.clone() {
+                    Type::Map(key, _) => builtin(MAP_KEYS_SYMBOL, Type::List(key), vec![values]),
+                    Type::String => builtin(STRING_CHARS_SYMBOL, Type::List(Box::new(Type::String)), vec![values]),
+                    _ => values,
+                };
+                let ty = list.ty.clone();
+                let (list_slot, count) = (self.hidden_slot(ty.clone()), self.hidden_slot(Type::Int));
+                let len = builtin(LIST_LEN_SYMBOL, Type::Int, vec![load(list_slot, &ty)]);
+                let cond = Node::new(Type::Bool, Kind::Binary(BinaryOp::LessThan, Box::new(load(count, &Type::Int)), Box::new(len)));
+                let item_ty = match &ty {
+                    Type::List(item) => *item.clone(),
+                    _ => unreachable!()
+                };
+                let item = builtin(LIST_GET_SYMBOL, item_ty, vec![load(list_slot, &ty), load(count, &Type::Int)]);
+                let stmts = vec![store(list_slot, list), store(count, Node::new(Type::Int, Kind::Int(0)))];
+                (count, cond, item, stmts)
+            }
+        };
+        // The variable only exists in the body
+        let outside = (self.slots.clone(), self.scope.clone());
+        let variable = self.declare(name, item.ty.clone());
+        let body = self.lower(body);
+        let (slots, scope) = outside;
+        self.slots = slots;
+        self.scope = scope;
+        let body = Node::new(body.ty.clone(), Kind::Block(vec![store(variable, item), body]));
+        let one = Node::new(Type::Int, Kind::Int(1));
+        let step = store(count, Node::new(Type::Int, Kind::Binary(BinaryOp::Add, Box::new(load(count, &Type::Int)), Box::new(one))));
+        stmts.push(Node::new(Type::Void, Kind::Loop(Box::new(cond), Box::new(body), Box::new(step))));
+        Node::new(Type::Void, Kind::Block(stmts))
+    }
+
+    /// `map`, `filter` and `fold`, which go through the list the way a for
+    /// loop does and call the function on each item.
+    fn list_function(&mut self, name: &str, args: &[Expr], ty: Type) -> Node {
+        let load = |slot: Slot, ty: &Type| Node::new(ty.clone(), Kind::Load(slot));
+        let store = |slot: Slot, value: Node| Node::new(value.ty.clone(), Kind::Store(slot, Box::new(value)));
+        let mut args = args.iter().map(|arg| self.lower(arg)).collect::<Vec<Node>>();
+        let function = args.pop().unwrap();
+        let list = args.remove(0);
+        let (list_ty, function_ty) = (list.ty.clone(), function.ty.clone());
+        let item_ty = match &list_ty {
+            Type::List(item) => *item.clone(),
+            _ => unreachable!()
+        };
+        let list_slot = self.hidden_slot(list_ty.clone());
+        let function_slot = self.hidden_slot(function_ty.clone());
+        let (count, item, result) = (self.hidden_slot(Type::Int), self.hidden_slot(item_ty.clone()), self.hidden_slot(ty.clone()));
+
+        // What's given back starts out as the initial value or an empty list
+        let initial = match (name, &ty) {
+            (FOLD_SYMBOL, _) => args.remove(0),
+            (_, Type::List(result_item)) => builtin(LIST_NEW_SYMBOL, ty.clone(), vec![flag(counted(result_item))]),
+            _ => unreachable!()
+        };
+        let call = |args: Vec<Node>, returns: &Type| {
+            let mut values = vec![load(function_slot, &function_ty)];
+            values.extend(args);
+            Node::new(returns.clone(), Kind::Call(Callee::Closure, values))
+        };
+        let step = match name {
+            MAP_FN_SYMBOL => {
+                let returns = match &ty {
+                    Type::List(returns) => returns,
+                    _ => unreachable!()
+                };
+                let mapped = call(vec![load(item, &item_ty)], returns);
+                builtin(LIST_PUSH_SYMBOL, Type::Void, vec![load(result, &ty), mapped])
+            }
+            FILTER_SYMBOL => {
+                let kept = call(vec![load(item, &item_ty)], &Type::Bool);
+                let push = builtin(LIST_PUSH_SYMBOL, Type::Void, vec![load(result, &ty), load(item, &item_ty)]);
+                let otherwise = Node::new(Type::Void, Kind::Block(Vec::new()));
+                Node::new(Type::Void, Kind::If(Box::new(kept), Box::new(push), Box::new(otherwise)))
+            }
+            _ => store(result, call(vec![load(result, &ty), load(item, &item_ty)], &ty)),
+        };
+
+        let len = builtin(LIST_LEN_SYMBOL, Type::Int, vec![load(list_slot, &list_ty)]);
+        let cond = Node::new(Type::Bool, Kind::Binary(BinaryOp::LessThan, Box::new(load(count, &Type::Int)), Box::new(len)));
+        let get = builtin(LIST_GET_SYMBOL, item_ty, vec![load(list_slot, &list_ty), load(count, &Type::Int)]);
+        let body = Node::new(step.ty.clone(), Kind::Block(vec![store(item, get), step]));
+        let one = Node::new(Type::Int, Kind::Int(1));
+        let next = store(count, Node::new(Type::Int, Kind::Binary(BinaryOp::Add, Box::new(load(count, &Type::Int)), Box::new(one))));
+        let stmts = vec![
+            store(list_slot, list),
+            store(function_slot, function),
+            store(result, initial),
+            store(count, Node::new(Type::Int, Kind::Int(0))),
+            Node::new(Type::Void, Kind::Loop(Box::new(cond), Box::new(body), Box::new(next))),
+            load(result, &ty),
+        ];
+        Node::new(ty, Kind::Block(stmts))
+    }
+
+    /// An anonymous function, lifted out into a function of its own that
+    /// takes the closure it's called through before its parameters. The
+    /// closure holds the address of the function and a copy of every variable
+    /// from here the body uses, which the function starts by loading.
+    fn lambda(&mut self, params: &[(Parameter, Range<usize>)], body: &Expr, loc: &Range<usize>, ty: &Type) -> Kind {
+        self.lambdas += 1;
+        let name = format!("{}#{}", self.function_name, self.lambdas);
+        let symbol = format!("{}#{}", self.function_symbol, self.lambdas);
+        let mut lowering = FunctionLowering {
+            analyzer: self.analyzer,
+            scope: self.scope.clone(),
+            local_functions: self.local_functions,
+            namespace: self.namespace,
+            function_name: name.to_owned(),
+            function_symbol: symbol.to_owned(),
+            slots: HashMap::new(),
+            slot_types: Vec::new(),
+            slot_names: Vec::new(),
+            captured: Vec::new(),
+            lambdas: 0,
+            lifted: Vec::new(),
+        };
+        let closure = lowering.hidden_slot(Type::Pointer);
+        for (param, _) in params {
+            lowering.declare(&param.name, param.typename.clone());
+        }
+        let lowered = lowering.lower(body);
+        let captured = std::mem::take(&mut lowering.captured);
+        let mut stmts = captured.iter().enumerate().map(|(index, slot)| {
+            let ty = lowering.slot_types[slot.0].clone();
+            let closure = Node::new(Type::Pointer, Kind::Load(closure));
+            let value = Node::new(ty.clone(), Kind::GetField(Box::new(closure), index + 1));
+            Node::new(ty, Kind::Store(*slot, Box::new(value)))
+        }).collect::<Vec<Node>>();
+        stmts.push(lowered);
+
+        let mut param_types = vec![Type::Pointer];
+        param_types.extend(params.iter().map(|(param, _)| param.typename.clone()));
+        let returns = self.analyzer.functions[&lambda_name(loc)].return_type.clone();
+        let signature = Signature { params: param_types, returns };
+        let mut fields = vec![Node::new(Type::Pointer, Kind::Address(symbol.to_owned(), signature.clone()))];
+        for slot in &captured {
+            let name = &lowering.slot_names[slot.0];
+            fields.push(Node::new(self.scope[name].ty.clone(), Kind::Load(self.slot(name))));
+        }
+        self.lifted.extend(lowering.lifted);
+        self.lifted.push(Function {
+            name,
+            symbol,
+            signature,
+            slots: lowering.slot_types,
+            names: lowering.slot_names,
+            body: Node::new(stmts.last().unwrap().ty.clone(), Kind::Block(stmts)),
+            test: false,
+        });
+        debug_assert!(matches!(ty, Type::Function(..)));
+        Kind::Allocate(None, fields)
+    }
+
+    /// A function used as a value, which is a closure that doesn't capture
+    /// anything around a function that calls it.
+    fn function_value(&mut self, name: &str, ty: &Type) -> Kind {
+        let (params, returns) = match ty {
+            Type::Function(params, returns) => (params, *returns.clone()),
+            _ => unreachable!()
+        };
+        let target = self.symbol(name);
+        // Other modules could use the same function, so the wrapper is named
+        // after the module that uses it
+        let symbol = match self.namespace {
+            Some(namespace) if !self.local_functions.contains(name) => format!("{}.{}#value", namespace, target),
+            _ => format!("{}#value", target),
+        };
+        let mut slots = vec![Type::Pointer];
+        slots.extend(params.iter().cloned());
+        let args = params.iter().enumerate()
+            .map(|(index, ty)| Node::new(ty.clone(), Kind::Load(Slot(index + 1))))
+            .collect();
+        let call = Node::new(returns.clone(), Kind::Call(Callee::Function(target), args));
+        let signature = Signature { params: slots.clone(), returns };
+        self.lifted.push(Function {
+            name: format!("{}#value", name),
+            symbol: symbol.to_owned(),
+            signature: signature.clone(),
+            names: vec![String::new(); slots.len()],
+            slots,
+            body: Node::new(call.ty.clone(), Kind::Block(vec![call])),
+            test: false,
+        });
+        Kind::Allocate(None, vec![Node::new(Type::Pointer, Kind::Address(symbol, signature))])
+    }
+
+    /// A slot the lowering keeps a value in on its own, which has no name.
+    fn hidden_slot(&mut self, ty: Type) -> Slot {
+        let slot = Slot(self.slot_types.len());
+        self.slot_types.push(ty);
+        self.slot_names.push(String::new());
+        slot
+    }
+
+    /// The slot of a field, which the analyzer has already checked.
+    fn field(&self, record: &Expr, field: &str) -> usize {
+        match self.ty(record) {
+            Type::Named(record) => self.analyzer.functions[&record].field_names.iter().position(|name| name == field).unwrap(),
+            _ => unreachable!()
+        }
+    }
+}
+
+/// The runtime function a call by name goes to. Those that take either
+/// integers or floats have one of each.
+fn runtime_symbol<'a>(name: &'a str, args: &[Node]) -> &'a str {
+    let float = args.first().is_some_and(|arg| arg.ty == Type::Float);
+    match (name, float) {
+        (ABS_SYMBOL, false) => ABS_INT_SYMBOL,
+        (ABS_SYMBOL, true) => ABS_FLOAT_SYMBOL,
+        (MIN_SYMBOL, false) => MIN_INT_SYMBOL,
+        (MIN_SYMBOL, true) => MIN_FLOAT_SYMBOL,
+        (MAX_SYMBOL, false) => MAX_INT_SYMBOL,
+        (MAX_SYMBOL, true) => MAX_FLOAT_SYMBOL,
+        (CLAMP_SYMBOL, false) => CLAMP_INT_SYMBOL,
+        (CLAMP_SYMBOL, true) => CLAMP_FLOAT_SYMBOL,
+        _ => name,
+    }
+}
+
+fn flag(set: bool) -> Node {
+    Node::new(Type::Int, Kind::Int(set as i64))
+}
+
+fn builtin(name: &str, ty: Type, args: Vec<Node>) -> Node {
+    Node::new(ty, Kind::Call(Callee::Builtin(name.to_owned()), args))
+}
+
+/// Converts a value that is being interpolated into a string.
+fn to_string(value: Node) -> Node {
+    let name = match value.ty {
+        Type::String => return value,
+        Type::Float => STRING_FROM_FLOAT_SYMBOL,
+        Type::Bool => STRING_FROM_BOOL_SYMBOL,
+        _ => STRING_FROM_INT_SYMBOL,
+    };
+    builtin(name, Type::String, vec![value])
+}
@@ -1 +1,182 @@
-pub mod config;
\ No newline at end of file
+//! The lowered intermediate representation that code is generated from.
+//!
+//! Everything the analyzer worked out is spelled out here: every node carries
+//! its resolved type, variables are numbered slots, calls name the symbol they
+//! end up at, and the work hidden behind operators and string interpolation is
+//! turned into explicit calls into the runtime. Code generation never has to
+//! look anything up again.
+
+use std::ops::Range;
+
+use gold_frontend::frontend::Type;
+
+pub mod config;
+mod lower;
+
+/// A variable of a function, numbered from zero with the parameters first.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Slot(pub usize);
+
+/// Everything compiled from one Gold file.
+#[derive(Clone, Debug, Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+    pub externals: Vec<External>,
+    /// The file the module was lowered from and its source, which spans
+    /// point into
+    pub file: String,
+    pub source: String,
+}
+
+impl Module {
+    /// The function a program starts at, which is `main` when there is one
+    /// and otherwise the first function that isn't a test.
+    pub fn entry(&self) -> Option<&Function> {
+        self.functions.iter()
+            .find(|function| function.name == "main")
+            .or_else(|| self.functions.iter().find(|function| !function.test))
+    }
+}
+
+/// A C function the module calls, to be found when the program is compiled.
+#[derive(Clone, Debug)]
+pub struct External {
+    pub symbol: String,
+    pub library: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub returns: Type,
+}
+
+impl Signature {
+    /// Whether a program can start at a function with this signature. The
+    /// entry point is given `1` and `2` for up to two `Int`s, and what it
+    /// gives back is printed.
+    pub fn check_entry(&self, name: &str) -> Result<(), String> {
+        if self.params.len() > 2 || self.params.iter().any(|param| *param != Type::Int) {
+            let params = self.params.iter().map(Type::to_string).collect::<Vec<String>>().join(", ");
+            return Err(format!("'{}' can't be run: it takes ({}), but an entry point can only take up to two Ints", name, params));
+        }
+        match self.returns {
+            Type::Void | Type::Int | Type::Float | Type::Bool => Ok(()),
+            ref returns => Err(format!("'{}' can't be run: it returns {}, which an entry point can't print", name, returns)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    /// The name the function was declared with
+    pub name: String,
+    /// The name it is compiled under, which is qualified by the module it
+    /// comes from when that isn't the program itself
+    pub symbol: String,
+    pub signature: Signature,
+    /// The type of every slot, so parameters are the first few of them
+    pub slots: Vec<Type>,
+    /// The name of the variable in every slot
+    pub names: Vec<String>,
+    /// Always a `Kind::Block`, whose value is returned
+    pub body: Node,
+    /// Whether this is a test, which `gold test` runs on its own
+    pub test: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub ty: Type,
+    pub kind: Kind,
+    /// The expression the node was lowered from. Nodes the lowering adds on
+    /// its own have none and belong to the closest node around them that has.
+    pub span: Option<Range<usize>>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Kind {
+    Int(i64),
+    Bool(bool),
+    String(String),
+    Load(Slot),
+    /// Gives back the value stored
+    Store(Slot, Box<Node>),
+    /// Both sides have the same type, and comparisons give a `Bool`
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Negate(Box<Node>),
+    Call(Callee, Vec<Node>),
+    /// The address of a Gold function with the given signature, which a
+    /// closure keeps in its first field
+    Address(String, Signature),
+    /// Allocates a structure or a closure, or an enum value when there is a
+    /// tag, and fills in its fields in order
+    Allocate(Option<usize>, Vec<Node>),
+    /// Reads the field in the given slot of a structure or enum value, where
+    /// an enum keeps its tag in slot zero
+    GetField(Box<Node>, usize),
+    /// Gives back the value stored
+    SetField(Box<Node>, usize, Box<Node>),
+    /// Only has a value when both branches have one of the same type. A
+    /// missing else branch is an empty block.
+    If(Box<Node>, Box<Node>, Box<Node>),
+    /// Checks the condition before every run of the body, and runs the last
+    /// node after it, even when the body continues
+    Loop(Box<Node>, Box<Node>, Box<Node>),
+    /// Leaves the innermost loop
+    Break,
+    /// Goes on to the next run of the innermost loop
+    Continue,
+    /// Jumps to the arm for the tag of an enum value with the given number of
+    /// variants
+    Match(Box<Node>, Vec<Arm>, usize),
+    /// Has the value of its last node
+    Block(Vec<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    GreaterThanEqual,
+    LessThanEqual,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Callee {
+    /// A Gold function, a function of the host or a C function
+    Function(String),
+    /// One of the functions of the runtime declared by `gold_standard`
+    Builtin(String),
+    /// The function a closure holds. The closure is the first argument, and
+    /// is passed on to the function before the rest.
+    Closure,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arm {
+    /// `None` for the else arm
+    pub tag: Option<usize>,
+    /// The slots that take the values the variant carries, along with the
+    /// field slots they're read from
+    pub bindings: Vec<(Slot, usize)>,
+    pub body: Node,
+}
+
+impl Node {
+    pub fn new(ty: Type, kind: Kind) -> Self {
+        Self { ty, kind, span: None }
+    }
+}
+
+impl BinaryOp {
+    pub fn is_comparison(self) -> bool {
+        !matches!(self, BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide)
+    }
+}
@@ -0,0 +1,22 @@
+use gold_backend::lir::config::{Config, OptLevel};
+
+#[test]
+fn parses_optimization_levels() {
+    assert_eq!("speed_and_size".parse::<OptLevel>(), Ok(OptLevel::SpeedAndSize));
+    assert!("fast".parse::<OptLevel>().unwrap_err().contains("expected none, speed or speed_and_size"));
+}
+
+#[test]
+fn builds_instruction_sets_for_other_targets() {
+    let config = Config {
+        opt_level: OptLevel::Speed,
+        target: Some(Config::parse_target("aarch64-unknown-linux-gnu").unwrap()),
+        ..Config::default()
+    };
+    let isa = config.isa().unwrap();
+    assert_eq!(isa.name(), "aarch64");
+    assert_eq!(isa.flags().opt_level().to_string(), "speed");
+
+    assert!(Config::parse_target("i686-unknown-linux-gnu").is_err());
+    assert!(Config::parse_target("not-a-target").is_err());
+}
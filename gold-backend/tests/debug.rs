@@ -0,0 +1,106 @@
+use gimli::{EndianSlice, LittleEndian};
+use gold_backend::analysis::lower::{Analyzer, Lower};
+use gold_backend::codegen::Compilation;
+use gold_backend::lir::config::Config;
+use gold_frontend::frontend::parser;
+
+const SOURCE: &str = "// divide is a function.
+// Params:
+// 'a' is of type Int.
+// 'b' is of type Int.
+// Returns: Int
+fn {
+  var quotient = a / b;
+  quotient
+}
+
+// main is a function.
+// Params:
+// Returns: Int
+fn {
+  divide(6, 3)
+}
+";
+
+/// Lowers the program for compiling with debug info.
+fn compilation(source: &str) -> Compilation {
+    let items = parser::program(source).unwrap();
+    let mut analyzer = Analyzer::new(source.to_owned(), "debug.gold".to_owned());
+    analyzer.declare_functions(&items);
+    for item in &items {
+        item.typecheck(&mut analyzer);
+    }
+    assert!(analyzer.errors.is_empty());
+    let mut comp = Compilation::new(analyzer.lower(&items, None));
+    comp.set_config(Config { debug_info: true, ..Config::default() });
+    comp
+}
+
+/// Compiles the program with debug info, giving back the ELF file gdb gets.
+fn debug_object(source: &str) -> Vec<u8> {
+    compilation(source).compile().unwrap().debug_object().unwrap().to_vec()
+}
+
+/// The contents of a section of a little-endian 64-bit ELF file.
+fn section<'a>(object: &'a [u8], name: &str) -> &'a [u8] {
+    let int = |offset: usize, size: usize| {
+        object[offset..offset + size].iter().rev().fold(0, |value, byte| value << 8 | *byte as usize)
+    };
+    let header = |index: usize| int(0x28, 8) + index * 64;
+    let names = int(header(int(0x3e, 2)) + 24, 8);
+    (0..int(0x3c, 2)).map(header)
+        .find(|header| object[names + int(*header, 4)..].split(|byte| *byte == 0).next() == Some(name.as_bytes()))
+        .map(|header| &object[int(header + 24, 8)..int(header + 24, 8) + int(header + 32, 8)])
+        .unwrap_or(&[])
+}
+
+#[test]
+fn describes_lines_functions_and_variables() {
+    let object = debug_object(SOURCE);
+    assert_eq!(&object[..4], b"\x7fELF");
+    check_dwarf(&object);
+}
+
+/// An object file compiled ahead of time has the same debug info, with the
+/// addresses of functions and the offsets into other sections left to the
+/// linker.
+#[test]
+fn writes_debug_info_to_object_files() {
+    let object = compilation(SOURCE).emit_object().unwrap();
+    // Relocatable
+    assert_eq!(object[16], 1);
+    for name in &[".rela.debug_info", ".rela.debug_line", ".rela.debug_frame"] {
+        assert!(!section(&object, name).is_empty(), "{}", name);
+    }
+    check_dwarf(&object);
+}
+
+// Checks the lines, functions and variables of `SOURCE`
+fn check_dwarf(object: &[u8]) {
+    let dwarf = gimli::Dwarf::load(|id| Ok::<_, ()>(EndianSlice::new(section(object, id.name()), LittleEndian))).unwrap();
+
+    let mut units = dwarf.units();
+    let unit = dwarf.unit(units.next().unwrap().unwrap()).unwrap();
+    let mut rows = unit.line_program.clone().unwrap().rows();
+    let mut lines = Vec::new();
+    while let Some((_, row)) = rows.next_row().unwrap() {
+        lines.push(row.line().unwrap().get());
+    }
+    // The line each function starts on, then the lines with code
+    assert!(lines.starts_with(&[6, 7]), "{:?}", lines);
+    assert!(lines.contains(&14) && lines.contains(&15), "{:?}", lines);
+
+    let mut names = Vec::new();
+    let mut entries = unit.entries();
+    while let Some((_, entry)) = entries.next_dfs().unwrap() {
+        if let Some(name) = entry.attr_value(gimli::DW_AT_name).unwrap() {
+            let name = dwarf.attr_string(&unit, name).unwrap();
+            names.push((entry.tag(), name.to_string().unwrap().to_owned()));
+        }
+    }
+    assert!(names.contains(&(gimli::DW_TAG_subprogram, "divide".to_owned())), "{:?}", names);
+    assert!(names.contains(&(gimli::DW_TAG_subprogram, "main".to_owned())), "{:?}", names);
+    assert!(names.contains(&(gimli::DW_TAG_formal_parameter, "a".to_owned())), "{:?}", names);
+    assert!(names.contains(&(gimli::DW_TAG_variable, "quotient".to_owned())), "{:?}", names);
+    assert!(names.contains(&(gimli::DW_TAG_base_type, "Int".to_owned())), "{:?}", names);
+}
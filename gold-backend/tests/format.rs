@@ -0,0 +1,28 @@
+use gold_backend::analysis::format::{self, Piece};
+use gold_frontend::frontend::Type;
+
+#[test]
+fn parses_formats() {
+    let pieces = format::parse("%d of %s is %.2f%% (%f, %b)").unwrap();
+    assert_eq!(pieces, vec![
+        Piece::Value(Type::Int, None),
+        Piece::Text(" of ".to_owned()),
+        Piece::Value(Type::String, None),
+        Piece::Text(" is ".to_owned()),
+        Piece::Value(Type::Float, Some(2)),
+        Piece::Text("% (".to_owned()),
+        Piece::Value(Type::Float, None),
+        Piece::Text(", ".to_owned()),
+        Piece::Value(Type::Bool, None),
+        Piece::Text(")".to_owned()),
+    ]);
+    assert_eq!(format::value_types(&pieces), vec![Type::Int, Type::String, Type::Float, Type::Float, Type::Bool]);
+}
+
+#[test]
+fn rejects_bad_placeholders() {
+    assert!(format::parse("%x").unwrap_err().contains("`%x` isn't a placeholder"));
+    assert!(format::parse("%.2d").unwrap_err().contains("only `%f` can have digits"));
+    assert!(format::parse("%.f").is_err());
+    assert!(format::parse("100%").is_err());
+}
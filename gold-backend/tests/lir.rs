@@ -0,0 +1,192 @@
+use gold_backend::analysis::lower::{Analyzer, Lower};
+use gold_backend::lir::{BinaryOp, Callee, Function, Kind, Slot};
+use gold_frontend::frontend::{parser, Type};
+
+/// Checks a program and lowers every function in it.
+fn lowered_all(source: &str) -> Vec<Function> {
+    let items = parser::program(source).unwrap();
+    let mut analyzer = Analyzer::new(source.to_owned(), "test.gold".to_owned());
+    analyzer.declare_functions(&items);
+    for item in &items {
+        item.typecheck(&mut analyzer);
+    }
+    assert!(analyzer.errors.is_empty());
+    analyzer.lower(&items, None).functions
+}
+
+/// Checks a program and lowers its first function.
+fn lowered(source: &str) -> Function {
+    lowered_all(source).remove(0)
+}
+
+fn statements(function: &Function) -> &[gold_backend::lir::Node] {
+    match &function.body.kind {
+        Kind::Block(stmts) => stmts,
+        _ => panic!("the body is always a block"),
+    }
+}
+
+#[test]
+fn numbers_slots_with_parameters_first() {
+    let function = lowered("
+// scale is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var doubled = n * 2;
+  doubled
+}
+");
+    assert_eq!(function.slots, vec![Type::Int, Type::Int]);
+    assert_eq!(function.names, vec!["n", "doubled"]);
+    let stmts = statements(&function);
+    assert!(matches!(&stmts[0].kind, Kind::Store(Slot(1), value) if matches!(value.kind, Kind::Binary(BinaryOp::Multiply, _, _))));
+    assert!(matches!(stmts[1].kind, Kind::Load(Slot(1))));
+    assert_eq!(stmts[1].ty, Type::Int);
+}
+
+#[test]
+fn compares_strings_through_the_runtime() {
+    let function = lowered("
+// same is a function.
+// Params:
+// 'a' is of type String.
+// 'b' is of type String.
+// Returns: Bool
+fn {
+  a is b
+}
+");
+    let stmt = &statements(&function)[0];
+    assert_eq!(stmt.ty, Type::Bool);
+    match &stmt.kind {
+        Kind::Binary(BinaryOp::Equal, lhs, _) => {
+            assert!(matches!(&lhs.kind, Kind::Call(Callee::Builtin(name), _) if name == "string_compare"));
+        }
+        other => panic!("expected a comparison, got {:?}", other),
+    }
+}
+
+#[test]
+fn nests_elifs_in_else_branches() {
+    let function = lowered("
+// sign is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  if n > 0 {
+    1
+  } elif n < 0 {
+    -1
+  } else {
+    0
+  }
+}
+");
+    let stmt = &statements(&function)[0];
+    assert_eq!(stmt.ty, Type::Int);
+    match &stmt.kind {
+        Kind::If(_, _, otherwise) => assert!(matches!(otherwise.kind, Kind::If(_, _, _))),
+        other => panic!("expected an if, got {:?}", other),
+    }
+}
+
+#[test]
+fn keeps_while_conditions_in_the_loop() {
+    let function = lowered("
+// halve is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  while n > 1 {
+    n = n / 2;
+  }
+  n
+}
+");
+    match &statements(&function)[0].kind {
+        Kind::Loop(cond, body, step) => {
+            assert!(matches!(&cond.kind, Kind::Binary(BinaryOp::GreaterThan, lhs, _) if matches!(lhs.kind, Kind::Load(Slot(0)))));
+            assert!(matches!(&body.kind, Kind::Block(stmts) if matches!(stmts[0].kind, Kind::Store(Slot(0), _))));
+            assert!(matches!(&step.kind, Kind::Block(stmts) if stmts.is_empty()));
+        }
+        other => panic!("expected a loop, got {:?}", other),
+    }
+}
+
+#[test]
+fn lifts_anonymous_functions_with_their_captures() {
+    let functions = lowered_all("
+// adder is a function.
+// Params:
+// 'amount' is of type Int.
+// Returns: Function(Int) -> Int
+fn {
+  fn(n: Int) { n + amount }
+}
+");
+    let (adder, lifted) = (&functions[0], &functions[1]);
+    assert_eq!((lifted.name.as_str(), lifted.symbol.as_str()), ("adder#1", "adder#1"));
+    assert_eq!(lifted.signature.params, vec![Type::Pointer, Type::Int]);
+    assert_eq!(lifted.names, vec!["", "n", "amount"]);
+    match &statements(adder)[0].kind {
+        Kind::Allocate(None, fields) => {
+            assert!(matches!(&fields[0].kind, Kind::Address(symbol, _) if symbol == "adder#1"));
+            assert!(matches!(fields[1].kind, Kind::Load(Slot(0))));
+        }
+        other => panic!("expected a closure, got {:?}", other),
+    }
+    // The captured value is copied out of the closure before the body runs
+    let stmts = statements(lifted);
+    assert!(matches!(&stmts[0].kind, Kind::Store(Slot(2), value) if matches!(value.kind, Kind::GetField(_, 1))));
+    assert!(matches!(&stmts[1].kind, Kind::Block(_)));
+}
+
+#[test]
+fn calls_closures_with_themselves_first() {
+    let function = lowered("
+// apply is a function.
+// Params:
+// 'f' is of type Function(Int) -> Int.
+// Returns: Int
+fn {
+  f(1)
+}
+");
+    match &statements(&function)[0].kind {
+        Kind::Call(Callee::Closure, args) => {
+            assert!(matches!(args[0].kind, Kind::Load(Slot(0))));
+            assert!(matches!(args[1].kind, Kind::Int(1)));
+        }
+        other => panic!("expected a call through the closure, got {:?}", other),
+    }
+}
+
+#[test]
+fn gives_each_declaration_its_own_slot() {
+    let function = lowered("
+// main is a function.
+// Params:
+// Returns: Void
+fn {
+  for x in [\"a\"] {
+    println(x);
+  }
+  for x in 0..3 {
+    println(\"{x}\");
+  }
+  if 1 < 2 {
+    var t = \"text\";
+  }
+  if 2 > 1 {
+    var t = 5;
+  }
+}
+");
+    let typed = |name: &str| function.names.iter().zip(&function.slots).filter(|(n, _)| *n == name).map(|(_, ty)| ty.clone()).collect::<Vec<_>>();
+    assert_eq!(typed("x"), vec![Type::String, Type::Int]);
+    assert_eq!(typed("t"), vec![Type::String, Type::Int]);
+}
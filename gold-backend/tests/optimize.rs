@@ -0,0 +1,62 @@
+use gold_backend::analysis::optimize::{dump, optimize};
+use gold_frontend::frontend::parser;
+
+/// Optimizes the body of a function and renders what's left of it.
+fn optimized(body: &str) -> String {
+    let source = ["// main is a function.\n// Params:\n// Returns: Int\nfn {\n", body, "\n}\n"].join("");
+    let items = parser::program(&source).unwrap();
+    dump(&optimize(items)).trim_start_matches("main:\n").to_owned()
+}
+
+#[test]
+fn folds_arithmetic() {
+    assert_eq!(optimized("1 + 2 * 3 - -4"), "  11\n");
+    assert_eq!(optimized("2 ^ 10"), "  1024\n");
+    assert_eq!(optimized("7 / 2"), "  3\n");
+}
+
+#[test]
+fn leaves_traps_to_the_program() {
+    assert_eq!(optimized("1 / 0"), "  (/ 1 0)\n");
+    assert_eq!(optimized("2 ^ -1"), "  (^ 2 -1)\n");
+}
+
+#[test]
+fn folds_comparisons() {
+    assert_eq!(optimized("1 + 1 is 2"), "  true\n");
+    assert_eq!(optimized("3 <= 2"), "  false\n");
+    assert_eq!(optimized("\"a\" is \"a\""), "  true\n");
+    assert_eq!(optimized("\"b\" < \"ab\""), "  false\n");
+}
+
+#[test]
+fn folds_interpolation() {
+    assert_eq!(optimized("\"{1 + 1} is {2 > 1}\""), "  \"2 is true\"\n");
+}
+
+#[test]
+fn removes_unreachable_branches() {
+    assert_eq!(optimized("if 0 > 1 {\n  print(\"a\");\n}\n1"), "  1\n");
+    assert_eq!(
+        optimized("if 0 > 1 {\n  print(\"a\");\n} elif 1 > 0 {\n  print(\"b\");\n} else {\n  print(\"c\");\n}\n1"),
+        "  (print \"b\")\n  1\n",
+    );
+    assert_eq!(optimized("while 1 > 2 {\n  print(\"a\");\n}\n1"), "  1\n");
+}
+
+#[test]
+fn keeps_declarations_of_dropped_branches() {
+    assert_eq!(optimized("if 1 > 2 {\n  var y = 3;\n}\ny"), "  (var y ())\n  y\n");
+    assert_eq!(
+        optimized("if 2 > 1 {\n  print(\"a\");\n} else {\n  var z = 1;\n}\nwhile 1 > 2 {\n  var w = 2;\n}\nz + w"),
+        "  (var z ())\n  (print \"a\")\n  (var w ())\n  (+ z w)\n",
+    );
+}
+
+#[test]
+fn removes_unused_variables() {
+    assert_eq!(
+        optimized("var a = 1;\nvar b = print(\"kept\");\nvar c = 2;\nc = 3;\nvar d = 4;\nd"),
+        "  (print \"kept\")\n  (var d 4)\n  d\n",
+    );
+}
@@ -2,29 +2,45 @@ use std::ops::Range;
 
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 use codespan_reporting::files::{Error, SimpleFiles};
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
-use peg::error::ParseError;
+use codespan_reporting::term::termcolor::{ColorChoice, NoColor, StandardStream};
+use peg::error::{ExpectedSet, ParseError};
 use peg::str::LineCol;
 
 use crate::frontend::{Expr, Type};
+use crate::parse::brace_depth;
 
 impl Expr {
     pub fn expression_range(&self) -> Range<usize> {
         match self {
             Expr::NoExpr => unreachable!(),
             Expr::Number(_, r) => r.to_owned(),
+            Expr::Bool(_, r) => r.to_owned(),
             Expr::String(_, r) => r.to_owned(),
+            Expr::Interpolation(_, r) => r.to_owned(),
             Expr::Parameter(_, r) => r.to_owned(),
             Expr::Function(_, _, _, _, r) => r.to_owned(),
+            Expr::Test(_, _, r) => r.to_owned(),
+            Expr::Structure(_, _, r) => r.to_owned(),
+            Expr::Enumeration(_, _, r) => r.to_owned(),
+            Expr::Import(_, r) => r.to_owned(),
+            Expr::External(_, _, _, _, r) => r.to_owned(),
             Expr::Else(_, r) => r.to_owned(),
             Expr::Elif(_, _, r) => r.to_owned(),
             Expr::If(_, _, _, _, r) => r.to_owned(),
             Expr::Call(_, _, r, _) => r.to_owned(),
+            Expr::Lambda(_, _, r) => r.to_owned(),
             Expr::While(_, _, r) => r.to_owned(),
+            Expr::For(_, _, _, _, r) => r.to_owned(),
+            Expr::Range(lhs, rhs, _) => lhs.expression_range().start..rhs.expression_range().end,
+            Expr::Break(r) => r.to_owned(),
+            Expr::Continue(r) => r.to_owned(),
+            Expr::Match(_, _, r) => r.to_owned(),
             Expr::List(_, r) => r.to_owned(),
             Expr::Var(_, r) => r.to_owned(),
             Expr::Assign(_, _, r) => r.to_owned(),
             Expr::Reassign(_, _, r) => r.to_owned(),
+            Expr::Field(record, _, r) => record.expression_range().start..r.end,
+            Expr::ReassignField(_, _, r) => r.to_owned(),
             Expr::Block(_, r) => r.to_owned(),
             Expr::Equality(lhs, rhs) => lhs.expression_range().start..rhs.expression_range().end,
             Expr::NotEqual(lhs, rhs) => lhs.expression_range().start..rhs.expression_range().end,
@@ -37,48 +53,136 @@ impl Expr {
             Expr::Multiplication(lhs, rhs) => lhs.expression_range().start..rhs.expression_range().end,
             Expr::Division(lhs, rhs) => lhs.expression_range().start..rhs.expression_range().end,
             Expr::Power(lhs, rhs) => lhs.expression_range().start..rhs.expression_range().end,
+            Expr::Negate(_, r) => r.to_owned(),
         }
     }
 }
 
+#[derive(Clone)]
 pub enum TypeError {
     // TODO: Make this something useful
     InvaidTypesForOperation(Range<usize>, Range<usize>, Type, Type),
-    NotEqualFunctionReturnType(Range<usize>, Range<usize>),
+    NotEqualFunctionReturnType(Range<usize>, Range<usize>, Type, Type),
     FunctionDoesNotExist(String, Range<usize>),
     IncorrectNumberOfFunctionArguments(Range<usize>, usize, usize),
     IncorrectTypeValueForArgument(Range<usize>, Range<usize>, Type, Type),
     ExpectedExpression(Range<usize>, Type),
     NotDefined(Range<usize>),
+    CannotInterpolate(Range<usize>, Type),
+    UnknownType(String, Range<usize>),
+    NotARecord(Range<usize>, Type),
+    UnknownField(String, String, Range<usize>),
+    IncorrectTypeForField(Range<usize>, Range<usize>, Type, Type),
+    NotAnEnum(Range<usize>, Type),
+    UnknownVariant(String, String, Range<usize>),
+    IncorrectNumberOfBindings(Range<usize>, usize, usize),
+    DuplicateMatchArm(Range<usize>, Range<usize>),
+    NonExhaustiveMatch(Range<usize>, Vec<String>),
+    MismatchedArmTypes(Range<usize>, Range<usize>, Type, Type),
+    ModuleNotFound(String, Range<usize>),
+    InvalidModuleName(String, Range<usize>),
+    CircularImport(Vec<String>, Range<usize>),
+    ExternalNotFound(String, String, Range<usize>),
+    NotAnExternalType(Range<usize>, Type),
+    CStringOutsideExternal(Range<usize>),
+    CannotAssertEqual(Range<usize>, Type),
+    InvalidFormat(Range<usize>, String),
+    // The function, what it takes and what it was given
+    NotACollection(Range<usize>, String, &'static str, Type),
+    InvalidKeyType(Range<usize>, Type),
+    EmptyList(Range<usize>),
+    MismatchedListItems(Range<usize>, Range<usize>, Type, Type),
+    NotIterable(Range<usize>, Type),
+    InvalidRangeBound(Range<usize>, Type),
+    // `break` or `continue`
+    OutsideOfLoop(Range<usize>, &'static str),
+    // A variable called like a function
+    NotCallable(Range<usize>, Type),
+    // Unary minus on something that isn't a number
+    CannotNegate(Range<usize>, Type),
+    // The condition of an `if`, `elif` or `while`
+    NotACondition(Range<usize>, Type),
 }
 
-pub fn report_type_error(error: TypeError, filename: &str, source: &str) {
-    let mut file_handler = SimpleFiles::new();
-    let file_id = file_handler.add(filename, source);
+impl TypeError {
+    /// The name of the kind of error, which is what test files expect.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypeError::InvaidTypesForOperation(..) => "InvaidTypesForOperation",
+            TypeError::NotEqualFunctionReturnType(..) => "NotEqualFunctionReturnType",
+            TypeError::FunctionDoesNotExist(..) => "FunctionDoesNotExist",
+            TypeError::IncorrectNumberOfFunctionArguments(..) => "IncorrectNumberOfFunctionArguments",
+            TypeError::IncorrectTypeValueForArgument(..) => "IncorrectTypeValueForArgument",
+            TypeError::ExpectedExpression(..) => "ExpectedExpression",
+            TypeError::NotDefined(..) => "NotDefined",
+            TypeError::CannotInterpolate(..) => "CannotInterpolate",
+            TypeError::UnknownType(..) => "UnknownType",
+            TypeError::NotARecord(..) => "NotARecord",
+            TypeError::UnknownField(..) => "UnknownField",
+            TypeError::IncorrectTypeForField(..) => "IncorrectTypeForField",
+            TypeError::NotAnEnum(..) => "NotAnEnum",
+            TypeError::UnknownVariant(..) => "UnknownVariant",
+            TypeError::IncorrectNumberOfBindings(..) => "IncorrectNumberOfBindings",
+            TypeError::DuplicateMatchArm(..) => "DuplicateMatchArm",
+            TypeError::NonExhaustiveMatch(..) => "NonExhaustiveMatch",
+            TypeError::MismatchedArmTypes(..) => "MismatchedArmTypes",
+            TypeError::ModuleNotFound(..) => "ModuleNotFound",
+            TypeError::InvalidModuleName(..) => "InvalidModuleName",
+            TypeError::CircularImport(..) => "CircularImport",
+            TypeError::ExternalNotFound(..) => "ExternalNotFound",
+            TypeError::NotAnExternalType(..) => "NotAnExternalType",
+            TypeError::CStringOutsideExternal(..) => "CStringOutsideExternal",
+            TypeError::CannotAssertEqual(..) => "CannotAssertEqual",
+            TypeError::InvalidFormat(..) => "InvalidFormat",
+            TypeError::NotACollection(..) => "NotACollection",
+            TypeError::InvalidKeyType(..) => "InvalidKeyType",
+            TypeError::EmptyList(..) => "EmptyList",
+            TypeError::MismatchedListItems(..) => "MismatchedListItems",
+            TypeError::NotIterable(..) => "NotIterable",
+            TypeError::InvalidRangeBound(..) => "InvalidRangeBound",
+            TypeError::OutsideOfLoop(..) => "OutsideOfLoop",
+            TypeError::NotCallable(..) => "NotCallable",
+            TypeError::CannotNegate(..) => "CannotNegate",
+            TypeError::NotACondition(..) => "NotACondition",
+        }
+    }
+}
 
-    let err: Diagnostic<usize>;
+/// Builds the diagnostic for a type error without emitting it, so that other
+/// frontends (such as the language server) can render it themselves.
+pub fn type_error_diagnostic(error: TypeError, file_id: usize) -> Diagnostic<usize> {
     match error {
-        TypeError::InvaidTypesForOperation(s1, s2, mut expected, mut got) => {
-            err = Diagnostic::error()
+        TypeError::InvaidTypesForOperation(s1, s2, expected, got) => {
+            Diagnostic::error()
                 .with_message("Both sides of the expression must be the same type")
                 .with_labels(vec![
-                    Label::secondary(file_id, s1).with_message(["is of type `", expected.as_str(), "`"].join("")),
-                    Label::secondary(file_id, s2).with_message(["is of type `", got.as_str(), "`"].join("")),
+                    Label::secondary(file_id, s1).with_message(["is of type `", expected.to_string().as_str(), "`"].join("")),
+                    Label::secondary(file_id, s2).with_message(["is of type `", got.to_string().as_str(), "`"].join("")),
                 ])
                 .with_notes(vec![
                     "Both expression should be the same type".to_owned(),
                 ])
         }
-        TypeError::NotEqualFunctionReturnType(s1, s2) => todo!(),
+        TypeError::NotEqualFunctionReturnType(s1, s2, expected, got) => {
+            Diagnostic::error()
+                .with_message("Function body does not match the declared return type")
+                .with_labels(vec![
+                    Label::secondary(file_id, s1).with_message(["Declared to return `", expected.to_string().as_str(), "`"].join("")),
+                    Label::primary(file_id, s2).with_message(["Returned value is of type `", got.to_string().as_str(), "`"].join("")),
+                ])
+                .with_notes(vec![
+                    "the value of the last statement in the body is returned".to_owned()
+                ])
+        }
         TypeError::FunctionDoesNotExist(name, loc) => {
-            err = Diagnostic::error()
+            Diagnostic::error()
                 .with_message(["Function with the name '", name.as_str(), "'", " does not exist"].join(""))
                 .with_labels(vec![
                     Label::primary(file_id, loc)
-                ]);
+                ])
         }
         TypeError::IncorrectNumberOfFunctionArguments(s1, expected_args, got) => {
-            err = Diagnostic::error()
+            Diagnostic::error()
                 .with_message("Function call has incorrect number of arguments")
                 .with_labels(vec![
                     Label::secondary(file_id, s1).with_message(["Expected ", expected_args.to_string().as_str(), " arguments"].join(""))
@@ -87,54 +191,634 @@ pub fn report_type_error(error: TypeError, filename: &str, source: &str) {
                     ["expected ", expected_args.to_string().as_str(), " arguments but got ", got.to_string().as_str()].join("")
                 ])
         }
-        TypeError::IncorrectTypeValueForArgument(defined, error, mut expected, mut got) => {
-            err = Diagnostic::error()
+        TypeError::IncorrectTypeValueForArgument(defined, error, expected, got) => {
+            let mut labels = vec![
+                Label::primary(file_id, error).with_message(["Expected type `", expected.to_string().as_str(), "` but got type `", got.to_string().as_str(), "`"].join("")),
+            ];
+            // Builtins and imported functions aren't defined in this file
+            if defined != Range::default() {
+                labels.insert(0, Label::secondary(file_id, defined).with_message("Type defined here"));
+            }
+            Diagnostic::error()
                 .with_message("Incorrect type for argument")
-                .with_labels(vec![
-                    //Label::primary(file_id, s1),
-                    Label::secondary(file_id, defined).with_message("Type defined here"),
-                    Label::primary(file_id, error).with_message(["Expected type `", expected.as_str(), "` but got type `", got.as_str(), "`"].join("")),
-                ]);
+                .with_labels(labels)
         }
-        TypeError::ExpectedExpression(loc, mut ty) => {
-            err = Diagnostic::error()
+        TypeError::ExpectedExpression(loc, ty) => {
+            Diagnostic::error()
                 .with_message("Invalid expression")
                 .with_labels(vec![
-                    Label::primary(file_id, loc).with_message(["Expected type `", ty.as_str(), "`"].join(""))
-                ]);
+                    Label::primary(file_id, loc).with_message(["Expected type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::CannotInterpolate(loc, ty) => {
+            Diagnostic::error()
+                .with_message("Value can't be interpolated into a string")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+                .with_notes(vec![
+                    "only `Int`, `Float`, `String` and `Bool` values can be interpolated".to_owned()
+                ])
+        }
+        TypeError::UnknownType(name, loc) => {
+            Diagnostic::error()
+                .with_message(["Type with the name '", name.as_str(), "' does not exist"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "types are `Int`, `Float`, `String`, `Bool`, `Void`, `Pointer`, `List(...)`, `Map(..., ...)` or a structure declared in this file".to_owned()
+                ])
+        }
+        TypeError::NotARecord(loc, ty) => {
+            Diagnostic::error()
+                .with_message("Only structures have fields")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::UnknownField(field, record, loc) => {
+            Diagnostic::error()
+                .with_message(["Structure '", record.as_str(), "' has no field named '", field.as_str(), "'"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message("unknown field")
+                ])
+        }
+        TypeError::IncorrectTypeForField(defined, error, expected, got) => {
+            let mut labels = vec![
+                Label::primary(file_id, error).with_message(["Expected type `", expected.to_string().as_str(), "` but got type `", got.to_string().as_str(), "`"].join("")),
+            ];
+            // Structures from another module aren't defined in this file
+            if defined != Range::default() {
+                labels.insert(0, Label::secondary(file_id, defined).with_message("Type defined here"));
+            }
+            Diagnostic::error()
+                .with_message("Incorrect type for field")
+                .with_labels(labels)
+        }
+        TypeError::NotAnEnum(loc, ty) => {
+            Diagnostic::error()
+                .with_message("Only enums can be matched")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::UnknownVariant(variant, enumeration, loc) => {
+            Diagnostic::error()
+                .with_message(["Enum '", enumeration.as_str(), "' has no variant named '", variant.as_str(), "'"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message("unknown variant")
+                ])
+        }
+        TypeError::IncorrectNumberOfBindings(loc, expected, got) => {
+            let values = if expected == 1 { " value" } else { " values" };
+            Diagnostic::error()
+                .with_message("Match arm binds the wrong number of values")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["this variant carries ", expected.to_string().as_str(), values].join(""))
+                ])
+                .with_notes(vec![
+                    ["expected ", expected.to_string().as_str(), " bindings but got ", got.to_string().as_str()].join("")
+                ])
+        }
+        TypeError::DuplicateMatchArm(first, loc) => {
+            Diagnostic::error()
+                .with_message("Match arm can never be reached")
+                .with_labels(vec![
+                    Label::secondary(file_id, first).with_message("already matched here"),
+                    Label::primary(file_id, loc).with_message("unreachable arm"),
+                ])
+        }
+        TypeError::NonExhaustiveMatch(loc, missing) => {
+            let missing = missing.iter().map(|variant| ["`", variant.as_str(), "`"].join("")).collect::<Vec<String>>();
+            Diagnostic::error()
+                .with_message("Match does not cover every variant")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["missing ", missing.join(", ").as_str()].join(""))
+                ])
+                .with_notes(vec![
+                    "add an arm for each missing variant or an `else` arm".to_owned()
+                ])
+        }
+        TypeError::MismatchedArmTypes(first, loc, expected, got) => {
+            Diagnostic::error()
+                .with_message("Match arms have different types")
+                .with_labels(vec![
+                    Label::secondary(file_id, first).with_message(["is of type `", expected.to_string().as_str(), "`"].join("")),
+                    Label::primary(file_id, loc).with_message(["is of type `", got.to_string().as_str(), "`"].join("")),
+                ])
+        }
+        TypeError::ModuleNotFound(path, loc) => {
+            Diagnostic::error()
+                .with_message(["Cannot find module '", path.as_str(), "'"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message("No such file")
+                ])
+                .with_notes(vec![
+                    "modules are looked up relative to the file that uses them".to_owned()
+                ])
+        }
+        TypeError::InvalidModuleName(name, loc) => {
+            Diagnostic::error()
+                .with_message(["'", name.as_str(), "' cannot be used as a module name"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "functions are called as `module.function(...)`, so the file name must be a valid identifier".to_owned()
+                ])
+        }
+        TypeError::CircularImport(cycle, loc) => {
+            Diagnostic::error()
+                .with_message("Modules cannot use each other in a cycle")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message("This import leads back to the same file")
+                ])
+                .with_notes(vec![
+                    ["cycle: ", cycle.join(" -> ").as_str()].join("")
+                ])
+        }
+        TypeError::ExternalNotFound(name, library, loc) => {
+            Diagnostic::error()
+                .with_message(["Cannot find '", name.as_str(), "' in '", library.as_str(), "'"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message("No such symbol")
+                ])
+                .with_notes(vec![
+                    "`from m` loads `libm.so`, a full file name such as `libm.so.6` can be given instead".to_owned()
+                ])
+        }
+        TypeError::NotAnExternalType(loc, ty) => {
+            Diagnostic::error()
+                .with_message(["`", ty.to_string().as_str(), "` cannot be passed to or from C"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "external functions use `Int`, `Float`, `Pointer` and `CString`, and may return `Void`".to_owned()
+                ])
+        }
+        TypeError::CStringOutsideExternal(loc) => {
+            Diagnostic::error()
+                .with_message("`CString` can only be used by external functions")
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "use `String`, which external functions take and return as a `CString`".to_owned()
+                ])
+        }
+        TypeError::CannotAssertEqual(loc, ty) => {
+            Diagnostic::error()
+                .with_message(["Values of type `", ty.to_string().as_str(), "` cannot be compared by `assert_eq`"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "`assert_eq` compares an `Int`, `Float`, `String` or `Bool` with another of the same type".to_owned()
+                ])
+        }
+        TypeError::InvalidFormat(loc, problem) => {
+            Diagnostic::error()
+                .with_message("Invalid format for `printf`")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(problem)
+                ])
+                .with_notes(vec![
+                    "a format is a string literal with `%d`, `%f`, `%.2f`, `%s` or `%b` for each value, and `%%` for a percent sign".to_owned()
+                ])
+        }
+        TypeError::NotACollection(loc, function, takes, ty) => {
+            Diagnostic::error()
+                .with_message(["`", function.as_str(), "` takes a ", takes].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::InvalidKeyType(loc, ty) => {
+            Diagnostic::error()
+                .with_message(["Maps can't have keys of type `", ty.to_string().as_str(), "`"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "keys are an `Int`, a `String` or a `Bool`".to_owned()
+                ])
+        }
+        TypeError::EmptyList(loc) => {
+            Diagnostic::error()
+                .with_message("An empty list needs to say what it holds")
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+                .with_notes(vec![
+                    "write `List(Int)` for an empty list of `Int`s".to_owned()
+                ])
+        }
+        TypeError::MismatchedListItems(first, loc, expected, got) => {
+            Diagnostic::error()
+                .with_message("List items have different types")
+                .with_labels(vec![
+                    Label::secondary(file_id, first).with_message(["is of type `", expected.to_string().as_str(), "`"].join("")),
+                    Label::primary(file_id, loc).with_message(["is of type `", got.to_string().as_str(), "`"].join("")),
+                ])
+        }
+        TypeError::NotIterable(loc, ty) => {
+            Diagnostic::error()
+                .with_message("A for loop can't go through this")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+                .with_notes(vec![
+                    "a for loop goes through a range like `0..10`, a list, the keys of a map or the characters of a string".to_owned()
+                ])
+        }
+        TypeError::InvalidRangeBound(loc, ty) => {
+            Diagnostic::error()
+                .with_message("A range goes between two `Int`s")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::OutsideOfLoop(loc, keyword) => {
+            Diagnostic::error()
+                .with_message(["`", keyword, "` can only be used in a loop"].join(""))
+                .with_labels(vec![
+                    Label::primary(file_id, loc)
+                ])
+        }
+        TypeError::NotCallable(loc, ty) => {
+            Diagnostic::error()
+                .with_message("Only functions can be called")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+        }
+        TypeError::CannotNegate(loc, ty) => {
+            Diagnostic::error()
+                .with_message("Only numbers can be negated")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
+                .with_notes(vec![
+                    "`-` goes in front of an `Int` or a `Float`".to_owned()
+                ])
+        }
+        TypeError::NotACondition(loc, ty) => {
+            Diagnostic::error()
+                .with_message("A condition must be a `Bool`")
+                .with_labels(vec![
+                    Label::primary(file_id, loc).with_message(["is of type `", ty.to_string().as_str(), "`"].join(""))
+                ])
         }
         TypeError::NotDefined(loc) => {
-            err = Diagnostic::error()
+            Diagnostic::error()
                 .with_message("Value has not been defined")
                 .with_labels(vec![
                     Label::primary(file_id, loc).with_message("Expected variable to be defined")
-                ]);
+                ])
         }
     }
-    let writer = StandardStream::stderr(ColorChoice::Always);
-    let config = codespan_reporting::term::Config::default();
-    let res = codespan_reporting::term::emit(&mut writer.lock(), &config, &file_handler, &err);
-    match res {
-        Ok(_) => {}
-        Err(e) => println!("{}", e.to_string())
-    }
-    std::process::exit(1);
 }
 
-pub fn report_parse_error(filename: &str, source: &str, err: ParseError<LineCol>) -> Result<(), Error> {
-    let mut file_handler = SimpleFiles::new();
+/// Builds the diagnostic for a syntax error without emitting it. Mistakes in
+/// a function's comment header get an explanation and a suggested fix, while
+/// anything else falls back to the set of tokens the parser expected.
+pub fn parse_error_diagnostic(source: &str, err: &ParseError<LineCol>, file_id: usize) -> Diagnostic<usize> {
+    let offset = err.location.offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+    let line_end = source[offset..].find('\n').map(|pos| offset + pos).unwrap_or(source.len());
+    let line = source[line_start..line_end].trim_end_matches('\r');
+
+    if err.expected.tokens().any(|token| token == "a single comparison") {
+        return Diagnostic::error()
+            .with_message("Comparison operators can't be chained")
+            .with_labels(vec![
+                Label::primary(file_id, offset..offset).with_message("the first comparison can't be compared again")
+            ])
+            .with_notes(vec![
+                "compare the values separately, for example `a < b` and then `b < c`".to_owned()
+            ]);
+    }
+
+    if let Some(mistake) = diagnose_header_line(line, follows_header(source, line_start), &err.expected) {
+        let notes = mistake.help.into_iter().collect();
+        let trimmed = line.trim_start();
+        let start = line_start + (line.len() - trimmed.len());
+        return Diagnostic::error()
+            .with_message(mistake.message)
+            .with_labels(vec![
+                Label::primary(file_id, start..line_start + line.len()).with_message(mistake.label)
+            ])
+            .with_notes(notes);
+    }
 
-    let file_id = file_handler.add(filename, source);
     let mut expected = "Expected ".to_string();
     expected.push_str(err.expected.to_string().as_str());
-    let err = Diagnostic::error()
-        .with_message(expected.to_string())
+    Diagnostic::error()
+        .with_message(expected)
         .with_labels(vec![
             Label::primary(file_id, err.location.offset..err.location.offset)
-        ]);
+        ])
+}
+
+/// Whether the line starting at `line_start` follows a comment line outside
+/// of any body, which is where a function's body starts.
+fn follows_header(source: &str, line_start: usize) -> bool {
+    let before = &source[..line_start];
+    let previous = before.lines().rev().map(str::trim).find(|line| !line.is_empty());
+    matches!(previous, Some(line) if line.starts_with("//")) && brace_depth(before) == 0
+}
+
+struct HeaderMistake {
+    message: String,
+    label: &'static str,
+    help: Option<String>,
+}
+
+impl HeaderMistake {
+    fn new(message: &str, label: &'static str, help: Option<String>) -> Self {
+        Self { message: message.to_owned(), label, help }
+    }
+}
+
+fn did_you_mean(suggestion: &str) -> String {
+    ["did you mean `", suggestion, "`?"].join("")
+}
+
+fn add_line(line: &str) -> String {
+    ["add `", line, "` above this line"].join("")
+}
+
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs = rhs.chars().collect::<Vec<char>>();
+    let mut row = (0..=rhs.len()).collect::<Vec<usize>>();
+    for (i, l) in lhs.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, r) in rhs.iter().enumerate() {
+            let cost = if l == *r { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[rhs.len()]
+}
+
+fn is_identifier(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Recognizes the usual ways a line of a function or structure header goes
+/// wrong:
+///
+/// ```text
+/// // foo is a function.
+/// // Params:
+/// // 'a' is of type Int.
+/// // Returns: Int
+/// fn { ... }
+///
+/// // Point is a structure.
+/// // Fields:
+/// // 'x' is of type Int.
+/// ```
+///
+/// A line that isn't a comment can only be a mistake in the header when it
+/// comes `after_header`, where the `fn` of the body belongs. Anywhere else
+/// `fn` is expected because it can start an anonymous function.
+fn diagnose_header_line(line: &str, after_header: bool, expected: &ExpectedSet) -> Option<HeaderMistake> {
+    let expects = |token: &str| expected.tokens().any(|t| t.trim_matches('"') == token);
+    let line = line.trim();
+
+    let comment = match line.strip_prefix("//") {
+        Some(comment) => comment.trim(),
+        None if !after_header => return None,
+        None => {
+            if expects("fn") {
+                let rest = line.trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_').trim();
+                return Some(HeaderMistake::new(
+                    "Function bodies start with `fn` after the comment header",
+                    "expected `fn` here",
+                    Some(did_you_mean(&["fn ", if rest.is_empty() { "{" } else { rest }].join(""))),
+                ));
+            }
+            // The parameter list has ended, so a comment line was expected
+            if expects("Returns:") || (expects("//") && line.starts_with("fn")) {
+                return Some(HeaderMistake::new(
+                    "The comment header is missing its `// Returns:` line",
+                    "expected `// Returns:` before this line",
+                    Some(add_line("// Returns: Void")),
+                ));
+            }
+            return None;
+        }
+    };
+    let words = comment.split_whitespace().collect::<Vec<&str>>();
+    let first = words.first().map(|w| w.trim_end_matches(':').to_lowercase()).unwrap_or_default();
+
+    // // Return: Int, // returns Int, ...
+    if ["return", "returns", "returning", "ret"].contains(&first.as_str()) {
+        if words[0] == "Returns:" {
+            if words.len() == 1 {
+                return Some(HeaderMistake::new(
+                    "The `// Returns:` line is missing a type",
+                    "expected a type after `Returns:`",
+                    Some(did_you_mean("// Returns: Void")),
+                ));
+            }
+            return None;
+        }
+        let ty = words[1..].join(" ");
+        let ty = ty.trim_start_matches(':').trim();
+        return Some(HeaderMistake::new(
+            "The return type is declared with `// Returns:`",
+            "this return type declaration is misspelled",
+            Some(did_you_mean(&["// Returns: ", if ty.is_empty() { "Void" } else { ty }].join(""))),
+        ));
+    }
+
+    // // Field:, // Members:, ...
+    if ["field", "fields", "member", "members"].contains(&first.as_str()) && comment != "Fields:" {
+        return Some(HeaderMistake::new(
+            "The field list starts with `// Fields:`",
+            "this field list header is misspelled",
+            Some(did_you_mean("// Fields:")),
+        ));
+    }
+
+    // // Param:, // Parameters:, ...
+    if ["param", "params", "parameter", "parameters", "arg", "args", "arguments"].contains(&first.as_str()) && comment != "Params:" {
+        return Some(HeaderMistake::new(
+            "The parameter list starts with `// Params:`",
+            "this parameter list header is misspelled",
+            Some(did_you_mean("// Params:")),
+        ));
+    }
+
+    // // 'a' is of type Int.
+    let quoted = comment.starts_with('\'');
+    let (name, rest) = if quoted {
+        match comment[1..].find('\'') {
+            Some(end) => (&comment[1..end + 1], comment[end + 2..].trim()),
+            None => (comment[1..].split_whitespace().next().unwrap_or(""), ""),
+        }
+    } else {
+        (words.first().copied().unwrap_or(""), comment.get(words.first().map(|w| w.len()).unwrap_or(0)..).unwrap_or("").trim())
+    };
+    let rest_words = rest.split_whitespace().collect::<Vec<&str>>();
+    // Enum variants, like `'Circle' with 'r' of type Float.`, aren't parameters
+    if quoted && (rest_words.first() == Some(&"with") || rest == ".") {
+        return None;
+    }
+    let well_formed = quoted && rest_words.len() == 4 && rest_words[..3] == ["is", "of", "type"] && rest.ends_with('.');
+    if (quoted || rest_words.contains(&"type")) && !well_formed {
+        let ty = rest_words.last().filter(|_| rest_words.len() > 1 && *rest_words.last().unwrap() != "type");
+        let suggestion = ty.map(|ty| did_you_mean(&["// '", name, "' is of type ", ty.trim_end_matches('.'), "."].join("")));
+        if !quoted {
+            return Some(HeaderMistake::new(
+                "Parameter names are written in single quotes",
+                "the parameter name needs quotes",
+                suggestion,
+            ));
+        }
+        if let Some(ty) = ty {
+            if rest_words.len() == 4 && rest_words[..3] == ["is", "of", "type"] && !ty.ends_with('.') {
+                return Some(HeaderMistake::new(
+                    "Parameter declarations end with a period",
+                    "missing a `.` at the end",
+                    suggestion,
+                ));
+            }
+        }
+        return Some(HeaderMistake::new(
+            "Parameters are declared as `'name' is of type Type.`",
+            "this parameter declaration is malformed",
+            suggestion,
+        ));
+    }
+
+    // // foo is a function. or // Point is a structure.
+    if words.len() >= 3 && is_identifier(words[0]) && words[1] == "is" && (words[2] == "a" || words[2] == "an") {
+        let kind = words[3..].join(" ");
+        let is_structure = edit_distance(kind.as_str(), "structure.") < edit_distance(kind.as_str(), "function.");
+        let declaration = if is_structure { "structure." } else { "function." };
+        let suggestion = Some(did_you_mean(&["// ", words[0], " is a ", declaration].join("")));
+        if kind == declaration.trim_end_matches('.') {
+            return Some(HeaderMistake::new(
+                if is_structure { "Structure declarations end with a period" } else { "Function declarations end with a period" },
+                "missing a `.` at the end",
+                suggestion,
+            ));
+        }
+        if edit_distance(kind.as_str(), declaration) <= 3 {
+            return Some(if is_structure {
+                HeaderMistake::new(
+                    "Structures are declared as `Name is a structure.`",
+                    "this structure declaration is misspelled",
+                    suggestion,
+                )
+            } else {
+                HeaderMistake::new(
+                    "Functions are declared as `name is a function.`",
+                    "this function declaration is misspelled",
+                    suggestion,
+                )
+            });
+        }
+    }
+
+    if expects("Fields:") && !expects("Params:") {
+        return Some(HeaderMistake::new(
+            "The comment header is missing its `// Fields:` line",
+            "expected `// Fields:` before this line",
+            Some(add_line("// Fields:")),
+        ));
+    }
+    if expects("Params:") {
+        return Some(HeaderMistake::new(
+            "The comment header is missing its `// Params:` line",
+            "expected `// Params:` before this line",
+            Some(add_line("// Params:")),
+        ));
+    }
+    if expects("Returns:") {
+        return Some(HeaderMistake::new(
+            "The comment header is missing its `// Returns:` line",
+            "expected `// Returns:` before this line",
+            Some(add_line("// Returns: Void")),
+        ));
+    }
+    None
+}
+
+// Diagnostics are built against file id 0, which is the only file added here.
+fn emit_diagnostics(filename: &str, source: &str, diagnostics: Vec<Diagnostic<usize>>) -> Result<(), Error> {
+    let mut file_handler = SimpleFiles::new();
+    file_handler.add(filename, source);
+
     let writer = StandardStream::stderr(ColorChoice::Always);
     let config = codespan_reporting::term::Config::default();
-    codespan_reporting::term::emit(&mut writer.lock(), &config, &file_handler, &err)?;
-    std::process::exit(1);
+    for diagnostic in diagnostics {
+        codespan_reporting::term::emit(&mut writer.lock(), &config, &file_handler, &diagnostic)?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn report_type_error(error: TypeError, filename: &str, source: &str) {
+    report_errors(filename, source, Vec::new(), vec![error])
+}
+
+/// Prints every syntax and type error found in a file and exits if there was
+/// at least one of them.
+pub fn report_errors(filename: &str, source: &str, parse_errors: Vec<ParseError<LineCol>>, type_errors: Vec<TypeError>) {
+    if print_errors(filename, source, parse_errors, type_errors) {
+        std::process::exit(1);
+    }
+}
+
+/// Prints every syntax and type error found in a file, returning whether
+/// there were any. Used when errors from several files should all be shown
+/// before giving up.
+pub fn print_errors(filename: &str, source: &str, parse_errors: Vec<ParseError<LineCol>>, type_errors: Vec<TypeError>) -> bool {
+    if parse_errors.is_empty() && type_errors.is_empty() {
+        return false;
+    }
+    let diagnostics = parse_errors.iter().map(|error| parse_error_diagnostic(source, error, 0))
+        .chain(type_errors.into_iter().map(|error| type_error_diagnostic(error, 0)))
+        .collect();
+    if let Err(e) = emit_diagnostics(filename, source, diagnostics) {
+        println!("{}", e)
+    }
+    true
+}
+
+/// Renders every syntax and type error found in a file as plain text, for
+/// callers that show errors somewhere other than the terminal.
+pub fn render_errors(filename: &str, source: &str, parse_errors: Vec<ParseError<LineCol>>, type_errors: Vec<TypeError>) -> String {
+    let diagnostics = parse_errors.iter().map(|error| parse_error_diagnostic(source, error, 0))
+        .chain(type_errors.into_iter().map(|error| type_error_diagnostic(error, 0)))
+        .collect();
+    render_diagnostics(filename, source, diagnostics)
+}
+
+/// Renders diagnostics built against file id 0 as plain text.
+pub fn render_diagnostics(filename: &str, source: &str, diagnostics: Vec<Diagnostic<usize>>) -> String {
+    let mut file_handler = SimpleFiles::new();
+    file_handler.add(filename, source);
+
+    let mut writer = NoColor::new(Vec::new());
+    let config = codespan_reporting::term::Config::default();
+    for diagnostic in diagnostics {
+        // Writing into memory can't fail, and every label points into the file
+        codespan_reporting::term::emit(&mut writer, &config, &file_handler, &diagnostic).unwrap();
+    }
+    String::from_utf8_lossy(&writer.into_inner()).into_owned()
+}
+
+pub fn report_parse_error(filename: &str, source: &str, err: ParseError<LineCol>) -> Result<(), Error> {
+    emit_diagnostics(filename, source, vec![parse_error_diagnostic(source, &err, 0)])?;
+    std::process::exit(1);
+}
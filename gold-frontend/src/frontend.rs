@@ -1,15 +1,11 @@
+#![allow(clippy::redundant_closure_call)]
+
+use core::fmt;
 use core::ops::Range;
 
 use cranelift::prelude::{AbiParam, types};
-use std::collections::HashMap;
-use std::mem::transmute;
-
-use cranelift::prelude::*;
-use cranelift_jit::{JITBuilder, JITModule};
-use cranelift_module::{FuncId, Linkage, Module};
-use target_lexicon::Triple;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Type {
     Int,
     Float,
@@ -17,36 +13,89 @@ pub enum Type {
     String,
     Bool,
     Void,
+    // A structure or enum declared in the file, passed around by pointer
+    Named(String),
+    // An address handed out by a C library, only good for passing back to it
+    Pointer,
+    // A nul terminated string passed to or returned from a C library
+    CString,
+    // Collections kept by the runtime, passed around by pointer
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    // A function or closure, by the types of its parameters and what it gives back
+    Function(Vec<Type>, Box<Type>),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Parameter {
     pub name: String,
     pub typename: Type,
 }
 
-#[derive(Clone)]
+/// One of the cases of an enum, along with the values it carries.
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<(Parameter, Range<usize>)>,
+    pub loc: Range<usize>,
+}
+
+/// An arm of a `match`. The `else` arm has no variant and matches anything.
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub variant: Option<String>,
+    pub bindings: Vec<(String, Range<usize>)>,
+    pub body: Expr,
+    pub loc: Range<usize>,
+}
+
+#[derive(Clone, Debug)]
 pub enum Expr {
     NoExpr,
     Number(i64, Range<usize>),
+    // Only produced by constant folding, there is no literal for it yet
+    Bool(bool, Range<usize>),
     String(String, Range<usize>),
+    // "text {expr} text"
+    Interpolation(Vec<Expr>, Range<usize>),
     Var(String, Range<usize>),
     Parameter(Parameter, Range<usize>),
     Block(Vec<Expr>, Range<usize>),
     Function(/* name */ String, /* params */ Vec<(Parameter, Range<usize>)>, /* Ret */ Type, /* stmts */ Box<Expr>, Range<usize>),
+    // A function without parameters or a return value, run by `gold test`
+    Test(/* name */ String, /* stmts */ Box<Expr>, Range<usize>),
+    Structure(/* name */ String, /* fields */ Vec<(Parameter, Range<usize>)>, Range<usize>),
+    Enumeration(/* name */ String, Vec<Variant>, Range<usize>),
+    External(/* name */ String, /* params */ Vec<(Parameter, Range<usize>)>, /* Ret */ Type, /* library */ String, Range<usize>),
+    // Uses: other.gold
+    Import(/* path */ String, Range<usize>),
 
     Else(/* block */ Box<Expr>, Range<usize>),
     Elif(/* block */ Box<Expr>, Box<Expr>, Range<usize>),
     If(Box<Expr>, /* block */ Box<Expr>, /*elifs*/ Option<Vec<Expr>>, Option<Box<Expr>>, Range<usize>),
 
     Call(String, Vec<Expr>, Range<usize>, Range<usize>),
+    // fn(x: Int) { ... }, which gives back what its last statement does
+    Lambda(/* params */ Vec<(Parameter, Range<usize>)>, /* stmts */ Box<Expr>, Range<usize>),
 
     While(Box<Expr>, /* block */ Box<Expr>, Range<usize>),
+    // for name in values { ... }, with the range of the name
+    For(String, Range<usize>, /* values */ Box<Expr>, /* block */ Box<Expr>, Range<usize>),
+    // start..end, or start..=end when inclusive, only found in a for loop
+    Range(Box<Expr>, Box<Expr>, /* inclusive */ bool),
+    Break(Range<usize>),
+    Continue(Range<usize>),
+    Match(Box<Expr>, Vec<MatchArm>, Range<usize>),
     List(Vec<Expr>, Range<usize>),
 
     Assign(String, Box<Expr>, Range<usize>),
     Reassign(String, Box<Expr>, Range<usize>),
 
+    // p.x, the range covers the field name
+    Field(Box<Expr>, String, Range<usize>),
+    // p.x = value;
+    ReassignField(/* field */ Box<Expr>, Box<Expr>, Range<usize>),
+
     // is
     Equality(Box<Expr>, Box<Expr>),
 
@@ -79,16 +128,48 @@ pub enum Expr {
 
     // Exponent
     Power(Box<Expr>, Box<Expr>),
+
+    // Unary -
+    Negate(Box<Expr>, Range<usize>),
+}
+
+/// Builds the node for a binary operator out of its two operands.
+pub type BinaryExpr = fn(Box<Expr>, Box<Expr>) -> Expr;
+
+/// Joins the pieces of a string literal. Neighbouring pieces of text are
+/// merged, and a literal without any `{expr}` becomes a plain string.
+fn string_literal(parts: Vec<Expr>, loc: Range<usize>) -> Expr {
+    let mut merged: Vec<Expr> = Vec::new();
+    for part in parts {
+        match (merged.last_mut(), part) {
+            (Some(Expr::String(text, text_loc)), Expr::String(next, next_loc)) => {
+                text.push_str(&next);
+                text_loc.end = next_loc.end;
+            }
+            (_, part) => merged.push(part),
+        }
+    }
+    match merged.as_slice() {
+        [] => Expr::String(String::new(), loc),
+        [Expr::String(text, _)] => Expr::String(text.to_owned(), loc),
+        _ => Expr::Interpolation(merged, loc),
+    }
 }
 
+/// Type names that can be written in a `// 'x' is of type ...` comment.
+pub const TYPE_NAMES: [&str; 10] = ["Int", "Float", "String", "Bool", "Void", "Pointer", "CString", "List", "Map", "Function"];
+
 impl From<String> for Type {
     fn from(input: String) -> Type {
         match input.as_str() {
             "Int" => Type::Int,
+            "Float" => Type::Float,
             "String" => Type::String,
             "Void" => Type::Void,
             "Bool" => Type::Bool,
-            _ => Type::Int
+            "Pointer" => Type::Pointer,
+            "CString" => Type::CString,
+            _ => Type::Named(input)
         }
     }
 }
@@ -99,9 +180,14 @@ impl From<Type> for types::Type {
       match ty {
           Type::Int =>   types::I64,
           Type::Float => types::F32,
-          Type::String => todo!(),
           Type::Bool =>  types::B1,
-          _ => todo!()
+          // Everything else is behind a pointer
+          Type::String | Type::Named(_) | Type::Pointer | Type::CString | Type::List(_) | Type::Map(..) | Type::Function(..) => {
+              cranelift::prelude::Type::triple_pointer_type(&target_lexicon::Triple::host())
+          }
+          // Nothing is passed or stored for `Void`, and a `Number` is always
+          // an `Int` or a `Float` by the time code is generated
+          Type::Void | Type::Number => unreachable!("`{}` has no Cranelift type", ty),
       }
   }
 }
@@ -109,25 +195,28 @@ impl From<Type> for types::Type {
 impl From<Type> for AbiParam {
     #[inline]
     fn from(ty: Type) -> Self {
-        match ty {
-          Type::Int =>  AbiParam::new(types::I64),
-          Type::Float => AbiParam::new(types::F32),
-          Type::String => AbiParam::new(cranelift::prelude::Type::triple_pointer_type(&target_lexicon::Triple::host())),
-          Type::Bool => AbiParam::new(types::B1),
-          _ => todo!()
-        }
+        AbiParam::new(ty.into())
     }
 }
 
-impl Type {
-    pub fn as_str(&mut self) -> &str {
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Type::Int => "Int",
-            Type::Float => "Float",
-            Type::Number => "Number",
-            Type::String => "String",
-            Type::Bool => "Bool",
-            Type::Void => "Void",
+            Type::Int => f.write_str("Int"),
+            Type::Float => f.write_str("Float"),
+            Type::Number => f.write_str("Number"),
+            Type::String => f.write_str("String"),
+            Type::Bool => f.write_str("Bool"),
+            Type::Void => f.write_str("Void"),
+            Type::Named(name) => f.write_str(name),
+            Type::Pointer => f.write_str("Pointer"),
+            Type::CString => f.write_str("CString"),
+            Type::List(item) => write!(f, "List({})", item),
+            Type::Map(key, value) => write!(f, "Map({}, {})", key, value),
+            Type::Function(params, returns) => {
+                let params = params.iter().map(Type::to_string).collect::<Vec<String>>();
+                write!(f, "Function({}) -> {}", params.join(", "), returns)
+            }
         }
     }
 }
@@ -135,16 +224,90 @@ impl Type {
 
 peg::parser!(pub grammar parser() for str {
 
+  pub rule program() -> Vec<Expr>
+      = items:((import() / function() / test() / external() / structure() / enumeration())*) _ { items }
+
+  pub rule import() -> Expr
+      = _ "//" _ "Uses:" [' ' | '\t']* start:position!() path:$((!['\n' | '\r'] [_])+) end:position!()
+      {
+          let trimmed = path.trim_end();
+          Expr::Import(trimmed.to_owned(), start..start + trimmed.len())
+      }
 
   pub rule function() -> Expr 
-      = "//" start:position!() _ function_name:identifier() end:position!() _ "is" _ "a" _ "function." _ 
+      = _ "//" _ start:position!() function_name:identifier() end:position!() _ "is" _ "a" _ "function." _ 
         "//" _ "Params:" 
         params:parameters() _ 
         ret:return_stmt() _ 
-        "fn" _ body:block()
+        "fn" _ body:block() _
       {
           Expr::Function(function_name, params, ret, Box::new(body), start..end)
       }  
+
+  // test_addition is a test.
+  pub rule test() -> Expr
+      = _ "//" _ start:position!() name:identifier() end:position!() _ "is" _ "a" _ "test." _
+        "fn" _ body:block() _
+      {
+          Expr::Test(name, Box::new(body), start..end)
+      }
+
+  // strlen is an external function from libc.
+  pub rule external() -> Expr
+      = _ "//" _ start:position!() name:identifier() end:position!() _ "is" _ "an" _ "external" _ "function" _ "from" _
+        library:library_name() "." _
+        "//" _ "Params:"
+        params:parameters() _
+        ret:return_stmt() _
+      {
+          Expr::External(name, params, ret, library, start..end)
+      }
+
+  // libc, libm.so.6, ...
+  rule library_name() -> String
+      = name:$(library_chars() ("." library_chars())*) { name.to_owned() }
+
+  rule library_chars() = ['a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-']+
+
+  pub rule structure() -> Expr
+      = _ "//" _ start:position!() name:identifier() end:position!() _ "is" _ "a" _ "structure." _
+        "//" _ "Fields:"
+        fields:parameters() _
+      {
+          Expr::Structure(name, fields, start..end)
+      }
+
+  pub rule enumeration() -> Expr
+      = _ "//" _ start:position!() name:identifier() end:position!() _ "is" _ "one" _ "of:"
+        variants:(variant_decl()+) _
+      {
+          Expr::Enumeration(name, variants, start..end)
+      }
+
+  // 'Circle' with 'r' of type Float.
+  // 'Rectangle' with 'w' of type Int and 'h' of type Int.
+  // 'Empty'.
+  pub rule variant_decl() -> Variant
+      = _ "//" _ "'" start:position!() name:identifier() end:position!() "'"
+        fields:(_ "with" _ fields:(variant_field() ++ (_ "and" _)) { fields })? _ "."
+      {
+          Variant { name, fields: fields.unwrap_or_default(), loc: start..end }
+      }
+
+  rule variant_field() -> (Parameter, Range<usize>)
+      = "'" name:identifier() "'" _ "of" _ "type" _ start:position!() typename:type_name() end:position!()
+      {
+          (Parameter { name, typename }, start..end)
+      }
+
+  // Int, Person, List(Int), Map(String, List(Int)), Function(Int, Int) -> Bool, ...
+  pub rule type_name() -> Type
+      = "List" _ "(" _ item:type_name() _ ")" { Type::List(Box::new(item)) }
+      / "Map" _ "(" _ key:type_name() _ "," _ value:type_name() _ ")" { Type::Map(Box::new(key), Box::new(value)) }
+      / "Function" _ "(" _ params:(type_name() ** (_ "," _)) _ ")" _ "->" _ returns:type_name()
+      { Type::Function(params, Box::new(returns)) }
+      / ty:identifier() { Type::from(ty) }
+
   pub rule block() -> Expr 
       = start:position!() "{" _ stmts:statements() _ "}" end:position!()
       {
@@ -159,13 +322,21 @@ peg::parser!(pub grammar parser() for str {
       = start:position!() i:identifier() _ "=" _ e:binary_op() ";" end:position!()
       { Expr::Reassign(i, Box::new(e), start..end) }
 
+  pub rule field_reassignment() -> Expr
+      = start:position!() field:arithmetic() _ "=" _ e:binary_op() ";" end:position!() {?
+          match field {
+            Expr::Field(_, _, _) => Ok(Expr::ReassignField(Box::new(field), Box::new(e), start..end)),
+            _ => Err("a field"),
+          }
+      }
+
   pub rule parameter_decl() -> (Parameter, Range<usize>)
       = _ "//" _  "'" param_name:identifier() "'" _ "is" _ "of" _ "type" _ 
-      start:position!() ty:identifier() end:position!() "."
+      start:position!() typename:type_name() end:position!() "."
       {
           (Parameter {
               name: param_name,
-              typename: Type::from(ty)
+              typename
           }, start..end)
       }
 
@@ -177,12 +348,18 @@ peg::parser!(pub grammar parser() for str {
   pub rule expression() -> Expr
       = 
       while_expr()
+      / for_expr()
+      / if_expr()
+      / match_expr()
+      / start:position!() "break" !ident_char() end:position!() { Expr::Break(start..end) }
+      / start:position!() "continue" !ident_char() end:position!() { Expr::Continue(start..end) }
+      / assignment()
       / reassignment()
-      / while_expr()
+      / field_reassignment()
       / binary_op()
 
   pub rule statements() -> Vec<Expr>
-      = stmt:(expression()*) { stmt }
+      = stmt:((_ stmt:expression() _ ";"? {stmt})*) { stmt }
   
   pub rule else_expr() -> Expr
       = start:position!() "else" _ body:block() end:position!()
@@ -201,15 +378,12 @@ peg::parser!(pub grammar parser() for str {
 
       {
         Expr::If(Box::new(expr), Box::new(if_body), 
-          if elif_body.len() > 0 {
+          if !elif_body.is_empty() {
             Some(elif_body)
           } else {
             None
           }, 
-          match else_body {
-            Some(v) => Some(Box::new(v)),
-            None => None
-          }, start..end)
+          else_body.map(Box::new), start..end)
       }
 
   pub rule while_expr() -> Expr 
@@ -218,24 +392,90 @@ peg::parser!(pub grammar parser() for str {
         Expr::While(Box::new(cond), Box::new(stmts), start..end)
       }
 
-  pub rule binary_op() -> Expr = precedence! {
-    lhs:@ _ "is" _ start:position!() rhs:(@) { Expr::Equality(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ "is" _ "not" _ rhs:(@) { Expr::NotEqual(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ "<" _ rhs:(@) { Expr::LessThan(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ ">" _ rhs:(@) { Expr::GreaterThan(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ "<=" _ rhs:(@) { Expr::GreaterThanEqual(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ ">=" _ rhs:(@) { Expr::LessThanEqual(Box::new(lhs), Box::new(rhs)) }
+  pub rule for_expr() -> Expr
+      = start:position!() "for" __ name_start:position!() name:identifier() name_end:position!() __ "in" __
+        values:(range() / binary_op()) _ stmts:block() end:position!()
+      {
+        Expr::For(name, name_start..name_end, Box::new(values), Box::new(stmts), start..end)
+      }
+
+  rule range() -> Expr
+      = lhs:arithmetic() _ ".." inclusive:"="? _ rhs:arithmetic()
+      {
+        Expr::Range(Box::new(lhs), Box::new(rhs), inclusive.is_some())
+      }
+
+  pub rule match_expr() -> Expr
+      = start:position!() "match" !ident_char() _ value:binary_op() _ "{" arms:((_ arm:match_arm() {arm})*) _ "}" end:position!()
+      {
+        Expr::Match(Box::new(value), arms, start..end)
+      }
+
+  // Circle(r) { ... }, Empty { ... } or else { ... }
+  rule match_arm() -> MatchArm
+      = start:position!() "else" end:position!() _ body:block()
+      {
+        MatchArm { variant: None, bindings: Vec::new(), body, loc: start..end }
+      }
+      / start:position!() name:identifier() end:position!() _
+        bindings:("(" bindings:((_ s:position!() binding:identifier() e:position!() _ { (binding, s..e) }) ** ",") ")" { bindings })? _
+        body:block()
+      {
+        MatchArm { variant: Some(name), bindings: bindings.unwrap_or_default(), body, loc: start..end }
+      }
+
+  // Comparisons don't associate, so `a < b < c` is rejected instead of
+  // comparing the result of `a < b` against `c`.
+  pub rule binary_op() -> Expr
+      = lhs:arithmetic() _ op:comparison_op() _ rhs:arithmetic() not_chained()
+      { op(Box::new(lhs), Box::new(rhs)) }
+      / arithmetic()
+
+  rule not_chained()
+      = !(_ comparison_op())
+      / _ comparison_op() _ chained()
+
+  rule chained() = expected!("a single comparison")
+
+  // Longer operators come first so `<=` isn't read as `<` followed by `=`
+  rule comparison_op() -> BinaryExpr
+      = "is" __ "not" !ident_char() { Expr::NotEqual }
+      / "is" !ident_char() { Expr::Equality }
+      / "<=" { Expr::LessThanEqual }
+      / ">=" { Expr::GreaterThanEqual }
+      / "<" { Expr::LessThan }
+      / ">" { Expr::GreaterThan }
+
+  pub rule arithmetic() -> Expr = precedence! {
+    lhs:(@) _ "+" _ rhs:@ { Expr::Addition(Box::new(lhs), Box::new(rhs)) }
+    lhs:(@) _ "-" _ rhs:@ { Expr::Subtraction(Box::new(lhs), Box::new(rhs)) }
     --
-    lhs:@ _ "+" _ rhs:(@) { Expr::Addition(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ "-" _ rhs:(@) { Expr::Subtraction(Box::new(lhs), Box::new(rhs)) }
+    lhs:(@) _ "*" _ rhs:@ { Expr::Multiplication(Box::new(lhs), Box::new(rhs)) }
+    lhs:(@) _ "/" _ rhs:@ { Expr::Division(Box::new(lhs), Box::new(rhs)) }
     --
-    lhs:@ _ "*" _ rhs:(@) { Expr::Multiplication(Box::new(lhs), Box::new(rhs)) }
-    lhs:@ _ "/" _ rhs:(@) { Expr::Division(Box::new(lhs), Box::new(rhs)) }
+    start:position!() "-" _ expr:@ {
+      match expr {
+        Expr::Number(value, loc) => Expr::Number(-value, start..loc.end),
+        expr => {
+          let end = expr.expression_range().end;
+          Expr::Negate(Box::new(expr), start..end)
+        }
+      }
+    }
     --
     lhs:@ _ "^" _ rhs:(@) { Expr::Power(Box::new(lhs), Box::new(rhs)) }
     --
+    record:@ "." start:position!() field:identifier() end:position!() {
+      Expr::Field(Box::new(record), field, start..end)
+    }
+    --
+
+    m:match_expr() { m }
+
+    start:position!() "fn" _ "(" params:((_ param:lambda_param() _ {param}) ** ",") ")" _ body:block() end:position!()
+    { Expr::Lambda(params, Box::new(body), start..end) }
 
-    start:position!() func_name:identifier() end:position!() _ "(" s2:position!() values:((_ expr:expression() _ {expr}) ** ",") e2:position!() _ ")"
+    start:position!() func_name:function_name() end:position!() _ "(" s2:position!() values:((_ expr:expression() _ {expr}) ** ",") e2:position!() _ ")"
     { Expr::Call(func_name, values, start..end, s2..e2) }
 
     "(" _ expr:expression() _ ")" { expr }
@@ -244,13 +484,28 @@ peg::parser!(pub grammar parser() for str {
     start:position!() "" i:identifier() "" end:position!() { Expr::Var(i, start..end) }
   }
 
+  // Functions from other modules are called as `module.function(...)`
+  rule function_name() -> String
+      = module:(module:identifier() "." { module })? name:identifier()
+      {
+        match module {
+          Some(module) => [module, name].join("."),
+          None => name,
+        }
+      }
+
+  // x: Int, with the range of the type
+  rule lambda_param() -> (Parameter, Range<usize>)
+      = name:identifier() _ ":" _ start:position!() typename:type_name() end:position!()
+      {
+          (Parameter { name, typename }, start..end)
+      }
+
   pub rule parameters() -> Vec<(Parameter, Range<usize>)>
       = params:(parameter_decl()*) { params }
 
   pub rule return_stmt() -> Type 
-      = "//" _ "Returns:" _ ty:identifier() {
-          Type::from(ty)
-      }
+      = "//" _ "Returns:" _ ty:type_name() { ty }
 
   #[cache]
   pub rule identifier() -> String 
@@ -262,12 +517,43 @@ peg::parser!(pub grammar parser() for str {
     = start:position!() number:$(['0'..='9']+) end:position!() {
       Expr::Number(number.parse().unwrap(), start..end)
     }
-    / start:position!() "\"" s:$([^'"'..='"']+) "\"" end:position!() {
-      Expr::String(s.to_owned(), start..end)
+    / start:position!() "\"" parts:(string_part()*) "\"" end:position!() {
+      string_literal(parts, start..end)
     }
-    
+
+  // A run of plain text or a single escape becomes an `Expr::String`, while
+  // `{expr}` is kept as the expression to interpolate.
+  rule string_part() -> Expr
+    = start:position!() text:$([^ '"' | '\\' | '{']+) end:position!() {
+      Expr::String(text.to_owned(), start..end)
+    }
+    / start:position!() "\\" ch:escape() end:position!() {
+      Expr::String(ch.to_string(), start..end)
+    }
+    / "{" _ expr:expression() _ "}" { expr }
+
+  rule escape() -> char
+    = quiet!{
+      "n" { '\n' }
+      / "t" { '\t' }
+      / "r" { '\r' }
+      / "\\" { '\\' }
+      / "\"" { '"' }
+      / "'" { '\'' }
+      / "{" { '{' }
+      / "}" { '}' }
+    }
+    / quiet!{"u{"} code:$(['0'..='9' | 'a'..='f' | 'A'..='F']*<1,6>) "}" {?
+      u32::from_str_radix(code, 16).ok()
+        .and_then(std::char::from_u32)
+        .ok_or("a Unicode scalar value")
+    }
+    / expected!("an escape sequence")
+
+  rule ident_char() = ['a'..='z' | 'A'..='Z' | '_' | '0'..='9']
 
   // Ignore these rules
   #[cache]
   rule _() = quiet!{[' ' | '\t' | '\n' | '\r']*}
+  rule __() = quiet!{[' ' | '\t' | '\n' | '\r']+}
 });
\ No newline at end of file
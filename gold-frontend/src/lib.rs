@@ -1,3 +1,4 @@
 pub mod frontend;
 pub mod error;
-pub mod parse;
\ No newline at end of file
+pub mod parse;
+pub mod module;
\ No newline at end of file
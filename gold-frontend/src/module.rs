@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::TypeError;
+use crate::frontend::Expr;
+use crate::parse::{Parser, Program};
+
+/// A single Gold file, parsed, along with the modules it uses.
+pub struct Module {
+    /// What other files call this module by, which is its file name without
+    /// the extension: `math_utils.gold` is used as `math_utils.square(2)`
+    pub name: String,
+    pub filename: String,
+    pub source: String,
+    pub program: Program,
+    /// Where each module used by this one ended up in the loaded list
+    pub imports: Vec<usize>,
+    /// Missing files and import cycles found while following `// Uses:` lines
+    pub errors: Vec<TypeError>,
+}
+
+/// Loads a file and every module it uses, directly or not. Modules come back
+/// in dependency order, each one after everything it uses, so the file that
+/// was asked for is always the last one.
+pub fn load(filename: &str, source: String) -> Vec<Module> {
+    let mut loader = Loader::default();
+    loader.visit(Path::new(filename), source);
+    loader.modules
+}
+
+#[derive(Default)]
+struct Loader {
+    modules: Vec<Module>,
+    loaded: HashMap<PathBuf, usize>,
+    // The chain of files currently being loaded, used to spot cycles
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    fn visit(&mut self, path: &Path, source: String) -> usize {
+        let filename = path.display().to_string();
+        let program = Parser::from_source(&filename, source.to_owned()).parse();
+        let key = canonical(path);
+        self.stack.push(key.to_owned());
+
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut imports = Vec::new();
+        let mut errors = Vec::new();
+        for item in &program.functions {
+            if let Expr::Import(file, loc) = item {
+                let import_path = directory.join(file);
+                let import_key = canonical(&import_path);
+                if let Some(index) = self.stack.iter().position(|p| *p == import_key) {
+                    let mut cycle: Vec<String> = self.stack[index..].iter().map(|p| display_name(p)).collect();
+                    cycle.push(display_name(&import_key));
+                    errors.push(TypeError::CircularImport(cycle, loc.to_owned()));
+                    continue;
+                }
+                let name = module_name(&import_path);
+                if !is_identifier(&name) {
+                    errors.push(TypeError::InvalidModuleName(name, loc.to_owned()));
+                    continue;
+                }
+                if let Some(&index) = self.loaded.get(&import_key) {
+                    imports.push(index);
+                    continue;
+                }
+                match fs::read_to_string(&import_path) {
+                    Ok(source) => imports.push(self.visit(&import_path, source)),
+                    Err(_) => errors.push(TypeError::ModuleNotFound(file.to_owned(), loc.to_owned())),
+                }
+            }
+        }
+
+        self.stack.pop();
+        self.modules.push(Module {
+            name: module_name(path),
+            filename,
+            source,
+            program,
+            imports,
+            errors,
+        });
+        self.loaded.insert(key, self.modules.len() - 1);
+        self.modules.len() - 1
+    }
+}
+
+// Unsaved files can't be canonicalized, but they can't be imported either
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
@@ -1,16 +1,28 @@
+use std::ops::Range;
 use std::{fs::File, io::Read};
 
-use codespan_reporting::files::Error;
+use peg::error::ParseError;
+use peg::str::LineCol;
 
-use crate::error::report_parse_error;
+use crate::error::report_errors;
 use crate::frontend::Expr;
 use crate::frontend::parser;
 
+// Give up on a file after this many syntax errors, the rest are usually noise.
+const MAX_PARSE_ERRORS: usize = 100;
+
 pub struct Parser<'a> {
     pub filename: &'a str,
     pub file_contents: String,
 }
 
+/// Every function that could be parsed from a file, along with all of the
+/// syntax errors that had to be skipped over to get to them.
+pub struct Program {
+    pub functions: Vec<Expr>,
+    pub errors: Vec<ParseError<LineCol>>,
+}
+
 impl<'a> Parser<'a> {
     pub fn new(filename: &'a str) -> Result<Parser<'a>, String> {
         let mut contents = String::new();
@@ -24,23 +36,237 @@ impl<'a> Parser<'a> {
             Err(e) => { return Err(e.to_string()); }
         }
         Ok(Self {
-            filename: filename,
+            filename,
             file_contents: contents,
         })
     }
 
-    pub fn parse_file(&mut self) -> Result<Expr, String> {
-        let expr = parser::function(self.file_contents.as_str());
-        match expr {
-            Ok(expr) => { Ok(expr) }
-            Err(err) => {
-                let res = report_parse_error(self.filename, self.file_contents.as_str(), err);
-                if res.is_err() {
-                    Err("Could not parse file".to_owned())
-                } else {
-                    Ok(Expr::NoExpr)
+    /// Wraps source that didn't come straight from the file system, such as
+    /// an unsaved buffer in an editor.
+    pub fn from_source(filename: &'a str, file_contents: String) -> Parser<'a> {
+        Self {
+            filename,
+            file_contents,
+        }
+    }
+
+    /// Parses the file contents without reporting anything, leaving it up to
+    /// the caller to decide what to do with syntax errors.
+    ///
+    /// When a statement fails to parse it is skipped and parsing starts again
+    /// after it. When a function header is broken the whole function is
+    /// skipped. The skipped text is blanked out rather than removed so every
+    /// location in the partial AST still points into the original source.
+    pub fn parse(&self) -> Program {
+        let mut text = self.file_contents.to_owned();
+        let mut errors = Vec::new();
+        loop {
+            match parser::program(text.as_str()) {
+                Ok(functions) => return Program { functions, errors },
+                Err(err) => {
+                    let skip = recovery_range(text.as_str(), err.location.offset);
+                    errors.push(err);
+                    match skip {
+                        Some(range) if errors.len() < MAX_PARSE_ERRORS => blank(&mut text, range),
+                        _ => return Program { functions: Vec::new(), errors },
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn parse_file(&mut self) -> Result<Vec<Expr>, String> {
+        let program = self.parse();
+        report_errors(self.filename, self.file_contents.as_str(), program.errors, Vec::new());
+        Ok(program.functions)
+    }
+}
+
+/// The source of a single function or structure: its comment header followed
+/// by its body, which structures don't have.
+struct Chunk {
+    start: usize,
+    body: Option<Range<usize>>,
+    end: usize,
+}
+
+/// Splits the source into functions and structures. One starts at the first
+/// comment line that follows the closing brace of the previous function, or at
+/// a `// name is a ...` line that follows the header of a structure.
+fn chunks(text: &str) -> Vec<Chunk> {
+    let mut chunks = vec![Chunk { start: 0, body: None, end: text.len() }];
+    let mut depth = 0;
+    let mut in_header = false;
+    let mut body_start = 0;
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '/' if depth == 0 && matches!(chars.peek(), Some((_, '/'))) => {
+                let after_body = !in_header && chunks.last().unwrap().body.is_some();
+                let after_header = in_header && pos > chunks.last().unwrap().start && is_declaration(text, pos);
+                if after_body || after_header {
+                    chunks.last_mut().unwrap().end = pos;
+                    chunks.push(Chunk { start: pos, body: None, end: text.len() });
+                }
+                in_header = true;
+                // Header lines can contain anything, so skip to the end of the line
+                for (_, ch) in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => skip_string(&mut chars),
+            '{' => {
+                if depth == 0 {
+                    in_header = false;
+                    body_start = pos;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    chunks.last_mut().unwrap().body = Some(body_start..pos + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A body that never got closed runs to the end of the file
+    if depth > 0 {
+        chunks.last_mut().unwrap().body = Some(body_start..text.len());
+    }
+    chunks
+}
+
+/// Whether the comment line at `pos` starts a declaration, like
+/// `// Point is a structure.` or `// Shape is one of:`
+fn is_declaration(text: &str, pos: usize) -> bool {
+    let line = text[pos..].lines().next().unwrap_or("");
+    let words = line.trim_start_matches('/').split_whitespace().collect::<Vec<&str>>();
+    words.len() >= 4 && words[1] == "is" && ["a", "an", "one"].contains(&words[2])
+}
+
+/// How many blocks are still open at the end of `text`, leaving out braces in
+/// strings and comment lines.
+pub(crate) fn brace_depth(text: &str) -> usize {
+    let mut depth = 0usize;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, ch)) = chars.next() {
+        match ch {
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                for (_, ch) in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => skip_string(&mut chars),
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn skip_string(chars: &mut std::iter::Peekable<std::str::CharIndices>) {
+    while let Some((_, ch)) = chars.next() {
+        match ch {
+            '\\' => { chars.next(); }
+            '"' => break,
+            '{' => skip_interpolation(chars),
+            _ => {}
+        }
+    }
+}
+
+/// Skips the expression inside `{...}` in a string, which can contain strings
+/// and blocks of its own.
+fn skip_interpolation(chars: &mut std::iter::Peekable<std::str::CharIndices>) {
+    let mut depth = 0;
+    while let Some((_, ch)) = chars.next() {
+        match ch {
+            '"' => skip_string(chars),
+            '{' => depth += 1,
+            '}' if depth == 0 => break,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+}
+
+/// Finds the text to skip so parsing can carry on after an error at `offset`.
+fn recovery_range(text: &str, offset: usize) -> Option<Range<usize>> {
+    let chunk = chunks(text).into_iter()
+        .find(|chunk| (chunk.start..chunk.end).contains(&offset) || chunk.end == text.len())?;
+    let whole_function = chunk.start..chunk.end;
+
+    let body = match &chunk.body {
+        Some(body) if offset > body.start => body.to_owned(),
+        // The error is in the comment header, so the function can't be salvaged
+        _ => return non_blank(text, whole_function),
+    };
+    if offset >= body.end {
+        return non_blank(text, body.end..chunk.end).or_else(|| non_blank(text, whole_function));
+    }
+
+    statement_range(text, body, offset)
+        .and_then(|range| non_blank(text, range))
+        .or_else(|| non_blank(text, whole_function))
+}
+
+/// The statement inside `body` that contains `offset`. Statements end at a
+/// `;` or at the closing brace of a nested block, and the brace that closes
+/// the enclosing block is never part of a statement.
+fn statement_range(text: &str, body: Range<usize>, offset: usize) -> Option<Range<usize>> {
+    let mut start = body.start + 1;
+    let mut chars = text[start..offset].char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '"' => skip_string(&mut chars),
+            ';' | '{' | '}' => start = body.start + 1 + pos + 1,
+            _ => {}
+        }
+    }
+
+    let mut depth = 0;
+    let mut chars = text[start..body.end].char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '"' => skip_string(&mut chars),
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(start..start + pos),
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start..start + pos + 1);
                 }
             }
+            ';' if depth == 0 => return Some(start..start + pos + 1),
+            _ => {}
         }
     }
-}
\ No newline at end of file
+    Some(start..body.end)
+}
+
+fn non_blank(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let end = range.end.min(text.len());
+    if text.get(range.start..end)?.trim().is_empty() {
+        None
+    } else {
+        Some(range.start..end)
+    }
+}
+
+/// Replaces the range with whitespace of the same byte length, keeping the
+/// newlines so line numbers stay the same.
+fn blank(text: &mut String, range: Range<usize>) {
+    let replacement = text[range.clone()].chars()
+        .map(|ch| if ch == '\n' { "\n".to_owned() } else { " ".repeat(ch.len_utf8()) })
+        .collect::<String>();
+    text.replace_range(range, replacement.as_str());
+}
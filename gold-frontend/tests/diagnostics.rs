@@ -0,0 +1,106 @@
+use codespan_reporting::diagnostic::Diagnostic;
+
+use gold_frontend::error::parse_error_diagnostic;
+use gold_frontend::frontend::Expr;
+use gold_frontend::parse::Parser;
+
+/// The diagnostics for every syntax error in a file, in the order they were
+/// found.
+fn diagnostics(source: &str) -> Vec<Diagnostic<usize>> {
+    let program = Parser::from_source("test.gold", source.to_owned()).parse();
+    program.errors.iter().map(|err| parse_error_diagnostic(source, err, 0)).collect()
+}
+
+/// The message of the only diagnostic for a file, along with its notes.
+fn only_message(source: &str) -> (String, Vec<String>) {
+    let diagnostics = diagnostics(source);
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    (diagnostics[0].message.to_owned(), diagnostics[0].notes.to_owned())
+}
+
+#[test]
+fn body_syntax_errors_are_not_header_mistakes() {
+    let (message, notes) = only_message("// main is a function.\n// Params:\n// Returns: Void\nfn {\n  var x = 1 +;\n}\n");
+    assert!(message.starts_with("Expected one of"), "{}", message);
+    assert!(notes.is_empty(), "{:?}", notes);
+
+    // Comments in a body aren't a header either
+    let (message, _) = only_message("// main is a function.\n// Params:\n// Returns: Void\nfn {\n  var s = \"{\";\n  // the next line is broken\n  var x = 1 +;\n}\n");
+    assert!(message.starts_with("Expected one of"), "{}", message);
+}
+
+#[test]
+fn recovers_once_per_broken_statement_and_function() {
+    let source = "// first is a function.
+// Params:
+// Returns: Int
+fn {
+  var a = 1 +;
+  var b = (2;
+  a
+}
+
+// broken is a functoin.
+// Params:
+// Returns: Int
+fn {
+  1
+}
+
+// last is a function.
+// Params:
+// Returns: Int
+fn {
+  var c = * 3;
+  4
+}
+";
+    let program = Parser::from_source("test.gold", source.to_owned()).parse();
+    let lines = program.errors.iter().map(|err| err.location.line).collect::<Vec<usize>>();
+    assert_eq!(lines, vec![5, 6, 10, 21]);
+    let messages = diagnostics(source).into_iter().map(|diagnostic| diagnostic.message).collect::<Vec<String>>();
+    assert!(messages[0].starts_with("Expected one of"), "{}", messages[0]);
+    assert!(messages[1].starts_with("Expected one of"), "{}", messages[1]);
+    assert_eq!(messages[2], "Functions are declared as `name is a function.`");
+    assert!(messages[3].starts_with("Expected one of"), "{}", messages[3]);
+
+    // Everything that could be salvaged is still there
+    let names = program.functions.iter().filter_map(|function| match function {
+        Expr::Function(name, ..) => Some(name.as_str()),
+        _ => None,
+    }).collect::<Vec<&str>>();
+    assert_eq!(names, vec!["first", "last"]);
+}
+
+#[test]
+fn suggests_fn_after_the_header() {
+    let (message, notes) = only_message("// main is a function.\n// Params:\n// Returns: Int\n{\n  1\n}\n");
+    assert_eq!(message, "Function bodies start with `fn` after the comment header");
+    assert_eq!(notes, vec!["did you mean `fn {`?"]);
+
+    let (message, notes) = only_message("// main is a function.\n// Params:\n// Returns: Int\nfunc {\n  1\n}\n");
+    assert_eq!(message, "Function bodies start with `fn` after the comment header");
+    assert_eq!(notes, vec!["did you mean `fn {`?"]);
+}
+
+#[test]
+fn suggests_how_to_write_a_parameter() {
+    let (message, notes) = only_message("// add is a function.\n// Params:\n// 'a' is of type Int\n// Returns: Int\nfn {\n  a\n}\n");
+    assert_eq!(message, "Parameter declarations end with a period");
+    assert_eq!(notes, vec!["did you mean `// 'a' is of type Int.`?"]);
+
+    let (message, notes) = only_message("// add is a function.\n// Params:\n// a is of type Int.\n// Returns: Int\nfn {\n  a\n}\n");
+    assert_eq!(message, "Parameter names are written in single quotes");
+    assert_eq!(notes, vec!["did you mean `// 'a' is of type Int.`?"]);
+}
+
+#[test]
+fn suggests_how_to_write_the_return_type() {
+    let (message, notes) = only_message("// one is a function.\n// Params:\n// Return: Int\nfn {\n  1\n}\n");
+    assert_eq!(message, "The return type is declared with `// Returns:`");
+    assert_eq!(notes, vec!["did you mean `// Returns: Int`?"]);
+
+    let (message, notes) = only_message("// one is a function.\n// Params:\nfn {\n  1\n}\n");
+    assert_eq!(message, "The comment header is missing its `// Returns:` line");
+    assert_eq!(notes, vec!["add `// Returns: Void` above this line"]);
+}
@@ -0,0 +1,420 @@
+use gold_frontend::frontend::{parser, Expr, Type};
+
+/// Renders an expression as an s-expression so the shape of the tree is easy
+/// to compare against.
+fn sexpr(expr: &Expr) -> String {
+    let binary = |op: &str, lhs: &Expr, rhs: &Expr| ["(", op, " ", sexpr(lhs).as_str(), " ", sexpr(rhs).as_str(), ")"].join("");
+    match expr {
+        Expr::Number(value, _) => value.to_string(),
+        Expr::String(value, _) => ["\"", value, "\""].join(""),
+        Expr::Var(name, _) => name.to_owned(),
+        Expr::Call(name, args, _, _) => {
+            let args = args.iter().map(sexpr).collect::<Vec<String>>();
+            ["(", name, " ", args.join(" ").as_str(), ")"].join("")
+        }
+        Expr::Negate(expr, _) => ["(neg ", sexpr(expr).as_str(), ")"].join(""),
+        Expr::Field(record, field, _) => ["(. ", sexpr(record).as_str(), " ", field, ")"].join(""),
+        Expr::ReassignField(field, value, _) => binary("=", field, value),
+        Expr::Equality(lhs, rhs) => binary("is", lhs, rhs),
+        Expr::NotEqual(lhs, rhs) => binary("is-not", lhs, rhs),
+        Expr::LessThan(lhs, rhs) => binary("<", lhs, rhs),
+        Expr::GreaterThan(lhs, rhs) => binary(">", lhs, rhs),
+        Expr::LessThanEqual(lhs, rhs) => binary("<=", lhs, rhs),
+        Expr::GreaterThanEqual(lhs, rhs) => binary(">=", lhs, rhs),
+        Expr::Addition(lhs, rhs) => binary("+", lhs, rhs),
+        Expr::Subtraction(lhs, rhs) => binary("-", lhs, rhs),
+        Expr::Multiplication(lhs, rhs) => binary("*", lhs, rhs),
+        Expr::Division(lhs, rhs) => binary("/", lhs, rhs),
+        Expr::Power(lhs, rhs) => binary("^", lhs, rhs),
+        other => panic!("unexpected expression {:?}", other),
+    }
+}
+
+fn parse(source: &str) -> String {
+    match parser::binary_op(source) {
+        Ok(expr) => sexpr(&expr),
+        Err(err) => panic!("failed to parse `{}`: {}", source, err),
+    }
+}
+
+#[test]
+fn less_than_equal() {
+    assert_eq!(parse("a <= b"), "(<= a b)");
+    assert_eq!(parse("a<=b"), "(<= a b)");
+}
+
+#[test]
+fn greater_than_equal() {
+    assert_eq!(parse("a >= b"), "(>= a b)");
+    assert_eq!(parse("a>=b"), "(>= a b)");
+}
+
+#[test]
+fn strict_comparisons() {
+    assert_eq!(parse("a < b"), "(< a b)");
+    assert_eq!(parse("a > b"), "(> a b)");
+}
+
+#[test]
+fn is_not() {
+    assert_eq!(parse("a is not b"), "(is-not a b)");
+    assert_eq!(parse("a is b"), "(is a b)");
+}
+
+#[test]
+fn is_needs_a_word_boundary() {
+    assert_eq!(parse("a is nothing"), "(is a nothing)");
+    assert_eq!(parse("a is island"), "(is a island)");
+    assert!(parser::binary_op("a isb").is_err());
+}
+
+#[test]
+fn comparisons_bind_looser_than_arithmetic() {
+    assert_eq!(parse("a + 1 <= b * 2"), "(<= (+ a 1) (* b 2))");
+    assert_eq!(parse("a - b is not c / d"), "(is-not (- a b) (/ c d))");
+}
+
+#[test]
+fn arithmetic_precedence() {
+    assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+    assert_eq!(parse("1 * 2 + 3"), "(+ (* 1 2) 3)");
+    assert_eq!(parse("1 - 2 - 3"), "(- (- 1 2) 3)");
+    assert_eq!(parse("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+}
+
+#[test]
+fn power_is_right_associative() {
+    assert_eq!(parse("2 ^ 3 ^ 2"), "(^ 2 (^ 3 2))");
+    assert_eq!(parse("2 * 3 ^ 2"), "(* 2 (^ 3 2))");
+}
+
+#[test]
+fn unary_minus() {
+    assert_eq!(parse("-a"), "(neg a)");
+    assert_eq!(parse("-(a + b)"), "(neg (+ a b))");
+    assert_eq!(parse("- -a"), "(neg (neg a))");
+    assert_eq!(parse("-a * b"), "(* (neg a) b)");
+    assert_eq!(parse("-a ^ 2"), "(neg (^ a 2))");
+    assert_eq!(parse("a - -b"), "(- a (neg b))");
+    assert_eq!(parse("-foo(1)"), "(neg (foo 1))");
+}
+
+#[test]
+fn negative_literals() {
+    assert_eq!(parse("-5"), "-5");
+    assert_eq!(parse("(-5)"), "-5");
+    assert_eq!(parse("3 - (-5)"), "(- 3 -5)");
+    assert_eq!(parse("3 -5"), "(- 3 5)");
+    assert_eq!(parse("a < -1"), "(< a -1)");
+}
+
+#[test]
+fn negative_literal_location_includes_the_sign() {
+    match parser::binary_op("(-5)").unwrap() {
+        Expr::Number(-5, loc) => assert_eq!(loc, 1..3),
+        other => panic!("expected a number, got {:?}", other),
+    }
+}
+
+#[test]
+fn comparisons_do_not_chain() {
+    for source in &["a < b < c", "a <= b >= c", "a is b is c", "a is not b < c"] {
+        let err = parser::binary_op(source).unwrap_err();
+        assert!(err.expected.tokens().any(|t| t == "a single comparison"), "`{}` gave {}", source, err);
+    }
+}
+
+#[test]
+fn parenthesized_comparisons_can_be_compared() {
+    assert_eq!(parse("(a < b) is (c < d)"), "(is (< a b) (< c d))");
+}
+
+fn string(source: &str) -> Expr {
+    match parser::literal(source) {
+        Ok(expr) => expr,
+        Err(err) => panic!("failed to parse `{}`: {}", source, err),
+    }
+}
+
+fn text(source: &str) -> String {
+    match string(source) {
+        Expr::String(text, _) => text,
+        other => panic!("expected a plain string, got {:?}", other),
+    }
+}
+
+#[test]
+fn empty_string() {
+    assert_eq!(text(r#""""#), "");
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(text(r#""a\nb""#), "a\nb");
+    assert_eq!(text(r#""\t\r""#), "\t\r");
+    assert_eq!(text(r#""say \"hi\"""#), "say \"hi\"");
+    assert_eq!(text(r#""back\\slash""#), "back\\slash");
+    assert_eq!(text(r#""it\'s""#), "it's");
+    assert_eq!(text(r#""\{not interpolated\}""#), "{not interpolated}");
+}
+
+#[test]
+fn unicode_escapes() {
+    assert_eq!(text(r#""caf\u{e9}""#), "café");
+    assert_eq!(text(r#""\u{1F947}""#), "🥇");
+    assert!(parser::literal(r#""\u{110000}""#).is_err());
+    assert!(parser::literal(r#""\u{D800}""#).is_err());
+    assert!(parser::literal(r#""\u{}""#).is_err());
+}
+
+#[test]
+fn unknown_escapes_are_errors() {
+    let err = parser::literal(r#""\q""#).unwrap_err();
+    assert!(err.expected.tokens().any(|t| t == "an escape sequence"), "{}", err);
+}
+
+#[test]
+fn string_location_includes_the_quotes() {
+    assert_eq!(string(r#""a\nb""#).expression_range(), 0..6);
+}
+
+#[test]
+fn interpolation() {
+    match string(r#""total: {count + 1}!""#) {
+        Expr::Interpolation(parts, loc) => {
+            let parts = parts.iter().map(sexpr).collect::<Vec<String>>();
+            assert_eq!(parts, vec!["\"total: \"", "(+ count 1)", "\"!\""]);
+            assert_eq!(loc, 0..21);
+        }
+        other => panic!("expected an interpolation, got {:?}", other),
+    }
+}
+
+#[test]
+fn interpolation_merges_escapes_into_text() {
+    match string(r#""\t{a}\n\"{b}""#) {
+        Expr::Interpolation(parts, _) => {
+            let parts = parts.iter().map(sexpr).collect::<Vec<String>>();
+            assert_eq!(parts, vec!["\"\t\"", "a", "\"\n\"\"", "b"]);
+        }
+        other => panic!("expected an interpolation, got {:?}", other),
+    }
+}
+
+#[test]
+fn nested_interpolation() {
+    match string(r#""{"x{y}"}""#) {
+        Expr::Interpolation(parts, _) => match parts.as_slice() {
+            [Expr::Interpolation(inner, _)] => {
+                assert_eq!(inner.iter().map(sexpr).collect::<Vec<String>>(), vec!["\"x\"", "y"]);
+            }
+            other => panic!("expected a nested interpolation, got {:?}", other),
+        },
+        other => panic!("expected an interpolation, got {:?}", other),
+    }
+}
+
+#[test]
+fn structure_declaration() {
+    let source = "// Point is a structure.\n// Fields:\n// 'x' is of type Int.\n// 'origin' is of type Point.\n";
+    match parser::program(source).unwrap().as_slice() {
+        [Expr::Structure(name, fields, loc)] => {
+            assert_eq!(name, "Point");
+            assert_eq!(*loc, 3..8);
+            let fields = fields.iter().map(|(field, _)| (field.name.as_str(), field.typename.clone())).collect::<Vec<(&str, Type)>>();
+            assert_eq!(fields, vec![("x", Type::Int), ("origin", Type::Named("Point".to_owned()))]);
+        }
+        other => panic!("expected a structure, got {:?}", other),
+    }
+}
+
+#[test]
+fn structures_and_functions_mix() {
+    let source = "// Point is a structure.\n// Fields:\n// 'x' is of type Int.\n\n\
+                  // get is a function.\n// Params:\n// 'p' is of type Point.\n// Returns: Int\nfn {\n  p.x\n}\n";
+    let items = parser::program(source).unwrap();
+    assert!(matches!(items.as_slice(), [Expr::Structure(..), Expr::Function(..)]));
+}
+
+#[test]
+fn field_access() {
+    assert_eq!(parse("p.x"), "(. p x)");
+    assert_eq!(parse("line.from.x"), "(. (. line from) x)");
+    assert_eq!(parse("origin().x"), "(. (origin ) x)");
+}
+
+#[test]
+fn field_access_binds_tightest() {
+    assert_eq!(parse("p.x + q.y * 2"), "(+ (. p x) (* (. q y) 2))");
+    assert_eq!(parse("-p.x"), "(neg (. p x))");
+    assert_eq!(parse("p.x ^ 2"), "(^ (. p x) 2)");
+    assert_eq!(parse("p.x < q.x"), "(< (. p x) (. q x))");
+}
+
+#[test]
+fn field_reassignment() {
+    let expr = parser::expression("line.to.x = p.x + 1;").unwrap();
+    assert_eq!(sexpr(&expr), "(= (. (. line to) x) (+ (. p x) 1))");
+    assert_eq!(expr.expression_range(), 0..20);
+}
+
+#[test]
+fn only_fields_can_be_reassigned_through_an_expression() {
+    assert!(parser::expression("(a) = 1;").is_err());
+    assert!(parser::expression("f().x = 1;").is_ok());
+}
+
+#[test]
+fn enum_declaration() {
+    let source = "// Shape is one of:\n// 'Circle' with 'r' of type Float.\n\
+                  // 'Rectangle' with 'w' of type Int and 'h' of type Int.\n// 'Empty'.\n";
+    match parser::program(source).unwrap().as_slice() {
+        [Expr::Enumeration(name, variants, _)] => {
+            assert_eq!(name, "Shape");
+            let variants = variants.iter().map(|variant| {
+                let fields = variant.fields.iter().map(|(field, _)| field.name.as_str()).collect::<Vec<&str>>();
+                (variant.name.as_str(), fields)
+            }).collect::<Vec<(&str, Vec<&str>)>>();
+            assert_eq!(variants, vec![("Circle", vec!["r"]), ("Rectangle", vec!["w", "h"]), ("Empty", vec![])]);
+        }
+        other => panic!("expected an enum, got {:?}", other),
+    }
+}
+
+#[test]
+fn enum_needs_a_variant() {
+    assert!(parser::program("// Shape is one of:\n").is_err());
+}
+
+#[test]
+fn match_arms() {
+    let source = "match shape {\n  Circle(r) { r }\n  Rectangle(w, h) { w * h }\n  Empty { 0 }\n  else { 1 }\n}";
+    match parser::expression(source).unwrap() {
+        Expr::Match(value, arms, loc) => {
+            assert_eq!(sexpr(&value), "shape");
+            assert_eq!(loc, 0..source.len());
+            let arms = arms.iter().map(|arm| {
+                let bindings = arm.bindings.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>();
+                (arm.variant.as_deref(), bindings)
+            }).collect::<Vec<(Option<&str>, Vec<&str>)>>();
+            assert_eq!(arms, vec![
+                (Some("Circle"), vec!["r"]),
+                (Some("Rectangle"), vec!["w", "h"]),
+                (Some("Empty"), vec![]),
+                (None, vec![]),
+            ]);
+        }
+        other => panic!("expected a match, got {:?}", other),
+    }
+}
+
+#[test]
+fn match_is_a_keyword() {
+    assert_eq!(parse("matches + 1"), "(+ matches 1)");
+    assert!(matches!(parser::expression("match p.shape { else { 0 } }"), Ok(Expr::Match(..))));
+}
+
+#[test]
+fn match_is_a_value() {
+    match parser::expression("var r = match a { Some(v) { v } else { 0 } };") {
+        Ok(Expr::Assign(name, value, _)) => assert!(name == "r" && matches!(*value, Expr::Match(..))),
+        other => panic!("expected an assignment, got {:?}", other),
+    }
+    match parser::binary_op("1 + match a { else { 2 } } * 3") {
+        Ok(Expr::Addition(_, rhs)) => assert!(matches!(*rhs, Expr::Multiplication(lhs, _) if matches!(*lhs, Expr::Match(..)))),
+        other => panic!("expected an addition, got {:?}", other),
+    }
+}
+
+#[test]
+fn uses_lines() {
+    let source = "// Uses: lib/math_utils.gold  \n// Uses: shapes.gold\n";
+    let items = parser::program(source).unwrap();
+    let imports = items.iter().map(|item| match item {
+        Expr::Import(path, loc) => (path.as_str(), &source[loc.to_owned()]),
+        other => panic!("expected an import, got {:?}", other),
+    }).collect::<Vec<(&str, &str)>>();
+    assert_eq!(imports, vec![("lib/math_utils.gold", "lib/math_utils.gold"), ("shapes.gold", "shapes.gold")]);
+}
+
+#[test]
+fn test_declarations() {
+    let source = "// test_addition is a test.\nfn {\n  assert_eq(1 + 1, 2);\n}\n";
+    match parser::program(source).unwrap().as_slice() {
+        [Expr::Test(name, body, loc)] => {
+            assert_eq!(name, "test_addition");
+            assert_eq!(&source[loc.to_owned()], "test_addition");
+            match body.as_ref() {
+                Expr::Block(stmts, _) => assert_eq!(stmts.iter().map(sexpr).collect::<Vec<String>>(), vec!["(assert_eq (+ 1 1) 2)"]),
+                other => panic!("expected a block, got {:?}", other),
+            }
+        }
+        other => panic!("expected a test, got {:?}", other),
+    }
+}
+
+#[test]
+fn qualified_calls() {
+    assert_eq!(parse("math_utils.square(2) + 1"), "(+ (math_utils.square 2) 1)");
+    assert_eq!(parse("p.x + 1"), "(+ (. p x) 1)");
+}
+
+#[test]
+fn external_functions() {
+    let source = "// cosf is an external function from libm.so.6.\n// Params:\n// 'x' is of type Float.\n// Returns: Float\n\n// fopen is an external function from libc.\n// Params:\n// 'path' is of type CString.\n// 'mode' is of type CString.\n// Returns: Pointer\n";
+    let externals = parser::program(source).unwrap().into_iter().map(|item| match item {
+        Expr::External(name, params, ret, library, _) => {
+            let params = params.into_iter().map(|(param, _)| param.typename).collect::<Vec<Type>>();
+            (name, params, ret, library)
+        }
+        other => panic!("expected an external function, got {:?}", other),
+    }).collect::<Vec<(String, Vec<Type>, Type, String)>>();
+    assert_eq!(externals, vec![
+        ("cosf".to_owned(), vec![Type::Float], Type::Float, "libm.so.6".to_owned()),
+        ("fopen".to_owned(), vec![Type::CString, Type::CString], Type::Pointer, "libc".to_owned()),
+    ]);
+}
+
+#[test]
+fn for_loops() {
+    let source = "for i in 0..=n - 1 { break; }";
+    match parser::expression(source) {
+        Ok(Expr::For(name, name_loc, values, body, _)) => {
+            assert_eq!((name.as_str(), &source[name_loc]), ("i", "i"));
+            match *values {
+                Expr::Range(start, end, inclusive) => {
+                    assert_eq!((sexpr(&start), sexpr(&end), inclusive), ("0".to_owned(), "(- n 1)".to_owned(), true));
+                }
+                other => panic!("expected a range, got {:?}", other),
+            }
+            assert!(matches!(*body, Expr::Block(stmts, _) if matches!(stmts.as_slice(), [Expr::Break(_)])));
+        }
+        other => panic!("expected a for loop, got {:?}", other),
+    }
+    assert!(matches!(parser::expression("for word in words { continue }"), Ok(Expr::For(_, _, values, _, _)) if matches!(*values, Expr::Var(..))));
+    assert_eq!(parse("format + breaks"), "(+ format breaks)");
+}
+
+#[test]
+fn anonymous_functions() {
+    let source = "// apply is a function.\n// Params:\n// 'f' is of type Function(Int, String) -> Bool.\n// Returns: Function(Int) -> Int\nfn {\n  fn(n: Int) { n * 2 }\n}\n";
+    match parser::program(source).unwrap().as_slice() {
+        [Expr::Function(_, params, returns, body, _)] => {
+            let function = |params: Vec<Type>, returns: Type| Type::Function(params, Box::new(returns));
+            assert_eq!(params[0].0.typename, function(vec![Type::Int, Type::String], Type::Bool));
+            assert_eq!(*returns, function(vec![Type::Int], Type::Int));
+            match body.as_ref() {
+                Expr::Block(stmts, _) => match stmts.as_slice() {
+                    [Expr::Lambda(params, body, _)] => {
+                        assert_eq!((params[0].0.name.as_str(), &source[params[0].1.clone()]), ("n", "Int"));
+                        assert!(matches!(body.as_ref(), Expr::Block(stmts, _) if sexpr(&stmts[0]) == "(* n 2)"));
+                    }
+                    other => panic!("expected an anonymous function, got {:?}", other),
+                },
+                other => panic!("expected a block, got {:?}", other),
+            }
+        }
+        other => panic!("expected a function, got {:?}", other),
+    }
+    assert_eq!(parse("fns(1) + f(2)"), "(+ (fns 1) (f 2))");
+}
@@ -0,0 +1,374 @@
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, LabelStyle, Severity};
+use lsp_types::{CompletionItem, CompletionItemKind, DiagnosticSeverity, Position};
+
+use gold_backend::analysis::lower::Analyzer;
+use gold_frontend::error::{parse_error_diagnostic, type_error_diagnostic};
+use gold_frontend::frontend::{Expr, Parameter, TYPE_NAMES};
+use gold_frontend::module;
+use gold_standard::load::SYMBOLS;
+
+/// Maps byte offsets in a document to LSP positions and back.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(pos, _)| pos + 1));
+        Self { line_starts }
+    }
+
+    pub fn position(&self, text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let start = self.line_starts[line];
+        let character = text[start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, text: &str, position: Position) -> usize {
+        let start = match self.line_starts.get(position.line as usize) {
+            Some(start) => *start,
+            None => return text.len(),
+        };
+        let mut units = 0;
+        for (pos, ch) in text[start..].char_indices() {
+            if units >= position.character as usize || ch == '\n' {
+                return start + pos;
+            }
+            units += ch.len_utf16();
+        }
+        text.len()
+    }
+
+    pub fn range(&self, text: &str, range: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(text, range.start), self.position(text, range.end))
+    }
+}
+
+/// An open document along with everything the analysis learned about it.
+pub struct Document {
+    pub text: String,
+    pub lines: LineIndex,
+    pub functions: Vec<Expr>,
+    pub analyzer: Analyzer,
+    pub diagnostics: Vec<Diagnostic<usize>>,
+}
+
+impl Document {
+    /// Analyzes the text of the document at `path`, reading the modules it
+    /// uses from disk. Only errors in the document itself are reported.
+    pub fn new(path: &str, text: String) -> Self {
+        let mut modules = module::load(path, text);
+        let mut analyzer = Analyzer::for_module(&modules[modules.len() - 1]);
+        analyzer.check_module(&modules, modules.len() - 1);
+        let mut document = modules.pop().unwrap();
+        let source = &document.source;
+        let diagnostics = document.program.errors.iter().map(|e| parse_error_diagnostic(source, e, 0))
+            .chain(document.errors.drain(..).map(|e| type_error_diagnostic(e, 0)))
+            .chain(analyzer.errors.drain(..).map(|e| type_error_diagnostic(e, 0)))
+            .collect();
+        Self {
+            lines: LineIndex::new(&document.source),
+            text: document.source,
+            functions: document.program.functions,
+            analyzer,
+            diagnostics,
+        }
+    }
+
+    pub fn lsp_diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        self.diagnostics.iter().map(|diagnostic| {
+            let primary = diagnostic.labels.iter()
+                .find(|label| label.style == LabelStyle::Primary)
+                .or_else(|| diagnostic.labels.first());
+
+            let mut message = diagnostic.message.clone();
+            for label in &diagnostic.labels {
+                if !label.message.is_empty() {
+                    message.push('\n');
+                    message.push_str(&label.message);
+                }
+            }
+            for note in &diagnostic.notes {
+                message.push('\n');
+                message.push_str(note);
+            }
+
+            let range = match primary {
+                Some(label) => self.lines.range(&self.text, label.range.clone()),
+                None => lsp_types::Range::default(),
+            };
+            let severity = match diagnostic.severity {
+                Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+                Severity::Note => DiagnosticSeverity::INFORMATION,
+                Severity::Help => DiagnosticSeverity::HINT,
+            };
+            lsp_types::Diagnostic {
+                range,
+                severity: Some(severity),
+                source: Some("gold".to_owned()),
+                message,
+                ..Default::default()
+            }
+        }).collect()
+    }
+
+    /// Hover text for the function or variable under the cursor.
+    pub fn hover(&self, offset: usize) -> Option<(String, Range<usize>)> {
+        match self.node_at(offset)? {
+            Expr::Call(name, _, loc, _) => Some((self.function_signature(name)?, loc.to_owned())),
+            Expr::Function(name, _, _, _, loc) | Expr::Test(name, _, loc) | Expr::Structure(name, _, loc)
+            | Expr::Enumeration(name, _, loc) | Expr::External(name, _, _, _, loc) => {
+                Some((self.function_signature(name)?, loc.to_owned()))
+            }
+            Expr::Var(name, loc) => {
+                let scope = self.enclosing_function(offset)
+                    .and_then(|function| self.analyzer.functions.get(function))
+                    .map(|sig| sig.scope_index)?;
+                match self.analyzer.variables.variables_of(scope).get(name) {
+                    Some(var) => {
+                        Some((["```gold\n", name, ": ", var.ty.to_string().as_str(), "\n```"].join(""), loc.to_owned()))
+                    }
+                    // Variants without values are written like variables
+                    None => Some((self.function_signature(name)?, loc.to_owned())),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The definition of the function, structure or enum variant used at
+    /// `offset`.
+    pub fn definition(&self, offset: usize) -> Option<Range<usize>> {
+        let name = match self.node_at(offset)? {
+            Expr::Call(name, _, _, _) | Expr::Var(name, _) => name,
+            _ => return None,
+        };
+        self.functions().find_map(|f| match f {
+            Expr::Function(fn_name, _, _, _, loc) | Expr::Structure(fn_name, _, loc) | Expr::External(fn_name, _, _, _, loc)
+                if fn_name == name => Some(loc.to_owned()),
+            Expr::Enumeration(_, variants, _) => variants.iter()
+                .find(|variant| &variant.name == name)
+                .map(|variant| variant.loc.to_owned()),
+            _ => None,
+        })
+    }
+
+    pub fn completions(&self, offset: usize) -> Vec<CompletionItem> {
+        let line_start = self.text[..offset].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+        if in_type_position(&self.text[line_start..offset]) {
+            let structures = self.functions().filter_map(|item| match item {
+                Expr::Structure(name, _, _) | Expr::Enumeration(name, _, _) => Some(name.as_str()),
+                _ => None,
+            });
+            return TYPE_NAMES.iter().copied().chain(structures).map(|name| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::CLASS),
+                ..Default::default()
+            }).collect();
+        }
+
+        let mut items = SYMBOLS.iter().map(|(name, _)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: self.function_signature(name),
+            ..Default::default()
+        }).collect::<Vec<CompletionItem>>();
+        for function in self.functions() {
+            let (name, kind) = match function {
+                Expr::Function(name, _, _, _, _) | Expr::External(name, _, _, _, _) => (name, CompletionItemKind::FUNCTION),
+                Expr::Structure(name, _, _) => (name, CompletionItemKind::STRUCT),
+                Expr::Enumeration(_, variants, _) => {
+                    items.extend(variants.iter().map(|variant| CompletionItem {
+                        label: variant.name.to_owned(),
+                        kind: Some(CompletionItemKind::ENUM_MEMBER),
+                        detail: self.function_signature(&variant.name),
+                        ..Default::default()
+                    }));
+                    continue;
+                }
+                _ => continue,
+            };
+            items.push(CompletionItem {
+                label: name.to_owned(),
+                kind: Some(kind),
+                detail: self.function_signature(name),
+                ..Default::default()
+            });
+        }
+        items
+    }
+
+    fn functions(&self) -> impl Iterator<Item = &Expr> {
+        self.functions.iter()
+    }
+
+    fn node_at(&self, offset: usize) -> Option<&Expr> {
+        self.functions.iter().find_map(|function| node_at(function, offset))
+    }
+
+    fn enclosing_function(&self, offset: usize) -> Option<&String> {
+        self.functions().find_map(|function| match function {
+            Expr::Function(name, _, _, body, loc) | Expr::Test(name, body, loc) => {
+                let body = body.expression_range();
+                if loc.contains(&offset) || (body.start..=body.end).contains(&offset) {
+                    Some(name)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// Renders the signature of a function, structure or enum the way it
+    /// would be written in its comment header. Builtins and enum variants have
+    /// no header of their own, so they get a short form.
+    fn function_signature(&self, name: &str) -> Option<String> {
+        let declared = self.functions().find(|function| match function {
+            Expr::Function(fn_name, _, _, _, _) | Expr::Test(fn_name, _, _) | Expr::Structure(fn_name, _, _)
+            | Expr::Enumeration(fn_name, _, _) | Expr::External(fn_name, _, _, _, _) => fn_name == name,
+            _ => false,
+        });
+        let mut lines = vec!["```gold".to_owned()];
+        let params_lines = |params: &Vec<(Parameter, Range<usize>)>| params.iter().map(|(param, _)| {
+            ["// '", param.name.as_str(), "' is of type ", param.typename.to_string().as_str(), "."].join("")
+        }).collect::<Vec<String>>();
+        match declared {
+            Some(Expr::Function(_, params, ret, _, _)) => {
+                lines.push(["// ", name, " is a function."].join(""));
+                lines.push("// Params:".to_owned());
+                lines.extend(params_lines(params));
+                lines.push(["// Returns: ", ret.to_string().as_str()].join(""));
+            }
+            Some(Expr::Test(_, _, _)) => lines.push(["// ", name, " is a test."].join("")),
+            Some(Expr::External(_, params, ret, library, _)) => {
+                lines.push(["// ", name, " is an external function from ", library.as_str(), "."].join(""));
+                lines.push("// Params:".to_owned());
+                lines.extend(params_lines(params));
+                lines.push(["// Returns: ", ret.to_string().as_str()].join(""));
+            }
+            Some(Expr::Structure(_, fields, _)) => {
+                lines.push(["// ", name, " is a structure."].join(""));
+                lines.push("// Fields:".to_owned());
+                lines.extend(params_lines(fields));
+            }
+            Some(Expr::Enumeration(_, variants, _)) => {
+                lines.push(["// ", name, " is one of:"].join(""));
+                for variant in variants {
+                    let fields = variant.fields.iter().map(|(field, _)| {
+                        ["'", field.name.as_str(), "' of type ", field.typename.to_string().as_str()].join("")
+                    }).collect::<Vec<String>>();
+                    if fields.is_empty() {
+                        lines.push(["// '", variant.name.as_str(), "'."].join(""));
+                    } else {
+                        lines.push(["// '", variant.name.as_str(), "' with ", fields.join(" and ").as_str(), "."].join(""));
+                    }
+                }
+            }
+            _ => {
+                let sig = self.analyzer.functions.get(name)?;
+                let params = sig.param_types.iter()
+                    .map(|(ty, _)| ty.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                lines.push([name, "(", params.as_str(), ") -> ", sig.return_type.to_string().as_str()].join(""));
+            }
+        }
+        lines.push("```".to_owned());
+        Some(lines.join("\n"))
+    }
+}
+
+/// Whether the text before the cursor is the type slot of a parameter line,
+/// i.e. `// 'x' is of type ` followed by a partially typed name.
+fn in_type_position(line: &str) -> bool {
+    let line = line.trim_start();
+    let rest = match line.strip_prefix("//") {
+        Some(rest) => rest.trim_start(),
+        None => return false,
+    };
+    let rest = match rest.strip_prefix('\'') {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let rest = match rest.find('\'') {
+        Some(end) => &rest[end + 1..],
+        None => return false,
+    };
+    let words = rest.split_whitespace().collect::<Vec<&str>>();
+    match words.as_slice() {
+        ["is", "of", "type"] => rest.ends_with(char::is_whitespace),
+        ["is", "of", "type", partial] => !rest.ends_with(char::is_whitespace) && !partial.contains('.'),
+        _ => false,
+    }
+}
+
+fn children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::NoExpr | Expr::Number(_, _) | Expr::Bool(_, _) | Expr::String(_, _) | Expr::Var(_, _) | Expr::Parameter(_, _)
+        | Expr::Structure(_, _, _) | Expr::Enumeration(_, _, _) | Expr::Import(_, _) | Expr::External(_, _, _, _, _)
+        | Expr::Break(_) | Expr::Continue(_) => vec![],
+        Expr::Block(stmts, _) => stmts.iter().collect(),
+        Expr::List(values, _) | Expr::Interpolation(values, _) => values.iter().collect(),
+        Expr::Call(_, args, _, _) => args.iter().collect(),
+        Expr::Function(_, _, _, body, _) | Expr::Test(_, body, _) => vec![body.as_ref()],
+        Expr::Else(body, _) | Expr::Lambda(_, body, _) => vec![body.as_ref()],
+        Expr::Elif(cond, body, _) => vec![cond.as_ref(), body.as_ref()],
+        Expr::If(cond, body, elifs, else_body, _) => {
+            let mut nodes = vec![cond.as_ref(), body.as_ref()];
+            if let Some(elifs) = elifs {
+                nodes.extend(elifs.iter());
+            }
+            if let Some(else_body) = else_body {
+                nodes.push(else_body.as_ref());
+            }
+            nodes
+        }
+        Expr::While(cond, body, _) | Expr::For(_, _, cond, body, _) => vec![cond.as_ref(), body.as_ref()],
+        Expr::Match(value, arms, _) => {
+            let mut nodes = vec![value.as_ref()];
+            nodes.extend(arms.iter().map(|arm| &arm.body));
+            nodes
+        }
+        Expr::Assign(_, value, _) | Expr::Reassign(_, value, _) => vec![value.as_ref()],
+        Expr::Negate(expr, _) | Expr::Field(expr, _, _) => vec![expr.as_ref()],
+        Expr::ReassignField(field, value, _) => vec![field.as_ref(), value.as_ref()],
+        Expr::Equality(lhs, rhs)
+        | Expr::NotEqual(lhs, rhs)
+        | Expr::GreaterThan(lhs, rhs)
+        | Expr::LessThan(lhs, rhs)
+        | Expr::GreaterThanEqual(lhs, rhs)
+        | Expr::LessThanEqual(lhs, rhs)
+        | Expr::Addition(lhs, rhs)
+        | Expr::Subtraction(lhs, rhs)
+        | Expr::Multiplication(lhs, rhs)
+        | Expr::Division(lhs, rhs)
+        | Expr::Power(lhs, rhs)
+        | Expr::Range(lhs, rhs, _) => vec![lhs.as_ref(), rhs.as_ref()],
+    }
+}
+
+/// Finds the innermost named node (function, call or variable) whose name
+/// covers `offset`.
+fn node_at(expr: &Expr, offset: usize) -> Option<&Expr> {
+    for child in children(expr) {
+        if let Some(found) = node_at(child, offset) {
+            return Some(found);
+        }
+    }
+    match expr {
+        Expr::Function(_, _, _, _, loc) | Expr::Test(_, _, loc) | Expr::Structure(_, _, loc) | Expr::Enumeration(_, _, loc)
+        | Expr::External(_, _, _, _, loc) | Expr::Call(_, _, loc, _) | Expr::Var(_, loc)
+            if (loc.start..=loc.end).contains(&offset) => Some(expr),
+        _ => None,
+    }
+}
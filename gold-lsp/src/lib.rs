@@ -0,0 +1,159 @@
+//! A language server for Gold, which checks open documents as they change
+//! and answers hover, definition and completion requests.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionOptions, CompletionParams, CompletionResponse, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability, Location,
+    MarkupContent, MarkupKind, OneOf, PublishDiagnosticsParams, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use crate::document::Document;
+
+pub mod document;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+/// What the server can do, to be sent back when the client initializes it.
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![" ".to_owned()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Handles messages from the client until it asks the server to shut down.
+pub fn main_loop(connection: Connection) -> Result<()> {
+    let mut documents: HashMap<Url, Document> = HashMap::new();
+
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                let response = handle_request(&documents, req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => {
+                if let Some(uri) = handle_notification(&mut documents, not)? {
+                    publish_diagnostics(&connection, &documents, uri)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn open(documents: &mut HashMap<Url, Document>, uri: Url, text: String) -> Url {
+    // Modules are found relative to the document, so it needs its full path
+    let path = match uri.to_file_path() {
+        Ok(path) => path.display().to_string(),
+        Err(_) => uri.path().to_owned(),
+    };
+    documents.insert(uri.clone(), Document::new(&path, text));
+    uri
+}
+
+/// Updates the open documents, returning the document whose diagnostics
+/// need to be republished.
+fn handle_notification(documents: &mut HashMap<Url, Document>, not: Notification) -> Result<Option<Url>> {
+    match not.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+            Ok(Some(open(documents, params.text_document.uri, params.text_document.text)))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+            // We only ask for full syncs, so the last change holds the whole text.
+            match params.content_changes.into_iter().last() {
+                Some(change) => Ok(Some(open(documents, params.text_document.uri, change.text))),
+                None => Ok(None),
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+            documents.remove(&params.text_document.uri);
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, documents: &HashMap<Url, Document>, uri: Url) -> Result<()> {
+    let diagnostics = match documents.get(&uri) {
+        Some(document) => document.lsp_diagnostics(),
+        None => return Ok(()),
+    };
+    let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+    let not = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+    connection.sender.send(Message::Notification(not))?;
+    Ok(())
+}
+
+fn handle_request(documents: &HashMap<Url, Document>, req: Request) -> Response {
+    let id = req.id.clone();
+    let result = match req.method.as_str() {
+        HoverRequest::METHOD => {
+            serde_json::from_value(req.params).map(|params| serde_json::to_value(hover(documents, params)))
+        }
+        GotoDefinition::METHOD => {
+            serde_json::from_value(req.params).map(|params| serde_json::to_value(definition(documents, params)))
+        }
+        Completion::METHOD => {
+            serde_json::from_value(req.params).map(|params| serde_json::to_value(completion(documents, params)))
+        }
+        _ => return method_not_found(id, &req.method),
+    };
+    match result {
+        Ok(Ok(value)) => Response::new_ok(id, value),
+        Ok(Err(e)) | Err(e) => Response::new_err(id, lsp_server::ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+fn method_not_found(id: RequestId, method: &str) -> Response {
+    Response::new_err(id, lsp_server::ErrorCode::MethodNotFound as i32, ["Unhandled method ", method].join(""))
+}
+
+fn hover(documents: &HashMap<Url, Document>, params: HoverParams) -> Option<Hover> {
+    let position = params.text_document_position_params;
+    let document = documents.get(&position.text_document.uri)?;
+    let offset = document.lines.offset(&document.text, position.position);
+    let (value, range) = document.hover(offset)?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+        range: Some(document.lines.range(&document.text, range)),
+    })
+}
+
+fn definition(documents: &HashMap<Url, Document>, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+    let position = params.text_document_position_params;
+    let document = documents.get(&position.text_document.uri)?;
+    let offset = document.lines.offset(&document.text, position.position);
+    let range = document.definition(offset)?;
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        position.text_document.uri,
+        document.lines.range(&document.text, range),
+    )))
+}
+
+fn completion(documents: &HashMap<Url, Document>, params: CompletionParams) -> Option<CompletionResponse> {
+    let position = params.text_document_position;
+    let document = documents.get(&position.text_document.uri)?;
+    let offset = document.lines.offset(&document.text, position.position);
+    Some(CompletionResponse::Array(document.completions(offset)))
+}
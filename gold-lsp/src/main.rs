@@ -0,0 +1,12 @@
+use lsp_server::Connection;
+
+use gold_lsp::{capabilities, main_loop, Result};
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    main_loop(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
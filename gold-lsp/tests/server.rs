@@ -0,0 +1,157 @@
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{DidOpenTextDocument, Exit, Notification as _, PublishDiagnostics};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _, Shutdown};
+use lsp_types::{
+    CompletionResponse, DidOpenTextDocumentParams, GotoDefinitionResponse, Hover, HoverContents,
+    Position, PublishDiagnosticsParams, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url,
+};
+use serde_json::{json, Value};
+
+use gold_lsp::main_loop;
+
+const SOURCE: &str = "// double is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  n * 2
+}
+
+// main is a function.
+// Params:
+// Returns: Void
+fn {
+  var count = double(2);
+  var name = count + \"s\";
+  println(\"{count}\");
+}
+";
+
+/// A client talking to a server running on another thread, the way an editor
+/// would over stdio.
+struct Client {
+    connection: Connection,
+    server: Option<thread::JoinHandle<()>>,
+    next_id: i32,
+    uri: Url,
+}
+
+impl Client {
+    fn open(text: &str) -> Self {
+        let (client, server) = Connection::memory();
+        let server = thread::spawn(move || main_loop(server).unwrap());
+        let uri = Url::parse("file:///tmp/gold-lsp-test/main.gold").unwrap();
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "gold".to_owned(), 1, text.to_owned()),
+        };
+        let not = Notification::new(DidOpenTextDocument::METHOD.to_owned(), params);
+        client.sender.send(Message::Notification(not)).unwrap();
+        Self { connection: client, server: Some(server), next_id: 0, uri }
+    }
+
+    fn notification(&self) -> Notification {
+        match self.connection.receiver.recv().unwrap() {
+            Message::Notification(not) => not,
+            other => panic!("expected a notification, got {:?}", other),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let req = Request::new(id.clone(), method.to_owned(), params);
+        self.connection.sender.send(Message::Request(req)).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(response) if response.id == id => response,
+            other => panic!("expected a response to {}, got {:?}", method, other),
+        }
+    }
+
+    /// Asks about the position of the first `needle` in the source, `after`
+    /// characters into it.
+    fn at(&mut self, method: &str, needle: &str, after: u32) -> Value {
+        let (line, text) = SOURCE.lines().enumerate().find(|(_, text)| text.contains(needle)).unwrap();
+        let character = text.find(needle).unwrap() as u32 + after;
+        let params = TextDocumentPositionParams::new(TextDocumentIdentifier::new(self.uri.clone()), Position::new(line as u32, character));
+        let response = self.request(method, serde_json::to_value(params).unwrap());
+        assert!(response.error.is_none(), "{:?}", response.error);
+        response.result.unwrap()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.request(Shutdown::METHOD, Value::Null);
+        let exit = Notification::new(Exit::METHOD.to_owned(), Value::Null);
+        self.connection.sender.send(Message::Notification(exit)).unwrap();
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+#[test]
+fn publishes_diagnostics_when_a_document_opens() {
+    let client = Client::open(SOURCE);
+    let not = client.notification();
+    assert_eq!(not.method, PublishDiagnostics::METHOD);
+    let params: PublishDiagnosticsParams = serde_json::from_value(not.params).unwrap();
+    assert_eq!(params.diagnostics.len(), 1);
+    let diagnostic = &params.diagnostics[0];
+    assert!(diagnostic.message.contains("Int"), "{}", diagnostic.message);
+    assert_eq!(diagnostic.range.start.line, 13);
+}
+
+#[test]
+fn hovers_over_calls_and_variables() {
+    let mut client = Client::open(SOURCE);
+    client.notification();
+    let hover: Hover = serde_json::from_value(client.at(HoverRequest::METHOD, "double(2)", 1)).unwrap();
+    match hover.contents {
+        HoverContents::Markup(markup) => assert!(markup.value.contains("// double is a function.\n// Params:\n// 'n' is of type Int.\n// Returns: Int")),
+        other => panic!("expected markdown, got {:?}", other),
+    }
+    let hover: Hover = serde_json::from_value(client.at(HoverRequest::METHOD, "count + ", 1)).unwrap();
+    match hover.contents {
+        HoverContents::Markup(markup) => assert!(markup.value.contains("count: Int")),
+        other => panic!("expected markdown, got {:?}", other),
+    }
+}
+
+#[test]
+fn goes_to_the_definition_of_a_function() {
+    let mut client = Client::open(SOURCE);
+    client.notification();
+    let definition: GotoDefinitionResponse = serde_json::from_value(client.at(GotoDefinition::METHOD, "double(2)", 2)).unwrap();
+    match definition {
+        GotoDefinitionResponse::Scalar(location) => {
+            assert_eq!(location.uri, client.uri);
+            assert_eq!(location.range.start.line, 0);
+        }
+        other => panic!("expected one location, got {:?}", other),
+    }
+    assert_eq!(client.at(GotoDefinition::METHOD, "println", 1), Value::Null);
+}
+
+#[test]
+fn completes_functions_and_type_names() {
+    let mut client = Client::open(SOURCE);
+    client.notification();
+    let labels = |value: Value| match serde_json::from_value(value).unwrap() {
+        CompletionResponse::Array(items) => items.into_iter().map(|item| item.label).collect::<Vec<String>>(),
+        other => panic!("expected a list, got {:?}", other),
+    };
+    let names = labels(client.at(Completion::METHOD, "var count", 0));
+    assert!(names.contains(&"double".to_owned()) && names.contains(&"println".to_owned()));
+    let types = labels(client.at(Completion::METHOD, "'n' is of type ", 15));
+    assert!(types.contains(&"Int".to_owned()) && !types.contains(&"double".to_owned()));
+}
+
+#[test]
+fn rejects_unknown_requests() {
+    let mut client = Client::open(SOURCE);
+    client.notification();
+    let response = client.request("gold/unknown", json!({}));
+    assert_eq!(response.error.unwrap().code, lsp_server::ErrorCode::MethodNotFound as i32);
+}
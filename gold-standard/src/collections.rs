@@ -0,0 +1,191 @@
+//! Lists and maps, which programs hold as pointers to them. They're reference
+//! counted like the rest of the values programs allocate, and hold a reference
+//! to each of their values that is counted too.
+//!
+//! Values are kept the way structures keep their fields, in 8 bytes each. A
+//! key is an integer, a boolean or a string, and a map keeps its own copy of
+//! a string key, so only its values have to be retained.
+//!
+//! Compiled code checks that a key or index is there before getting it, so
+//! getting what isn't there gives back 0.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_char;
+
+use crate::mem::{register, release, retain};
+use crate::string::into_c_string;
+
+pub(crate) struct List {
+    items: Vec<i64>,
+    // Whether the items are values to retain and release
+    counted: bool,
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Key {
+    Int(i64),
+    String(Vec<u8>),
+}
+
+pub(crate) struct Map {
+    entries: HashMap<Key, i64>,
+    string_keys: bool,
+    counted: bool,
+}
+
+unsafe fn free_list(value: usize, released: &mut Vec<usize>) {
+    let list = Box::from_raw(value as *mut List);
+    if list.counted {
+        released.extend(list.items.iter().map(|item| *item as usize));
+    }
+}
+
+unsafe fn free_map(value: usize, released: &mut Vec<usize>) {
+    let map = Box::from_raw(value as *mut Map);
+    if map.counted {
+        released.extend(map.entries.values().map(|value| *value as usize));
+    }
+}
+
+fn new_list(items: Vec<i64>, counted: bool) -> *mut List {
+    let list = Box::into_raw(Box::new(List { items, counted }));
+    register(list as usize, mem::size_of::<List>(), free_list);
+    list
+}
+
+/// Makes an empty list. `counted` is 1 when its items are strings, structures,
+/// enums or collections.
+#[export_name = "gold_list_new"]
+pub(crate) unsafe extern "C" fn list_new(counted: i64) -> *mut List {
+    new_list(Vec::new(), counted != 0)
+}
+
+/// Adds an item to the end of a list.
+#[export_name = "gold_list_push"]
+pub(crate) unsafe extern "C" fn list_push(list: *mut List, item: i64) {
+    let list = &mut *list;
+    if list.counted {
+        retain(item as *mut _);
+    }
+    list.items.push(item);
+}
+
+/// Adds an item to the end of a list and gives the list back, which is how
+/// list literals are built.
+#[export_name = "gold_list_append"]
+pub(crate) unsafe extern "C" fn list_append(list: *mut List, item: i64) -> *mut List {
+    list_push(list, item);
+    retain(list as *mut _);
+    list
+}
+
+#[export_name = "gold_list_get"]
+pub(crate) unsafe extern "C" fn list_get(list: *mut List, index: i64) -> i64 {
+    let list = &*list;
+    let item = list.items.get(index as usize).copied().unwrap_or(0);
+    if list.counted {
+        retain(item as *mut _);
+    }
+    item
+}
+
+#[export_name = "gold_list_len"]
+pub(crate) unsafe extern "C" fn list_len(list: *mut List) -> i64 {
+    (*list).items.len() as i64
+}
+
+/// Makes an empty map. `string_keys` is 1 when its keys are strings, and
+/// `counted` is 1 when its values are strings, structures, enums or
+/// collections.
+#[export_name = "gold_map_new"]
+pub(crate) unsafe extern "C" fn map_new(string_keys: i64, counted: i64) -> *mut Map {
+    let map = Map { entries: HashMap::new(), string_keys: string_keys != 0, counted: counted != 0 };
+    let map = Box::into_raw(Box::new(map));
+    register(map as usize, mem::size_of::<Map>(), free_map);
+    map
+}
+
+unsafe fn key(map: &Map, key: i64) -> Key {
+    if map.string_keys {
+        Key::String(CStr::from_ptr(key as *const c_char).to_bytes().to_vec())
+    } else {
+        Key::Int(key)
+    }
+}
+
+/// Sets the value of a key, replacing the value it had.
+#[export_name = "gold_map_insert"]
+pub(crate) unsafe extern "C" fn map_insert(map: *mut Map, key_value: i64, value: i64) {
+    let map = &mut *map;
+    if map.counted {
+        retain(value as *mut _);
+    }
+    let replaced = map.entries.insert(key(map, key_value), value);
+    if let Some(replaced) = replaced.filter(|_| map.counted) {
+        release(replaced as *mut _);
+    }
+}
+
+#[export_name = "gold_map_get"]
+pub(crate) unsafe extern "C" fn map_get(map: *mut Map, key_value: i64) -> i64 {
+    let map = &*map;
+    let value = map.entries.get(&key(map, key_value)).copied().unwrap_or(0);
+    if map.counted {
+        retain(value as *mut _);
+    }
+    value
+}
+
+#[export_name = "gold_map_contains"]
+pub(crate) unsafe extern "C" fn map_contains(map: *mut Map, key_value: i64) -> i64 {
+    let map = &*map;
+    map.entries.contains_key(&key(map, key_value)) as i64
+}
+
+/// Removes a key along with its value, giving back 1 when it was there and 0
+/// when it wasn't.
+#[export_name = "gold_map_remove"]
+pub(crate) unsafe extern "C" fn map_remove(map: *mut Map, key_value: i64) -> i64 {
+    let map = &mut *map;
+    match map.entries.remove(&key(map, key_value)) {
+        Some(value) => {
+            if map.counted {
+                release(value as *mut _);
+            }
+            1
+        }
+        None => 0,
+    }
+}
+
+#[export_name = "gold_map_len"]
+pub(crate) unsafe extern "C" fn map_len(map: *mut Map) -> i64 {
+    (*map).entries.len() as i64
+}
+
+/// The keys of a map as a list, in order so a program does the same thing
+/// every time it runs. Strings are ordered byte by byte.
+#[export_name = "gold_map_keys"]
+pub(crate) unsafe extern "C" fn map_keys(map: *mut Map) -> *mut List {
+    let map = &*map;
+    let mut keys = map.entries.keys().collect::<Vec<&Key>>();
+    keys.sort();
+    let items = keys.into_iter().map(|key| match key {
+        Key::Int(n) => *n,
+        Key::String(bytes) => into_c_string(bytes) as i64,
+    }).collect();
+    new_list(items, map.string_keys)
+}
+
+/// The characters of a string as a list of strings, which is what a `for`
+/// loop over a string goes through.
+#[export_name = "gold_string_chars"]
+pub(crate) unsafe extern "C" fn string_chars(string: *mut c_char) -> *mut List {
+    let string = CStr::from_ptr(string).to_string_lossy();
+    let items = string.chars()
+        .map(|c| into_c_string(c.encode_utf8(&mut [0; 4]).as_bytes()) as i64)
+        .collect();
+    new_list(items, true)
+}
@@ -0,0 +1,53 @@
+//! Ends a program compiled ahead of time the same way `gold run` does when it
+//! crashes.
+//!
+//! Those programs don't have the source map the JIT reports crashes with, so
+//! all the handler can say is what kind of crash it was.
+
+use std::mem;
+use std::os::raw::{c_int, c_void};
+
+/// The exit code of a program that crashed.
+pub const CRASH_EXIT_CODE: i32 = 70;
+
+const SIGNALS: [c_int; 4] = [libc::SIGSEGV, libc::SIGBUS, libc::SIGFPE, libc::SIGILL];
+
+// The handler runs on a stack of its own, so a stack overflow can be handled
+// too
+const SIGNAL_STACK_SIZE: usize = 1 << 16;
+
+/// Called by the `main` of a program compiled ahead of time, before its entry
+/// point.
+#[export_name = "gold_catch_crashes"]
+pub extern "C" fn catch_crashes() {
+    // It's needed until the process exits
+    let stack = Box::leak(vec![0u8; SIGNAL_STACK_SIZE].into_boxed_slice());
+    let signal_stack = libc::stack_t {
+        ss_sp: stack.as_mut_ptr() as *mut c_void,
+        ss_flags: 0,
+        ss_size: stack.len(),
+    };
+    unsafe { libc::sigaltstack(&signal_stack, std::ptr::null_mut()) };
+    for signal in &SIGNALS {
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = handle as extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        unsafe { libc::sigaction(*signal, &action, std::ptr::null_mut()) };
+    }
+}
+
+extern "C" fn handle(signal: c_int, _: *mut libc::siginfo_t, _: *mut c_void) {
+    // Traps are illegal instructions
+    let message: &[u8] = match signal {
+        libc::SIGFPE => b"error: arithmetic error\n",
+        libc::SIGILL => b"error: the program hit a trap\n",
+        _ => b"error: invalid memory access\n",
+    };
+    // What the program printed should come before the error
+    use std::io::Write;
+    std::io::stdout().flush().ok();
+    unsafe {
+        libc::write(libc::STDERR_FILENO, message.as_ptr() as *const c_void, message.len());
+        libc::_exit(CRASH_EXIT_CODE);
+    }
+}
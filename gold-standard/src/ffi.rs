@@ -0,0 +1,55 @@
+use std::ffi::{CStr, CString};
+use std::os::raw;
+
+use crate::string::into_c_string;
+
+#[cfg(target_os = "macos")]
+const LIBRARY_EXTENSION: &str = "dylib";
+#[cfg(not(target_os = "macos"))]
+const LIBRARY_EXTENSION: &str = "so";
+
+/// Finds a function in a C library for an external declaration. Anything
+/// already linked into the process, like libc, is found without loading the
+/// library. Otherwise `from m` and `from libm` both load `libm.so`, while a
+/// name with a dot in it such as `libm.so.6` is loaded as it's written.
+pub fn resolve(library: &str, symbol: &str) -> Option<*const u8> {
+    let symbol = CString::new(symbol).ok()?;
+    unsafe {
+        let address = libc::dlsym(libc::RTLD_DEFAULT, symbol.as_ptr());
+        if !address.is_null() {
+            return Some(address as *const u8);
+        }
+
+        let handle = libc::dlopen(library_file(library)?.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+        if handle.is_null() {
+            return None;
+        }
+        let address = libc::dlsym(handle, symbol.as_ptr());
+        if address.is_null() {
+            None
+        } else {
+            Some(address as *const u8)
+        }
+    }
+}
+
+fn library_file(library: &str) -> Option<CString> {
+    let file = if library.contains('.') {
+        library.to_owned()
+    } else if library.starts_with("lib") {
+        [library, ".", LIBRARY_EXTENSION].join("")
+    } else {
+        ["lib", library, ".", LIBRARY_EXTENSION].join("")
+    };
+    CString::new(file).ok()
+}
+
+/// Copies a string returned by a C library into one owned by Gold, since the
+/// library may free or reuse it. A null pointer becomes the empty string.
+#[export_name = "gold_string_from_c"]
+pub(crate) unsafe extern "C" fn string_from_c(string: *const raw::c_char) -> *mut raw::c_char {
+    if string.is_null() {
+        return into_c_string(b"");
+    }
+    into_c_string(CStr::from_ptr(string).to_bytes())
+}
@@ -1,6 +1,38 @@
 use std::ffi;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Read, Write};
 use std::os::raw;
+use std::sync::Mutex;
 
+use crate::string::into_c_string;
+
+// What went wrong in the last call that reads or writes, or nothing when it
+// went fine
+static ERROR: Mutex<String> = Mutex::new(String::new());
+
+// Files are handed to programs as their index in here, and closing one leaves
+// its place empty
+static FILES: Mutex<Vec<Option<File>>> = Mutex::new(Vec::new());
+
+/// Remembers how a call went for `io_error`, giving back its value or
+/// `failed` when it didn't work out.
+fn report<T>(result: Result<T, String>, failed: T) -> T {
+    let mut error = ERROR.lock().unwrap();
+    error.clear();
+    match result {
+        Ok(value) => value,
+        Err(message) => {
+            error.push_str(&message);
+            failed
+        }
+    }
+}
+
+unsafe fn string(s: *const raw::c_char) -> String {
+    ffi::CStr::from_ptr(s).to_string_lossy().into_owned()
+}
+
+#[export_name = "gold_print"]
 pub unsafe extern "C" fn print(s: *mut raw::c_char) -> i64 {
     let c_string = ffi::CStr::from_ptr(s);
 
@@ -12,14 +44,39 @@ pub unsafe extern "C" fn print(s: *mut raw::c_char) -> i64 {
     }
 }
 
+#[export_name = "gold_print_int"]
 pub unsafe extern "C" fn print_int(i: i64) {
     print!("{}", i);
 }
 
+#[export_name = "gold_print_float"]
 pub unsafe extern "C" fn print_float(i: f64) {
     print!("{}", i);
 }
 
+/// Prints what the entry point of a program compiled ahead of time gave back,
+/// the way `gold run` does. Bools are given as integers.
+#[export_name = "gold_print_result_int"]
+pub unsafe extern "C" fn print_result_int(i: i64) {
+    println!("{}", i);
+}
+
+/// Floats come as 64 bits like they do to every runtime function, but are
+/// printed as the 32 bit floats Gold has.
+#[export_name = "gold_print_result_float"]
+pub unsafe extern "C" fn print_result_float(f: f64) {
+    println!("{}", f as f32);
+}
+
+/// Writes out what was printed before a program compiled ahead of time
+/// returns from `main`, which Rust only does on its own when its `main` is the
+/// one returning.
+#[export_name = "gold_flush"]
+pub unsafe extern "C" fn flush() {
+    io::stdout().flush().ok();
+}
+
+#[export_name = "gold_println"]
 pub unsafe extern "C" fn println(s: *mut raw::c_char) -> i64 {
     let c_string = ffi::CStr::from_ptr(s);
 
@@ -29,4 +86,115 @@ pub unsafe extern "C" fn println(s: *mut raw::c_char) -> i64 {
     } else {
         1
     }
-}
\ No newline at end of file
+}
+
+#[export_name = "gold_eprint"]
+pub unsafe extern "C" fn eprint(s: *mut raw::c_char) -> i64 {
+    // What was printed before should come first
+    io::stdout().flush().ok();
+    let written = write!(io::stderr(), "{}", string(s));
+    report(written.map(|_| 0).map_err(|e| e.to_string()), 1)
+}
+
+#[export_name = "gold_eprintln"]
+pub unsafe extern "C" fn eprintln(s: *mut raw::c_char) -> i64 {
+    io::stdout().flush().ok();
+    let written = writeln!(io::stderr(), "{}", string(s));
+    report(written.map(|_| 0).map_err(|e| e.to_string()), 1)
+}
+
+/// A line of standard input without its line ending.
+fn line() -> Result<String, String> {
+    // A prompt is usually printed without a newline
+    io::stdout().flush().ok();
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Err("reached the end of the input".to_owned()),
+        Ok(_) => {
+            let end = line.trim_end_matches(&['\n', '\r'][..]).len();
+            line.truncate(end);
+            Ok(line)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Reads a line from standard input, or gives back the empty string when
+/// there is none.
+#[export_name = "gold_read_line"]
+pub unsafe extern "C" fn read_line() -> *mut raw::c_char {
+    into_c_string(report(line(), String::new()).as_bytes())
+}
+
+/// Reads a line from standard input holding a whole number, or gives back 0
+/// when it doesn't.
+#[export_name = "gold_read_int"]
+pub unsafe extern "C" fn read_int() -> i64 {
+    let number = line().and_then(|line| {
+        line.trim().parse().map_err(|_| format!("expected a whole number, got `{}`", line))
+    });
+    report(number, 0)
+}
+
+/// Opens a file to read it with the mode `r`, to replace what's in it with
+/// `w` or to add to the end of it with `a`. Gives back the file to read or
+/// write with, or -1 when it can't be opened.
+#[export_name = "gold_open_file"]
+pub unsafe extern "C" fn open_file(path: *const raw::c_char, mode: *const raw::c_char) -> i64 {
+    let path = string(path);
+    let mut options = OpenOptions::new();
+    let opened = match string(mode).as_str() {
+        "r" => Ok(options.read(true)),
+        "w" => Ok(options.write(true).create(true).truncate(true)),
+        "a" => Ok(options.append(true).create(true)),
+        mode => Err(format!("`{}` isn't a mode files can be opened with, which are `r`, `w` and `a`", mode)),
+    }.and_then(|options| options.open(&path).map_err(|e| format!("can't open {}: {}", path, e)));
+    let file = opened.map(|file| {
+        let mut files = FILES.lock().unwrap();
+        files.push(Some(file));
+        files.len() as i64 - 1
+    });
+    report(file, -1)
+}
+
+fn with_file<T>(file: i64, action: impl FnOnce(&mut File) -> io::Result<T>) -> Result<T, String> {
+    let mut files = FILES.lock().unwrap();
+    match files.get_mut(file as usize).and_then(Option::as_mut) {
+        Some(opened) => action(opened).map_err(|e| e.to_string()),
+        _ => Err(format!("{} isn't an open file", file)),
+    }
+}
+
+/// Reads the rest of a file, or gives back the empty string when it can't.
+#[export_name = "gold_read_file"]
+pub unsafe extern "C" fn read_file(file: i64) -> *mut raw::c_char {
+    let text = with_file(file, |file| {
+        let mut text = String::new();
+        file.read_to_string(&mut text).map(|_| text)
+    });
+    into_c_string(report(text, String::new()).as_bytes())
+}
+
+/// Writes to a file, giving back 0 when it's written and 1 when it isn't.
+#[export_name = "gold_write_file"]
+pub unsafe extern "C" fn write_file(file: i64, text: *const raw::c_char) -> i64 {
+    let text = ffi::CStr::from_ptr(text).to_bytes();
+    report(with_file(file, |file| file.write_all(text)).map(|_| 0), 1)
+}
+
+/// Closes a file, giving back 0 when it was open and 1 when it wasn't.
+#[export_name = "gold_close_file"]
+pub unsafe extern "C" fn close_file(file: i64) -> i64 {
+    let closed = with_file(file, |file| file.flush()).map(|_| {
+        FILES.lock().unwrap()[file as usize] = None;
+        0
+    });
+    report(closed, 1)
+}
+
+/// What went wrong in the last call that reads or writes, or the empty
+/// string when it worked.
+#[export_name = "gold_io_error"]
+pub unsafe extern "C" fn io_error() -> *mut raw::c_char {
+    into_c_string(ERROR.lock().unwrap().as_bytes())
+}
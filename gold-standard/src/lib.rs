@@ -2,10 +2,18 @@ pub use load::{load_symbols, declare_functions};
 
 pub mod load;
 
+pub mod crash;
+
+pub mod ffi;
+
+pub mod test;
+
 mod io;
 
-mod mem;
+pub mod mem;
 
 mod string;
 
-mod math;
\ No newline at end of file
+mod math;
+
+mod collections;
\ No newline at end of file
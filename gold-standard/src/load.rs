@@ -1,56 +1,449 @@
 use std::collections::HashMap;
-use std::mem::transmute;
 
 use cranelift::prelude::*;
-use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_jit::JITBuilder;
 use cranelift_module::{FuncId, Linkage, Module};
 use target_lexicon::Triple;
 
-use crate::io::{print, println, print_int, print_float};
-use crate::math::ipowi;
-use crate::mem::{free, malloc};
+use crate::collections::{list_append, list_get, list_len, list_new, list_push, map_contains, map_get, map_insert, map_keys, map_len, map_new, map_remove, string_chars};
+use crate::crash::catch_crashes;
+use crate::ffi::string_from_c;
+use crate::io::{close_file, eprint, eprintln, flush, io_error, open_file, print, println, print_int, print_float, print_result_float, print_result_int, read_file, read_int, read_line, write_file};
+use crate::math::{abs_float, abs_int, acos, asin, atan, atan2, ceil, clamp_float, clamp_int, cos, exp, floor, gcd, ipowi, ln, log10, log2, max_float, max_int, min_float, min_int, powf, random_float, random_int, round, seed_random, sin, sqrt, tan, to_float, to_int};
+use crate::mem::{alloc, alloc_wide, release, retain};
+use crate::string::{string_compare, string_concat, string_from_bool, string_from_float, string_from_float_digits, string_from_int};
+use crate::test::{assert, assert_eq_bool, assert_eq_float, assert_eq_int, assert_eq_string};
 
 pub const PRINT_INT_SYMBOL: &str = "print_int";
 pub const PRINT_INT_ADDRESS: *const u8 =
-    unsafe { transmute(print_int as unsafe extern "C" fn(_)) };
+    print_int as unsafe extern "C" fn(_) as *const u8;
 
 pub const PRINT_FLOAT_SYMBOL: &str = "print_float";
 pub const PRINT_FLOAT_ADDRESS: *const u8 =
-    unsafe { transmute(print_float as unsafe extern "C" fn(_)) };
+    print_float as unsafe extern "C" fn(_) as *const u8;
+
+/// Called by the `main` of a program compiled ahead of time, around its entry
+/// point.
+pub const PRINT_RESULT_INT_SYMBOL: &str = "print_result_int";
+pub const PRINT_RESULT_INT_ADDRESS: *const u8 =
+    print_result_int as unsafe extern "C" fn(_) as *const u8;
+
+pub const PRINT_RESULT_FLOAT_SYMBOL: &str = "print_result_float";
+pub const PRINT_RESULT_FLOAT_ADDRESS: *const u8 =
+    print_result_float as unsafe extern "C" fn(_) as *const u8;
+
+pub const CATCH_CRASHES_SYMBOL: &str = "catch_crashes";
+pub const CATCH_CRASHES_ADDRESS: *const u8 =
+    catch_crashes as extern "C" fn() as *const u8;
+
+pub const FLUSH_SYMBOL: &str = "flush";
+pub const FLUSH_ADDRESS: *const u8 =
+    flush as unsafe extern "C" fn() as *const u8;
 
 pub const PRINT_SYMBOL: &str = "print";
 pub const PRINT_ADDRESS: *const u8 =
-    unsafe { transmute(print as unsafe extern "C" fn(_) -> _) };
+    print as unsafe extern "C" fn(_) -> _ as *const u8;
 
 pub const PRINTLN_SYMBOL: &str = "println";
 pub const PRINTLN_ADDRESS: *const u8 =
-    unsafe { transmute(println as unsafe extern "C" fn(_) -> _) };
+    println as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const EPRINT_SYMBOL: &str = "eprint";
+pub const EPRINT_ADDRESS: *const u8 =
+    eprint as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const EPRINTLN_SYMBOL: &str = "eprintln";
+pub const EPRINTLN_ADDRESS: *const u8 =
+    eprintln as unsafe extern "C" fn(_) -> _ as *const u8;
+
+/// Called as `printf(format, values...)`, which is checked against the
+/// format and compiled to a `print` of the formatted string.
+pub const PRINTF_SYMBOL: &str = "printf";
+
+pub const READ_LINE_SYMBOL: &str = "read_line";
+pub const READ_LINE_ADDRESS: *const u8 =
+    read_line as unsafe extern "C" fn() -> _ as *const u8;
+
+pub const READ_INT_SYMBOL: &str = "read_int";
+pub const READ_INT_ADDRESS: *const u8 =
+    read_int as unsafe extern "C" fn() -> _ as *const u8;
+
+pub const OPEN_FILE_SYMBOL: &str = "open_file";
+pub const OPEN_FILE_ADDRESS: *const u8 =
+    open_file as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const READ_FILE_SYMBOL: &str = "read_file";
+pub const READ_FILE_ADDRESS: *const u8 =
+    read_file as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const WRITE_FILE_SYMBOL: &str = "write_file";
+pub const WRITE_FILE_ADDRESS: *const u8 =
+    write_file as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const CLOSE_FILE_SYMBOL: &str = "close_file";
+pub const CLOSE_FILE_ADDRESS: *const u8 =
+    close_file as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const IO_ERROR_SYMBOL: &str = "io_error";
+pub const IO_ERROR_ADDRESS: *const u8 =
+    io_error as unsafe extern "C" fn() -> _ as *const u8;
+
+pub const ALLOC_SYMBOL: &str = "alloc";
+pub const ALLOC_ADDRESS: *const u8 =
+    alloc as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const ALLOC_WIDE_SYMBOL: &str = "alloc_wide";
+pub const ALLOC_WIDE_ADDRESS: *const u8 =
+    alloc_wide as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const RETAIN_SYMBOL: &str = "retain";
+pub const RETAIN_ADDRESS: *const u8 =
+    retain as unsafe extern "C" fn(_) as *const u8;
+
+pub const RELEASE_SYMBOL: &str = "release";
+pub const RELEASE_ADDRESS: *const u8 =
+    release as unsafe extern "C" fn(_) as *const u8;
+
+/// Lists and maps are made with `List(Int)` and `Map(String, Int)`, and used
+/// through `insert`, `get`, `contains`, `remove`, `len`, `keys` and `push`,
+/// which are compiled to the `list_` or `map_` function for the collection.
+pub const LIST_SYMBOL: &str = "List";
+pub const MAP_SYMBOL: &str = "Map";
+pub const INSERT_SYMBOL: &str = "insert";
+pub const GET_SYMBOL: &str = "get";
+pub const CONTAINS_SYMBOL: &str = "contains";
+pub const REMOVE_SYMBOL: &str = "remove";
+pub const LEN_SYMBOL: &str = "len";
+pub const KEYS_SYMBOL: &str = "keys";
+pub const PUSH_SYMBOL: &str = "push";
+
+/// `map(list, f)`, `filter(list, f)` and `fold(list, initial, f)` call a
+/// function on every item of a list, and are compiled to loops.
+pub const MAP_FN_SYMBOL: &str = "map";
+pub const FILTER_SYMBOL: &str = "filter";
+pub const FOLD_SYMBOL: &str = "fold";
 
-pub const MALLOC_SYMBOL: &str = "malloc";
-pub const MALLOC_ADDRESS: *const u8 =
-    unsafe { transmute(malloc as unsafe extern "C" fn(_) -> _) };
+pub const LIST_NEW_SYMBOL: &str = "list_new";
+pub const LIST_NEW_ADDRESS: *const u8 =
+    list_new as unsafe extern "C" fn(_) -> _ as *const u8;
 
-pub const FREE_SYMBOL: &str = "free";
-pub const FREE_ADDRESS: *const u8 =
-    unsafe { transmute(free as unsafe extern "C" fn(_)) };
+pub const LIST_PUSH_SYMBOL: &str = "list_push";
+pub const LIST_PUSH_ADDRESS: *const u8 =
+    list_push as unsafe extern "C" fn(_, _) as *const u8;
+
+pub const LIST_APPEND_SYMBOL: &str = "list_append";
+pub const LIST_APPEND_ADDRESS: *const u8 =
+    list_append as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const LIST_GET_SYMBOL: &str = "list_get";
+pub const LIST_GET_ADDRESS: *const u8 =
+    list_get as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const LIST_LEN_SYMBOL: &str = "list_len";
+pub const LIST_LEN_ADDRESS: *const u8 =
+    list_len as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const STRING_CHARS_SYMBOL: &str = "string_chars";
+pub const STRING_CHARS_ADDRESS: *const u8 =
+    string_chars as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const MAP_NEW_SYMBOL: &str = "map_new";
+pub const MAP_NEW_ADDRESS: *const u8 =
+    map_new as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAP_INSERT_SYMBOL: &str = "map_insert";
+pub const MAP_INSERT_ADDRESS: *const u8 =
+    map_insert as unsafe extern "C" fn(_, _, _) as *const u8;
+
+pub const MAP_GET_SYMBOL: &str = "map_get";
+pub const MAP_GET_ADDRESS: *const u8 =
+    map_get as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAP_CONTAINS_SYMBOL: &str = "map_contains";
+pub const MAP_CONTAINS_ADDRESS: *const u8 =
+    map_contains as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAP_REMOVE_SYMBOL: &str = "map_remove";
+pub const MAP_REMOVE_ADDRESS: *const u8 =
+    map_remove as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAP_LEN_SYMBOL: &str = "map_len";
+pub const MAP_LEN_ADDRESS: *const u8 =
+    map_len as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const MAP_KEYS_SYMBOL: &str = "map_keys";
+pub const MAP_KEYS_ADDRESS: *const u8 =
+    map_keys as unsafe extern "C" fn(_) -> _ as *const u8;
 
 pub const IPOWI_SYMBOL: &str = "ipowi";
 pub const IPOWI_ADDRESS: *const u8 =
-    unsafe { transmute(ipowi as unsafe extern "C" fn(_, _) -> _) };
+    ipowi as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const POWF_SYMBOL: &str = "powf";
+pub const POWF_ADDRESS: *const u8 =
+    powf as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+/// Called as `abs(x)`, `min(a, b)`, `max(a, b)` and `clamp(x, low, high)` on
+/// either integers or floats, which are compiled to the `_int` or `_float`
+/// function for the type of the values.
+pub const ABS_SYMBOL: &str = "abs";
+
+pub const ABS_INT_SYMBOL: &str = "abs_int";
+pub const ABS_INT_ADDRESS: *const u8 =
+    abs_int as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ABS_FLOAT_SYMBOL: &str = "abs_float";
+pub const ABS_FLOAT_ADDRESS: *const u8 =
+    abs_float as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const MIN_SYMBOL: &str = "min";
+
+pub const MIN_INT_SYMBOL: &str = "min_int";
+pub const MIN_INT_ADDRESS: *const u8 =
+    min_int as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MIN_FLOAT_SYMBOL: &str = "min_float";
+pub const MIN_FLOAT_ADDRESS: *const u8 =
+    min_float as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAX_SYMBOL: &str = "max";
+
+pub const MAX_INT_SYMBOL: &str = "max_int";
+pub const MAX_INT_ADDRESS: *const u8 =
+    max_int as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const MAX_FLOAT_SYMBOL: &str = "max_float";
+pub const MAX_FLOAT_ADDRESS: *const u8 =
+    max_float as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const CLAMP_SYMBOL: &str = "clamp";
+
+pub const CLAMP_INT_SYMBOL: &str = "clamp_int";
+pub const CLAMP_INT_ADDRESS: *const u8 =
+    clamp_int as unsafe extern "C" fn(_, _, _) -> _ as *const u8;
+
+pub const CLAMP_FLOAT_SYMBOL: &str = "clamp_float";
+pub const CLAMP_FLOAT_ADDRESS: *const u8 =
+    clamp_float as unsafe extern "C" fn(_, _, _) -> _ as *const u8;
+
+pub const SQRT_SYMBOL: &str = "sqrt";
+pub const SQRT_ADDRESS: *const u8 =
+    sqrt as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const FLOOR_SYMBOL: &str = "floor";
+pub const FLOOR_ADDRESS: *const u8 =
+    floor as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const CEIL_SYMBOL: &str = "ceil";
+pub const CEIL_ADDRESS: *const u8 =
+    ceil as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ROUND_SYMBOL: &str = "round";
+pub const ROUND_ADDRESS: *const u8 =
+    round as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const SIN_SYMBOL: &str = "sin";
+pub const SIN_ADDRESS: *const u8 =
+    sin as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const COS_SYMBOL: &str = "cos";
+pub const COS_ADDRESS: *const u8 =
+    cos as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const TAN_SYMBOL: &str = "tan";
+pub const TAN_ADDRESS: *const u8 =
+    tan as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ASIN_SYMBOL: &str = "asin";
+pub const ASIN_ADDRESS: *const u8 =
+    asin as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ACOS_SYMBOL: &str = "acos";
+pub const ACOS_ADDRESS: *const u8 =
+    acos as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ATAN_SYMBOL: &str = "atan";
+pub const ATAN_ADDRESS: *const u8 =
+    atan as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ATAN2_SYMBOL: &str = "atan2";
+pub const ATAN2_ADDRESS: *const u8 =
+    atan2 as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const EXP_SYMBOL: &str = "exp";
+pub const EXP_ADDRESS: *const u8 =
+    exp as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const LN_SYMBOL: &str = "ln";
+pub const LN_ADDRESS: *const u8 =
+    ln as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const LOG10_SYMBOL: &str = "log10";
+pub const LOG10_ADDRESS: *const u8 =
+    log10 as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const LOG2_SYMBOL: &str = "log2";
+pub const LOG2_ADDRESS: *const u8 =
+    log2 as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const GCD_SYMBOL: &str = "gcd";
+pub const GCD_ADDRESS: *const u8 =
+    gcd as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const TO_FLOAT_SYMBOL: &str = "to_float";
+pub const TO_FLOAT_ADDRESS: *const u8 =
+    to_float as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const TO_INT_SYMBOL: &str = "to_int";
+pub const TO_INT_ADDRESS: *const u8 =
+    to_int as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const SEED_RANDOM_SYMBOL: &str = "seed_random";
+pub const SEED_RANDOM_ADDRESS: *const u8 =
+    seed_random as unsafe extern "C" fn(_) as *const u8;
+
+pub const RANDOM_INT_SYMBOL: &str = "random_int";
+pub const RANDOM_INT_ADDRESS: *const u8 =
+    random_int as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const RANDOM_FLOAT_SYMBOL: &str = "random_float";
+pub const RANDOM_FLOAT_ADDRESS: *const u8 =
+    random_float as unsafe extern "C" fn() -> _ as *const u8;
 
 pub const STRCMP_SYMBOL: &str = "string_compare";
 pub const STRCMP_ADDRESS: *const u8 =
-    unsafe { transmute(ipowi as unsafe extern "C" fn(_, _) -> _) };
+    string_compare as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const STRING_CONCAT_SYMBOL: &str = "string_concat";
+pub const STRING_CONCAT_ADDRESS: *const u8 =
+    string_concat as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const STRING_FROM_INT_SYMBOL: &str = "string_from_int";
+pub const STRING_FROM_INT_ADDRESS: *const u8 =
+    string_from_int as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const STRING_FROM_FLOAT_SYMBOL: &str = "string_from_float";
+pub const STRING_FROM_FLOAT_ADDRESS: *const u8 =
+    string_from_float as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const STRING_FROM_FLOAT_DIGITS_SYMBOL: &str = "string_from_float_digits";
+pub const STRING_FROM_FLOAT_DIGITS_ADDRESS: *const u8 =
+    string_from_float_digits as unsafe extern "C" fn(_, _) -> _ as *const u8;
+
+pub const STRING_FROM_BOOL_SYMBOL: &str = "string_from_bool";
+pub const STRING_FROM_BOOL_ADDRESS: *const u8 =
+    string_from_bool as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const STRING_FROM_C_SYMBOL: &str = "string_from_c";
+pub const STRING_FROM_C_ADDRESS: *const u8 =
+    string_from_c as unsafe extern "C" fn(_) -> _ as *const u8;
+
+pub const ASSERT_SYMBOL: &str = "assert";
+pub const ASSERT_ADDRESS: *const u8 =
+    assert as unsafe extern "C" fn(_, _, _, _) as *const u8;
+
+/// Called by tests as `assert_eq(left, right)`, which is compiled to the
+/// `assert_eq_` function for the type of the values.
+pub const ASSERT_EQ_SYMBOL: &str = "assert_eq";
+
+pub const ASSERT_EQ_INT_SYMBOL: &str = "assert_eq_int";
+pub const ASSERT_EQ_INT_ADDRESS: *const u8 =
+    assert_eq_int as unsafe extern "C" fn(_, _, _, _, _) as *const u8;
+
+pub const ASSERT_EQ_FLOAT_SYMBOL: &str = "assert_eq_float";
+pub const ASSERT_EQ_FLOAT_ADDRESS: *const u8 =
+    assert_eq_float as unsafe extern "C" fn(_, _, _, _, _) as *const u8;
+
+pub const ASSERT_EQ_BOOL_SYMBOL: &str = "assert_eq_bool";
+pub const ASSERT_EQ_BOOL_ADDRESS: *const u8 =
+    assert_eq_bool as unsafe extern "C" fn(_, _, _, _, _) as *const u8;
+
+pub const ASSERT_EQ_STRING_SYMBOL: &str = "assert_eq_string";
+pub const ASSERT_EQ_STRING_ADDRESS: *const u8 =
+    assert_eq_string as unsafe extern "C" fn(_, _, _, _, _) as *const u8;
+
+/// The prefix of the names runtime functions are exported under, since many
+/// of them are also in libc or libm.
+pub const EXPORT_PREFIX: &str = "gold_";
 
-pub const SYMBOLS: [(&str, *const u8); 8] = [
+pub const SYMBOLS: [(&str, *const u8); 77] = [
     (PRINT_SYMBOL, PRINT_ADDRESS),
     (PRINTLN_SYMBOL, PRINTLN_ADDRESS),
-    (MALLOC_SYMBOL, MALLOC_ADDRESS),
-    (FREE_SYMBOL, FREE_ADDRESS),
+    (EPRINT_SYMBOL, EPRINT_ADDRESS),
+    (EPRINTLN_SYMBOL, EPRINTLN_ADDRESS),
+    (READ_LINE_SYMBOL, READ_LINE_ADDRESS),
+    (READ_INT_SYMBOL, READ_INT_ADDRESS),
+    (OPEN_FILE_SYMBOL, OPEN_FILE_ADDRESS),
+    (READ_FILE_SYMBOL, READ_FILE_ADDRESS),
+    (WRITE_FILE_SYMBOL, WRITE_FILE_ADDRESS),
+    (CLOSE_FILE_SYMBOL, CLOSE_FILE_ADDRESS),
+    (IO_ERROR_SYMBOL, IO_ERROR_ADDRESS),
+    (ALLOC_SYMBOL, ALLOC_ADDRESS),
+    (ALLOC_WIDE_SYMBOL, ALLOC_WIDE_ADDRESS),
+    (RETAIN_SYMBOL, RETAIN_ADDRESS),
+    (RELEASE_SYMBOL, RELEASE_ADDRESS),
+    (LIST_NEW_SYMBOL, LIST_NEW_ADDRESS),
+    (LIST_PUSH_SYMBOL, LIST_PUSH_ADDRESS),
+    (LIST_APPEND_SYMBOL, LIST_APPEND_ADDRESS),
+    (LIST_GET_SYMBOL, LIST_GET_ADDRESS),
+    (LIST_LEN_SYMBOL, LIST_LEN_ADDRESS),
+    (STRING_CHARS_SYMBOL, STRING_CHARS_ADDRESS),
+    (MAP_NEW_SYMBOL, MAP_NEW_ADDRESS),
+    (MAP_INSERT_SYMBOL, MAP_INSERT_ADDRESS),
+    (MAP_GET_SYMBOL, MAP_GET_ADDRESS),
+    (MAP_CONTAINS_SYMBOL, MAP_CONTAINS_ADDRESS),
+    (MAP_REMOVE_SYMBOL, MAP_REMOVE_ADDRESS),
+    (MAP_LEN_SYMBOL, MAP_LEN_ADDRESS),
+    (MAP_KEYS_SYMBOL, MAP_KEYS_ADDRESS),
     (IPOWI_SYMBOL, IPOWI_ADDRESS),
+    (POWF_SYMBOL, POWF_ADDRESS),
+    (ABS_INT_SYMBOL, ABS_INT_ADDRESS),
+    (ABS_FLOAT_SYMBOL, ABS_FLOAT_ADDRESS),
+    (MIN_INT_SYMBOL, MIN_INT_ADDRESS),
+    (MIN_FLOAT_SYMBOL, MIN_FLOAT_ADDRESS),
+    (MAX_INT_SYMBOL, MAX_INT_ADDRESS),
+    (MAX_FLOAT_SYMBOL, MAX_FLOAT_ADDRESS),
+    (CLAMP_INT_SYMBOL, CLAMP_INT_ADDRESS),
+    (CLAMP_FLOAT_SYMBOL, CLAMP_FLOAT_ADDRESS),
+    (SQRT_SYMBOL, SQRT_ADDRESS),
+    (FLOOR_SYMBOL, FLOOR_ADDRESS),
+    (CEIL_SYMBOL, CEIL_ADDRESS),
+    (ROUND_SYMBOL, ROUND_ADDRESS),
+    (SIN_SYMBOL, SIN_ADDRESS),
+    (COS_SYMBOL, COS_ADDRESS),
+    (TAN_SYMBOL, TAN_ADDRESS),
+    (ASIN_SYMBOL, ASIN_ADDRESS),
+    (ACOS_SYMBOL, ACOS_ADDRESS),
+    (ATAN_SYMBOL, ATAN_ADDRESS),
+    (ATAN2_SYMBOL, ATAN2_ADDRESS),
+    (EXP_SYMBOL, EXP_ADDRESS),
+    (LN_SYMBOL, LN_ADDRESS),
+    (LOG10_SYMBOL, LOG10_ADDRESS),
+    (LOG2_SYMBOL, LOG2_ADDRESS),
+    (GCD_SYMBOL, GCD_ADDRESS),
+    (TO_FLOAT_SYMBOL, TO_FLOAT_ADDRESS),
+    (TO_INT_SYMBOL, TO_INT_ADDRESS),
+    (SEED_RANDOM_SYMBOL, SEED_RANDOM_ADDRESS),
+    (RANDOM_INT_SYMBOL, RANDOM_INT_ADDRESS),
+    (RANDOM_FLOAT_SYMBOL, RANDOM_FLOAT_ADDRESS),
     (STRCMP_SYMBOL, STRCMP_ADDRESS),
     (PRINT_INT_SYMBOL, PRINT_INT_ADDRESS),
     (PRINT_FLOAT_SYMBOL, PRINT_FLOAT_ADDRESS),
+    (PRINT_RESULT_INT_SYMBOL, PRINT_RESULT_INT_ADDRESS),
+    (PRINT_RESULT_FLOAT_SYMBOL, PRINT_RESULT_FLOAT_ADDRESS),
+    (CATCH_CRASHES_SYMBOL, CATCH_CRASHES_ADDRESS),
+    (FLUSH_SYMBOL, FLUSH_ADDRESS),
+    (STRING_CONCAT_SYMBOL, STRING_CONCAT_ADDRESS),
+    (STRING_FROM_INT_SYMBOL, STRING_FROM_INT_ADDRESS),
+    (STRING_FROM_FLOAT_SYMBOL, STRING_FROM_FLOAT_ADDRESS),
+    (STRING_FROM_FLOAT_DIGITS_SYMBOL, STRING_FROM_FLOAT_DIGITS_ADDRESS),
+    (STRING_FROM_BOOL_SYMBOL, STRING_FROM_BOOL_ADDRESS),
+    (STRING_FROM_C_SYMBOL, STRING_FROM_C_ADDRESS),
+    (ASSERT_SYMBOL, ASSERT_ADDRESS),
+    (ASSERT_EQ_INT_SYMBOL, ASSERT_EQ_INT_ADDRESS),
+    (ASSERT_EQ_FLOAT_SYMBOL, ASSERT_EQ_FLOAT_ADDRESS),
+    (ASSERT_EQ_BOOL_SYMBOL, ASSERT_EQ_BOOL_ADDRESS),
+    (ASSERT_EQ_STRING_SYMBOL, ASSERT_EQ_STRING_ADDRESS),
 ];
 
 pub fn load_symbols(jit_builder: &mut JITBuilder) {
@@ -58,7 +451,7 @@ pub fn load_symbols(jit_builder: &mut JITBuilder) {
 }
 
 fn fn_declare<'a>(
-    module: &mut JITModule,
+    module: &mut dyn Module,
     ids: &mut HashMap<&'a str, FuncId>,
     name: &'a str,
     params: &[AbiParam],
@@ -79,21 +472,91 @@ fn fn_declare<'a>(
     ids.insert(name, fid);
 }
 
-pub fn declare_functions(module: &mut JITModule) -> HashMap<&'static str, FuncId> {
+pub fn declare_functions(module: &mut dyn Module) -> HashMap<&'static str, FuncId> {
     let ptr_type = AbiParam::new(Type::triple_pointer_type(&Triple::host()));
     let int_type = AbiParam::new(types::I64);
     let float_type = AbiParam::new(types::F64);
 
     let mut ids = HashMap::new();
 
-    fn_declare(module, &mut ids, PRINT_SYMBOL, &[ptr_type.clone()], Some(&int_type));
-    fn_declare(module, &mut ids, PRINTLN_SYMBOL, &[ptr_type.clone()], Some(&int_type));
-    fn_declare(module, &mut ids, MALLOC_SYMBOL, &[int_type.clone()], Some(&ptr_type));
-    fn_declare(module, &mut ids, FREE_SYMBOL, &[ptr_type.clone()], None);
-    fn_declare(module, &mut ids, IPOWI_SYMBOL, &[int_type.clone(), int_type.clone()], Some(&int_type));
-    fn_declare(module, &mut ids, STRCMP_SYMBOL, &[ptr_type.clone(), ptr_type.clone()], Some(&int_type));
-    fn_declare(module, &mut ids, PRINT_INT_SYMBOL, &[int_type.clone()], None);
-    fn_declare(module, &mut ids, PRINT_FLOAT_SYMBOL, &[float_type.clone()], None);
+    fn_declare(module, &mut ids, PRINT_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, PRINTLN_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, EPRINT_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, EPRINTLN_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, READ_LINE_SYMBOL, &[], Some(&ptr_type));
+    fn_declare(module, &mut ids, READ_INT_SYMBOL, &[], Some(&int_type));
+    fn_declare(module, &mut ids, OPEN_FILE_SYMBOL, &[ptr_type, ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, READ_FILE_SYMBOL, &[int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, WRITE_FILE_SYMBOL, &[int_type, ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, CLOSE_FILE_SYMBOL, &[int_type], Some(&int_type));
+    fn_declare(module, &mut ids, IO_ERROR_SYMBOL, &[], Some(&ptr_type));
+    fn_declare(module, &mut ids, ALLOC_SYMBOL, &[int_type, int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, ALLOC_WIDE_SYMBOL, &[int_type, ptr_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, RETAIN_SYMBOL, &[ptr_type], None);
+    fn_declare(module, &mut ids, RELEASE_SYMBOL, &[ptr_type], None);
+    fn_declare(module, &mut ids, LIST_NEW_SYMBOL, &[int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, LIST_PUSH_SYMBOL, &[ptr_type, int_type], None);
+    fn_declare(module, &mut ids, LIST_APPEND_SYMBOL, &[ptr_type, int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, LIST_GET_SYMBOL, &[ptr_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, LIST_LEN_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, STRING_CHARS_SYMBOL, &[ptr_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, MAP_NEW_SYMBOL, &[int_type, int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, MAP_INSERT_SYMBOL, &[ptr_type, int_type, int_type], None);
+    fn_declare(module, &mut ids, MAP_GET_SYMBOL, &[ptr_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, MAP_CONTAINS_SYMBOL, &[ptr_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, MAP_REMOVE_SYMBOL, &[ptr_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, MAP_LEN_SYMBOL, &[ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, MAP_KEYS_SYMBOL, &[ptr_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, IPOWI_SYMBOL, &[int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, POWF_SYMBOL, &[float_type, float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ABS_INT_SYMBOL, &[int_type], Some(&int_type));
+    fn_declare(module, &mut ids, ABS_FLOAT_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, MIN_INT_SYMBOL, &[int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, MIN_FLOAT_SYMBOL, &[float_type, float_type], Some(&float_type));
+    fn_declare(module, &mut ids, MAX_INT_SYMBOL, &[int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, MAX_FLOAT_SYMBOL, &[float_type, float_type], Some(&float_type));
+    fn_declare(module, &mut ids, CLAMP_INT_SYMBOL, &[int_type, int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, CLAMP_FLOAT_SYMBOL, &[float_type, float_type, float_type], Some(&float_type));
+    fn_declare(module, &mut ids, SQRT_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, FLOOR_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, CEIL_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ROUND_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, SIN_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, COS_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, TAN_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ASIN_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ACOS_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ATAN_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, ATAN2_SYMBOL, &[float_type, float_type], Some(&float_type));
+    fn_declare(module, &mut ids, EXP_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, LN_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, LOG10_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, LOG2_SYMBOL, &[float_type], Some(&float_type));
+    fn_declare(module, &mut ids, GCD_SYMBOL, &[int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, TO_FLOAT_SYMBOL, &[int_type], Some(&float_type));
+    fn_declare(module, &mut ids, TO_INT_SYMBOL, &[float_type], Some(&int_type));
+    fn_declare(module, &mut ids, SEED_RANDOM_SYMBOL, &[int_type], None);
+    fn_declare(module, &mut ids, RANDOM_INT_SYMBOL, &[int_type, int_type], Some(&int_type));
+    fn_declare(module, &mut ids, RANDOM_FLOAT_SYMBOL, &[], Some(&float_type));
+    fn_declare(module, &mut ids, STRCMP_SYMBOL, &[ptr_type, ptr_type], Some(&int_type));
+    fn_declare(module, &mut ids, PRINT_INT_SYMBOL, &[int_type], None);
+    fn_declare(module, &mut ids, PRINT_FLOAT_SYMBOL, &[float_type], None);
+    fn_declare(module, &mut ids, PRINT_RESULT_INT_SYMBOL, &[int_type], None);
+    fn_declare(module, &mut ids, PRINT_RESULT_FLOAT_SYMBOL, &[float_type], None);
+    fn_declare(module, &mut ids, CATCH_CRASHES_SYMBOL, &[], None);
+    fn_declare(module, &mut ids, FLUSH_SYMBOL, &[], None);
+    fn_declare(module, &mut ids, STRING_CONCAT_SYMBOL, &[ptr_type, ptr_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, STRING_FROM_INT_SYMBOL, &[int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, STRING_FROM_FLOAT_SYMBOL, &[float_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, STRING_FROM_FLOAT_DIGITS_SYMBOL, &[float_type, int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, STRING_FROM_BOOL_SYMBOL, &[int_type], Some(&ptr_type));
+    fn_declare(module, &mut ids, STRING_FROM_C_SYMBOL, &[ptr_type], Some(&ptr_type));
+    // Assertions take the file and span they were made at after their values
+    fn_declare(module, &mut ids, ASSERT_SYMBOL, &[int_type, ptr_type, int_type, int_type], None);
+    fn_declare(module, &mut ids, ASSERT_EQ_INT_SYMBOL, &[int_type, int_type, ptr_type, int_type, int_type], None);
+    fn_declare(module, &mut ids, ASSERT_EQ_FLOAT_SYMBOL, &[float_type, float_type, ptr_type, int_type, int_type], None);
+    fn_declare(module, &mut ids, ASSERT_EQ_BOOL_SYMBOL, &[int_type, int_type, ptr_type, int_type, int_type], None);
+    fn_declare(module, &mut ids, ASSERT_EQ_STRING_SYMBOL, &[ptr_type, ptr_type, ptr_type, int_type, int_type], None);
 
     ids
 }
\ No newline at end of file
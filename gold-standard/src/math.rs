@@ -1,7 +1,202 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[export_name = "gold_ipowi"]
 pub unsafe extern "C" fn ipowi(lhs: i64, rhs: i64) -> i64 {
     if rhs < 0 {
         0
     } else {
         lhs.pow(rhs as u32)
     }
-}
\ No newline at end of file
+}
+
+#[export_name = "gold_powf"]
+pub unsafe extern "C" fn powf(lhs: f64, rhs: f64) -> f64 {
+    lhs.powf(rhs)
+}
+
+#[export_name = "gold_abs_int"]
+pub unsafe extern "C" fn abs_int(n: i64) -> i64 {
+    n.wrapping_abs()
+}
+
+#[export_name = "gold_abs_float"]
+pub unsafe extern "C" fn abs_float(f: f64) -> f64 {
+    f.abs()
+}
+
+#[export_name = "gold_min_int"]
+pub unsafe extern "C" fn min_int(a: i64, b: i64) -> i64 {
+    if a < b { a } else { b }
+}
+
+// Compiled code compares floats the same way, which doesn't skip over NaN
+// like `f64::min` does
+#[export_name = "gold_min_float"]
+pub unsafe extern "C" fn min_float(a: f64, b: f64) -> f64 {
+    if a < b { a } else { b }
+}
+
+#[export_name = "gold_max_int"]
+pub unsafe extern "C" fn max_int(a: i64, b: i64) -> i64 {
+    if a > b { a } else { b }
+}
+
+#[export_name = "gold_max_float"]
+pub unsafe extern "C" fn max_float(a: f64, b: f64) -> f64 {
+    if a > b { a } else { b }
+}
+
+#[export_name = "gold_clamp_int"]
+pub unsafe extern "C" fn clamp_int(n: i64, low: i64, high: i64) -> i64 {
+    if n < low { low } else if n > high { high } else { n }
+}
+
+#[export_name = "gold_clamp_float"]
+pub unsafe extern "C" fn clamp_float(f: f64, low: f64, high: f64) -> f64 {
+    if f < low { low } else if f > high { high } else { f }
+}
+
+#[export_name = "gold_sqrt"]
+pub unsafe extern "C" fn sqrt(f: f64) -> f64 {
+    f.sqrt()
+}
+
+#[export_name = "gold_floor"]
+pub unsafe extern "C" fn floor(f: f64) -> f64 {
+    f.floor()
+}
+
+#[export_name = "gold_ceil"]
+pub unsafe extern "C" fn ceil(f: f64) -> f64 {
+    f.ceil()
+}
+
+/// Rounds halfway cases away from zero.
+#[export_name = "gold_round"]
+pub unsafe extern "C" fn round(f: f64) -> f64 {
+    f.round()
+}
+
+#[export_name = "gold_sin"]
+pub unsafe extern "C" fn sin(f: f64) -> f64 {
+    f.sin()
+}
+
+#[export_name = "gold_cos"]
+pub unsafe extern "C" fn cos(f: f64) -> f64 {
+    f.cos()
+}
+
+#[export_name = "gold_tan"]
+pub unsafe extern "C" fn tan(f: f64) -> f64 {
+    f.tan()
+}
+
+#[export_name = "gold_asin"]
+pub unsafe extern "C" fn asin(f: f64) -> f64 {
+    f.asin()
+}
+
+#[export_name = "gold_acos"]
+pub unsafe extern "C" fn acos(f: f64) -> f64 {
+    f.acos()
+}
+
+#[export_name = "gold_atan"]
+pub unsafe extern "C" fn atan(f: f64) -> f64 {
+    f.atan()
+}
+
+#[export_name = "gold_atan2"]
+pub unsafe extern "C" fn atan2(y: f64, x: f64) -> f64 {
+    y.atan2(x)
+}
+
+#[export_name = "gold_exp"]
+pub unsafe extern "C" fn exp(f: f64) -> f64 {
+    f.exp()
+}
+
+#[export_name = "gold_ln"]
+pub unsafe extern "C" fn ln(f: f64) -> f64 {
+    f.ln()
+}
+
+#[export_name = "gold_log10"]
+pub unsafe extern "C" fn log10(f: f64) -> f64 {
+    f.log10()
+}
+
+#[export_name = "gold_log2"]
+pub unsafe extern "C" fn log2(f: f64) -> f64 {
+    f.log2()
+}
+
+/// The greatest common divisor of the sizes of two integers, which is 0 when
+/// both are.
+#[export_name = "gold_gcd"]
+pub unsafe extern "C" fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        let rest = a % b;
+        a = b;
+        b = rest;
+    }
+    a as i64
+}
+
+// Floats are 32 bits wide in Gold, so an integer is rounded straight to one
+// instead of going through a 64 bit float first
+#[export_name = "gold_to_float"]
+pub unsafe extern "C" fn to_float(n: i64) -> f64 {
+    n as f32 as f64
+}
+
+/// Drops what comes after the point. Floats too big to fit become the
+/// biggest or smallest integer, and NaN becomes 0.
+#[export_name = "gold_to_int"]
+pub unsafe extern "C" fn to_int(f: f64) -> i64 {
+    f as i64
+}
+
+// The state of the random number generator, which is seeded from the clock
+// when a program doesn't seed it
+static RANDOM: Mutex<Option<u64>> = Mutex::new(None);
+
+#[export_name = "gold_seed_random"]
+pub unsafe extern "C" fn seed_random(seed: i64) {
+    *RANDOM.lock().unwrap() = Some(seed as u64);
+}
+
+// SplitMix64, which is fast and good enough for anything but cryptography
+fn next_random() -> u64 {
+    let mut state = RANDOM.lock().unwrap();
+    let seed = state.get_or_insert_with(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+    });
+    *seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// A random integer from `low` up to and including `high`, or `low` when
+/// `high` is smaller.
+#[export_name = "gold_random_int"]
+pub unsafe extern "C" fn random_int(low: i64, high: i64) -> i64 {
+    if high <= low {
+        return low;
+    }
+    let count = (high as i128 - low as i128 + 1) as u128;
+    let offset = (next_random() as u128 * count) >> 64;
+    (low as i128 + offset as i128) as i64
+}
+
+/// A random float from 0 up to but not including 1.
+#[export_name = "gold_random_float"]
+pub unsafe extern "C" fn random_float() -> f64 {
+    // Only as many bits as a 32 bit float holds, so it can't round up to 1
+    (next_random() >> 40) as f64 / (1u64 << 24) as f64
+}
@@ -1,9 +1,115 @@
+//! Reference counting for the values programs allocate: strings built at
+//! runtime, structures and enums.
+//!
+//! Every value allocated here is registered along with its count, starting at
+//! one for whoever allocated it. Compiled code retains a value when it keeps
+//! another reference to it and releases it when a reference goes away, and
+//! the value is freed once nothing refers to it. Releasing a structure or enum
+//! releases the values its fields point to.
+//!
+//! Pointers that weren't allocated here, like string literals and strings from
+//! C, are left alone, so they can be handled the same way as the rest.
+
+use std::collections::BTreeMap;
 use std::ffi;
+use std::sync::Mutex;
+
+/// The exit code of a program that left values allocated, when it's run with
+/// `--leak-check`.
+pub const LEAK_EXIT_CODE: i32 = 71;
+
+struct Allocation {
+    references: i64,
+    size: usize,
+    contents: Contents,
+}
+
+enum Contents {
+    // A bit for each 8 byte field that holds a value to release with it
+    Fields(u64),
+    // The same for values with more fields than fit in one word
+    WideFields(Box<[u64]>),
+    // Drops a list or map made in Rust, adding the values it held to the ones
+    // to release
+    Collection(unsafe fn(usize, &mut Vec<usize>)),
+}
+
+static ALLOCATIONS: Mutex<BTreeMap<usize, Allocation>> = Mutex::new(BTreeMap::new());
+
+/// Allocates a value with a single reference. `pointers` has a bit set for
+/// each of its 8 byte fields that points to another value, which only the
+/// first 64 fields can have.
+#[export_name = "gold_alloc"]
+pub(crate) unsafe extern "C" fn alloc(size: i64, pointers: i64) -> *mut ffi::c_void {
+    allocate(size, Contents::Fields(pointers as u64))
+}
+
+/// Allocates a value with more fields than `alloc` can describe. `pointers`
+/// points to a bit for each of its fields, 64 to a word.
+#[export_name = "gold_alloc_wide"]
+pub(crate) unsafe extern "C" fn alloc_wide(size: i64, pointers: *const u64) -> *mut ffi::c_void {
+    let words = (size as usize / 8).div_ceil(64);
+    allocate(size, Contents::WideFields(std::slice::from_raw_parts(pointers, words).into()))
+}
+
+unsafe fn allocate(size: i64, contents: Contents) -> *mut ffi::c_void {
+    // Enums without fields are just a tag, but zero sized values still need
+    // an address of their own
+    let size = size.max(1) as usize;
+    let value = libc::malloc(size);
+    ALLOCATIONS.lock().unwrap().insert(value as usize, Allocation { references: 1, size, contents });
+    value
+}
+
+/// Registers a list or map with a single reference, which `free` drops once
+/// nothing refers to it.
+pub(crate) fn register(value: usize, size: usize, free: unsafe fn(usize, &mut Vec<usize>)) {
+    let allocation = Allocation { references: 1, size, contents: Contents::Collection(free) };
+    ALLOCATIONS.lock().unwrap().insert(value, allocation);
+}
 
-pub unsafe extern "C" fn malloc(size: i64) -> *mut ffi::c_void {
-    libc::malloc(size as libc::size_t)
+#[export_name = "gold_retain"]
+pub(crate) unsafe extern "C" fn retain(value: *mut ffi::c_void) {
+    if let Some(allocation) = ALLOCATIONS.lock().unwrap().get_mut(&(value as usize)) {
+        allocation.references += 1;
+    }
 }
 
-pub unsafe extern "C" fn free(ptr: *mut ffi::c_void) {
-    libc::free(ptr)
-}
\ No newline at end of file
+/// Drops a reference to a value, freeing it along with anything only it
+/// refers to once it was the last one.
+#[export_name = "gold_release"]
+pub(crate) unsafe extern "C" fn release(value: *mut ffi::c_void) {
+    // A long list is freed one value at a time rather than recursively
+    let mut released = vec![value as usize];
+    let mut allocations = ALLOCATIONS.lock().unwrap();
+    while let Some(value) = released.pop() {
+        let allocation = match allocations.get_mut(&value) {
+            Some(allocation) => allocation,
+            None => continue,
+        };
+        allocation.references -= 1;
+        if allocation.references > 0 {
+            continue;
+        }
+        match allocations.remove(&value).unwrap().contents {
+            Contents::Fields(pointers) => release_fields(value, &[pointers], &mut released),
+            Contents::WideFields(pointers) => release_fields(value, &pointers, &mut released),
+            Contents::Collection(free) => free(value, &mut released),
+        }
+    }
+}
+
+// Frees a structure or enum, adding the values its fields point to to the ones
+// to release
+unsafe fn release_fields(value: usize, pointers: &[u64], released: &mut Vec<usize>) {
+    let fields = value as *const usize;
+    let pointer = |bit: &usize| pointers[bit / 64] & (1 << (bit % 64)) != 0;
+    released.extend((0..pointers.len() * 64).filter(pointer).map(|bit| *fields.add(bit)));
+    libc::free(value as *mut ffi::c_void);
+}
+
+/// How many values are still allocated, and how many bytes they take up.
+pub fn allocated() -> (usize, usize) {
+    let allocations = ALLOCATIONS.lock().unwrap();
+    (allocations.len(), allocations.values().map(|allocation| allocation.size).sum())
+}
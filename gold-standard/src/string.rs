@@ -1,5 +1,49 @@
+use std::ffi;
 use std::os::raw;
+use std::ptr;
 
+use crate::mem::alloc;
+
+#[export_name = "gold_string_compare"]
 pub unsafe extern "C" fn string_compare(lhs: *mut raw::c_char, rhs: *mut raw::c_char) -> i64 {
     libc::strcmp(lhs, rhs) as i64
-}
\ No newline at end of file
+}
+
+/// Copies the bytes into a newly allocated C string, which is freed once
+/// nothing refers to it. None of the callers have an interior nul.
+pub(crate) fn into_c_string(bytes: &[u8]) -> *mut raw::c_char {
+    unsafe {
+        let string = alloc(bytes.len() as i64 + 1, 0) as *mut u8;
+        ptr::copy_nonoverlapping(bytes.as_ptr(), string, bytes.len());
+        *string.add(bytes.len()) = 0;
+        string as *mut raw::c_char
+    }
+}
+
+#[export_name = "gold_string_concat"]
+pub unsafe extern "C" fn string_concat(lhs: *mut raw::c_char, rhs: *mut raw::c_char) -> *mut raw::c_char {
+    let mut joined = ffi::CStr::from_ptr(lhs).to_bytes().to_vec();
+    joined.extend_from_slice(ffi::CStr::from_ptr(rhs).to_bytes());
+    into_c_string(&joined)
+}
+
+#[export_name = "gold_string_from_int"]
+pub unsafe extern "C" fn string_from_int(i: i64) -> *mut raw::c_char {
+    into_c_string(i.to_string().as_bytes())
+}
+
+#[export_name = "gold_string_from_float"]
+pub unsafe extern "C" fn string_from_float(f: f64) -> *mut raw::c_char {
+    into_c_string(f.to_string().as_bytes())
+}
+
+#[export_name = "gold_string_from_bool"]
+pub unsafe extern "C" fn string_from_bool(b: i64) -> *mut raw::c_char {
+    into_c_string(if b != 0 { b"true" } else { b"false" })
+}
+
+/// Writes a float with a number of digits after the point, for `printf`.
+#[export_name = "gold_string_from_float_digits"]
+pub unsafe extern "C" fn string_from_float_digits(f: f64, digits: i64) -> *mut raw::c_char {
+    into_c_string(format!("{:.*}", digits.max(0) as usize, f).as_bytes())
+}
@@ -0,0 +1,94 @@
+//! Assertions for tests written in Gold. Each test runs in a process of its
+//! own, so a failed assertion ends it after writing where it failed to stderr,
+//! which `gold test` reads back with `Failure::parse`.
+
+use std::ffi::CStr;
+use std::fmt;
+use std::io::Write;
+use std::ops::Range;
+use std::os::raw::c_char;
+
+/// The exit code of a test whose assertion failed.
+pub const FAILED_EXIT_CODE: i32 = 101;
+
+const PREFIX: &str = "assertion failed at ";
+
+/// Where an assertion failed and why.
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub file: String,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl Failure {
+    /// Finds the failure a test wrote to stderr.
+    pub fn parse(stderr: &str) -> Option<Self> {
+        let line = stderr.lines().find_map(|line| line.strip_prefix(PREFIX))?;
+        let (start, rest) = line.split_once("..")?;
+        let (end, rest) = rest.split_once(" in ")?;
+        let (file, message) = rest.split_once(": ")?;
+        Some(Failure {
+            file: file.to_owned(),
+            span: start.parse().ok()?..end.parse().ok()?,
+            message: message.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}..{} in {}: {}", PREFIX, self.span.start, self.span.end, self.file, self.message)
+    }
+}
+
+unsafe fn fail(file: *const c_char, start: i64, end: i64, message: String) -> ! {
+    let failure = Failure {
+        file: CStr::from_ptr(file).to_string_lossy().into_owned(),
+        span: start as usize..end as usize,
+        message,
+    };
+    // Exiting doesn't flush what the test printed so far
+    std::io::stdout().flush().ok();
+    eprintln!("{}", failure);
+    std::process::exit(FAILED_EXIT_CODE)
+}
+
+unsafe fn string(s: *const c_char) -> String {
+    format!("{:?}", CStr::from_ptr(s).to_string_lossy())
+}
+
+#[export_name = "gold_assert"]
+pub(crate) unsafe extern "C" fn assert(condition: i64, file: *const c_char, start: i64, end: i64) {
+    if condition == 0 {
+        fail(file, start, end, "the condition is false".to_owned());
+    }
+}
+
+#[export_name = "gold_assert_eq_int"]
+pub(crate) unsafe extern "C" fn assert_eq_int(left: i64, right: i64, file: *const c_char, start: i64, end: i64) {
+    if left != right {
+        fail(file, start, end, format!("left is {}, right is {}", left, right));
+    }
+}
+
+#[export_name = "gold_assert_eq_float"]
+pub(crate) unsafe extern "C" fn assert_eq_float(left: f64, right: f64, file: *const c_char, start: i64, end: i64) {
+    if left != right {
+        fail(file, start, end, format!("left is {}, right is {}", left, right));
+    }
+}
+
+#[export_name = "gold_assert_eq_bool"]
+pub(crate) unsafe extern "C" fn assert_eq_bool(left: i64, right: i64, file: *const c_char, start: i64, end: i64) {
+    if (left != 0) != (right != 0) {
+        fail(file, start, end, format!("left is {}, right is {}", left != 0, right != 0));
+    }
+}
+
+#[export_name = "gold_assert_eq_string"]
+pub(crate) unsafe extern "C" fn assert_eq_string(left: *const c_char, right: *const c_char, file: *const c_char, start: i64, end: i64) {
+    if CStr::from_ptr(left) != CStr::from_ptr(right) {
+        fail(file, start, end, format!("left is {}, right is {}", string(left), string(right)));
+    }
+}
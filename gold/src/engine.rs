@@ -0,0 +1,209 @@
+use std::ffi::c_void;
+use std::fmt;
+use std::mem;
+use std::os::raw::c_char;
+
+use gold_backend::codegen::Compiled;
+use gold_frontend::error::render_errors;
+use gold_frontend::module;
+
+use crate::{build_with_host, HostFunction, Type};
+
+// What scripts are called in error messages. `// Uses:` lines in a script are
+// looked up relative to the working directory.
+const SCRIPT_NAME: &str = "script.gold";
+
+/// Compiles Gold scripts and hands out their functions to the host.
+///
+/// Functions of the host are registered before compiling, and are called
+/// from Gold like any other function. Handles to compiled functions stay
+/// valid for as long as the engine is around, even after compiling again.
+#[derive(Default)]
+pub struct Engine {
+    host: Vec<HostFunction>,
+    compiled: Vec<Compiled>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The script has syntax or type errors, rendered the way `gold` prints
+    /// them, or code generation failed
+    Compile(String),
+    /// No function with the name was compiled
+    NotFound(String),
+    /// The function exists, but with a different signature than asked for
+    Signature { name: String, expected: String, found: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Compile(errors) => write!(f, "{}", errors),
+            Error::NotFound(name) => write!(f, "no function named '{}' has been compiled", name),
+            Error::Signature { name, expected, found } => {
+                write!(f, "'{}' is declared as `{}` but was asked for as `{}`", name, expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a function of the host callable from scripts compiled after
+    /// this, with the Gold signature that matches its Rust one.
+    pub fn register<F: Function>(&mut self, name: &str, function: F) {
+        self.host.push(HostFunction {
+            name: name.to_owned(),
+            params: F::params(),
+            return_type: F::return_type(),
+            address: function.address(),
+        });
+    }
+
+    /// Compiles a script, after which its functions can be looked up with
+    /// [`Engine::get`]. Functions of a previously compiled script are
+    /// replaced by ones with the same name.
+    pub fn compile(&mut self, source: &str) -> Result<(), Error> {
+        let modules = module::load(SCRIPT_NAME, source.to_owned());
+        let mut errors = String::new();
+        let compilation = build_with_host(modules, &self.host, false, |module, parse_errors, type_errors| {
+            errors.push_str(&render_errors(&module.filename, &module.source, parse_errors, type_errors));
+        });
+        match compilation {
+            Some(mut compilation) => {
+                self.compiled.push(compilation.compile().map_err(Error::Compile)?);
+                Ok(())
+            }
+            None => Err(Error::Compile(errors)),
+        }
+    }
+
+    /// A handle to a compiled function, as long as its signature matches.
+    ///
+    /// ```
+    /// # let mut engine = gold::Engine::new();
+    /// # engine.compile("// foo is a function.\n// Params:\n// 'x' is of type Int.\n// Returns: Int\nfn {\n  x + 1\n}\n").unwrap();
+    /// let foo = engine.get::<extern "C" fn(i64) -> i64>("foo").unwrap();
+    /// assert_eq!(foo(1), 2);
+    /// assert!(engine.get::<extern "C" fn(f32) -> i64>("foo").is_err());
+    /// ```
+    pub fn get<F: Function>(&self, name: &str) -> Result<F, Error> {
+        let (address, sig) = self.compiled.iter().rev()
+            .find_map(|compiled| compiled.function(name))
+            .ok_or_else(|| Error::NotFound(name.to_owned()))?;
+                if sig.params != F::params() || sig.returns != F::return_type() {
+            return Err(Error::Signature {
+                name: name.to_owned(),
+                expected: signature(sig.params.clone(), sig.returns.clone()),
+                found: signature(F::params(), F::return_type()),
+            });
+        }
+        // The signature was just checked against the one Gold compiled
+        Ok(unsafe { F::from_address(address) })
+    }
+}
+
+fn signature(params: Vec<Type>, return_type: Type) -> String {
+    let params = params.iter().map(Type::to_string).collect::<Vec<String>>();
+    ["fn(", params.join(", ").as_str(), ") -> ", return_type.to_string().as_str()].join("")
+}
+
+/// A Rust type that can be passed to and from Gold.
+pub trait Value {
+    fn gold_type() -> Type;
+}
+
+impl Value for i64 {
+    fn gold_type() -> Type {
+        Type::Int
+    }
+}
+
+impl Value for f32 {
+    fn gold_type() -> Type {
+        Type::Float
+    }
+}
+
+impl Value for bool {
+    fn gold_type() -> Type {
+        Type::Bool
+    }
+}
+
+/// A nul terminated Gold string. Gold only borrows the strings it's given,
+/// and a string it gives back stays allocated for the host to keep.
+impl Value for *const c_char {
+    fn gold_type() -> Type {
+        Type::String
+    }
+}
+
+impl Value for *mut c_void {
+    fn gold_type() -> Type {
+        Type::Pointer
+    }
+}
+
+/// A Rust type that can be returned from Gold, which also includes nothing.
+pub trait ReturnValue {
+    fn gold_type() -> Type;
+}
+
+impl<T: Value> ReturnValue for T {
+    fn gold_type() -> Type {
+        T::gold_type()
+    }
+}
+
+impl ReturnValue for () {
+    fn gold_type() -> Type {
+        Type::Void
+    }
+}
+
+/// A function pointer type with a matching Gold signature. Gold functions
+/// use the C calling convention, so these are all `extern "C" fn`s.
+pub trait Function: Copy {
+    fn params() -> Vec<Type>;
+    fn return_type() -> Type;
+    fn address(self) -> *const u8;
+    /// # Safety
+    ///
+    /// `address` must point to a function with this signature.
+    unsafe fn from_address(address: *const u8) -> Self;
+}
+
+macro_rules! function {
+    ($($arg:ident),*) => {
+        impl<R: ReturnValue, $($arg: Value),*> Function for extern "C" fn($($arg),*) -> R {
+            fn params() -> Vec<Type> {
+                vec![$($arg::gold_type()),*]
+            }
+
+            fn return_type() -> Type {
+                R::gold_type()
+            }
+
+            fn address(self) -> *const u8 {
+                self as *const u8
+            }
+
+            unsafe fn from_address(address: *const u8) -> Self {
+                mem::transmute::<*const u8, Self>(address)
+            }
+        }
+    };
+}
+
+function!();
+function!(A);
+function!(A, B);
+function!(A, B, C);
+function!(A, B, C, D);
+function!(A, B, C, D, E);
@@ -0,0 +1,224 @@
+//! Runs `.gold` files that say what they should do, which is how Gold itself
+//! is tested. A file is a test when it has at least one of these lines:
+//!
+//! - `// expect-stdout: text`, one for each line the program prints
+//! - `// expect-error: NotDefined`, one for each error it should be rejected
+//!   with, named after its `TypeError`, or `SyntaxError` for parse errors
+//! - `// expect-exit: 3`, the exit code, when it isn't 0 for a program that
+//!   runs or 1 for one that has errors
+//!
+//! Tests can also be written in Gold, as functions declared with
+//! `// test_addition is a test.` that check what they do with `assert` and
+//! `assert_eq`. Each of them runs in its own `gold` process.
+//!
+//! Everything is run with `--leak-check`, so a program that runs fine but
+//! leaves values allocated fails too.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use gold_frontend::error::render_diagnostics;
+use gold_frontend::frontend::Expr;
+use gold_frontend::module;
+use gold_standard::test::Failure;
+
+const STDOUT: &str = "expect-stdout:";
+const ERROR: &str = "expect-error:";
+const EXIT: &str = "expect-exit:";
+
+/// What a test file expects, read from its `// expect-` lines.
+#[derive(Default, Debug, PartialEq)]
+pub struct Expectations {
+    pub stdout: Vec<String>,
+    pub errors: Vec<String>,
+    pub exit_code: Option<i32>,
+}
+
+impl Expectations {
+    /// Reads the expectations of a file, if it has any.
+    pub fn parse(source: &str) -> Option<Self> {
+        let mut expectations = Expectations::default();
+        let mut found = false;
+        for line in source.lines() {
+            let line = match expectation(line) {
+                Some(line) => line,
+                None => continue,
+            };
+            if let Some(text) = line.strip_prefix(STDOUT) {
+                // A single space separates the text, anything after it is printed
+                expectations.stdout.push(text.strip_prefix(' ').unwrap_or(text).to_owned());
+            } else if let Some(name) = line.strip_prefix(ERROR) {
+                expectations.errors.push(name.trim().to_owned());
+            } else if let Some(code) = line.strip_prefix(EXIT) {
+                expectations.exit_code = code.trim().parse().ok();
+            }
+            found = true;
+        }
+        if found { Some(expectations) } else { None }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self.exit_code {
+            Some(code) => code,
+            None if self.errors.is_empty() => 0,
+            None => 1,
+        }
+    }
+}
+
+/// The rest of an `// expect-` line, from `expect-` on.
+fn expectation(line: &str) -> Option<&str> {
+    let comment = line.trim_start().strip_prefix("//")?.trim_start();
+    [STDOUT, ERROR, EXIT].iter().any(|prefix| comment.starts_with(prefix)).then_some(comment)
+}
+
+/// The source with its `// expect-` lines blanked out, which aren't Gold. The
+/// rest of the file keeps its place, so locations in errors still point into
+/// the file.
+pub fn without_expectations(source: &str) -> String {
+    source.split_inclusive('\n').map(|line| match expectation(line) {
+        Some(_) => line.chars().map(|ch| match ch {
+            '\n' | '\r' => ch.to_string(),
+            ch => " ".repeat(ch.len_utf8()),
+        }).collect(),
+        None => line.to_owned(),
+    }).collect()
+}
+
+/// Something `gold test` checks.
+pub enum Case {
+    /// A file that says what it should do
+    File(PathBuf),
+    /// A test written in Gold, by the file it's in and its name
+    Test(PathBuf, String),
+}
+
+impl Case {
+    /// Runs the case with the `gold` binary at `gold`, describing everything
+    /// that didn't go as expected in the error.
+    pub fn check(&self, gold: &Path) -> Result<(), String> {
+        match self {
+            Case::File(file) => check(gold, file),
+            Case::Test(file, name) => check_test(gold, file, name),
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Case::File(file) => write!(f, "{}", file.display()),
+            Case::Test(file, name) => write!(f, "{}::{}", file.display(), name),
+        }
+    }
+}
+
+/// Every test file and test in a directory and the directories under it, in
+/// order.
+pub fn find(dir: &Path) -> Vec<Case> {
+    let mut cases = Vec::new();
+    let mut entries = fs::read_dir(dir).into_iter().flatten().flatten().map(|entry| entry.path()).collect::<Vec<_>>();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            cases.extend(find(&path));
+        } else if path.extension().is_some_and(|ext| ext == "gold") {
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(_) => continue,
+            };
+            let expected = Expectations::parse(&source);
+            // A file that should be rejected has no tests that could run
+            if expected.as_ref().is_some_and(|expected| !expected.errors.is_empty()) {
+                cases.push(Case::File(path.to_owned()));
+                continue;
+            }
+            if expected.is_some() {
+                cases.push(Case::File(path.to_owned()));
+            }
+            for name in tests(&path, source) {
+                cases.push(Case::Test(path.to_owned(), name));
+            }
+        }
+    }
+    cases
+}
+
+/// The names of the tests declared in a file, in order.
+pub fn tests(file: &Path, source: String) -> Vec<String> {
+    let mut modules = module::load(&file.display().to_string(), without_expectations(&source));
+    // The file that was loaded comes after the modules it uses
+    let program = modules.pop().unwrap().program;
+    program.functions.into_iter().filter_map(|item| match item {
+        Expr::Test(name, _, _) => Some(name),
+        _ => None,
+    }).collect()
+}
+
+/// Checks a test file against what it expects. The errors are found here,
+/// while the program runs in its own `gold` process so what it prints and
+/// how it exits can be seen.
+pub fn check(gold: &Path, file: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let expected = Expectations::parse(&source).unwrap_or_default();
+    let mut problems = Vec::new();
+
+    let filename = file.display().to_string();
+    let mut errors = Vec::new();
+    crate::lower(module::load(&filename, without_expectations(&source)), false, |_, parse_errors, type_errors| {
+        errors.extend(parse_errors.iter().map(|_| "SyntaxError".to_owned()));
+        errors.extend(type_errors.iter().map(|error| error.name().to_owned()));
+    });
+    if errors != expected.errors {
+        problems.push(format!("expected errors [{}], got [{}]", expected.errors.join(", "), errors.join(", ")));
+    }
+
+    let output = Command::new(gold).arg(file).arg("--leak-check").output().map_err(|e| e.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines = stdout.lines().map(str::to_owned).collect::<Vec<String>>();
+    if lines != expected.stdout {
+        problems.push(format!("expected stdout:\n{}\ngot:\n{}", expected.stdout.join("\n"), lines.join("\n")));
+    }
+    // Killed by a signal when there is no code
+    let code = output.status.code().unwrap_or(-1);
+    if code != expected.exit_code() {
+        problems.push(format!("expected exit code {}, got {}", expected.exit_code(), code));
+    }
+
+    if problems.is_empty() { Ok(()) } else { Err(problems.join("\n")) }
+}
+
+/// Runs a test written in Gold in its own `gold` process. A failed assertion
+/// is shown along with the source of the assertion.
+pub fn check_test(gold: &Path, file: &Path, name: &str) -> Result<(), String> {
+    let output = Command::new(gold).arg(file).arg(["--test=", name].join("")).arg("--leak-check").output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let mut problems = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match Failure::parse(&stderr) {
+        Some(failure) => problems.push_str(&render_failure(failure)),
+        None => match output.status.code() {
+            Some(code) => problems.push_str(&format!("exited with code {}\n{}", code, stderr)),
+            None => problems.push_str(&format!("killed by a signal\n{}", stderr)),
+        },
+    }
+    Err(problems.trim_end().to_owned())
+}
+
+fn render_failure(failure: Failure) -> String {
+    let source = match fs::read_to_string(&failure.file) {
+        Ok(source) if source.get(failure.span.to_owned()).is_some() => source,
+        _ => return failure.to_string(),
+    };
+    let diagnostic = Diagnostic::error()
+        .with_message(["assertion failed: ", failure.message.as_str()].join(""))
+        .with_labels(vec![
+            Label::primary(0, failure.span)
+        ]);
+    render_diagnostics(&failure.file, &source, vec![diagnostic])
+}
@@ -0,0 +1,132 @@
+//! Gold as a scripting language for Rust applications.
+//!
+//! ```
+//! use gold::Engine;
+//!
+//! extern "C" fn triple(x: i64) -> i64 {
+//!     x * 3
+//! }
+//!
+//! let mut engine = Engine::new();
+//! engine.register("triple", triple as extern "C" fn(i64) -> i64);
+//! engine.compile("
+//! // sixfold is a function.
+//! // Params:
+//! // 'x' is of type Int.
+//! // Returns: Int
+//! fn {
+//!   triple(x) * 2
+//! }
+//! ").unwrap();
+//!
+//! let sixfold = engine.get::<extern "C" fn(i64) -> i64>("sixfold").unwrap();
+//! assert_eq!(sixfold(7), 42);
+//! ```
+
+use peg::error::ParseError;
+use peg::str::LineCol;
+
+use gold_backend::analysis::lower::Analyzer;
+use gold_backend::analysis::optimize;
+use gold_backend::codegen::Compilation;
+use gold_backend::lir;
+use gold_frontend::error::TypeError;
+use gold_frontend::module::Module;
+
+pub use gold_frontend::frontend::Type;
+
+pub use engine::{Engine, Error, Function, ReturnValue, Value};
+
+mod engine;
+pub mod golden;
+pub mod link;
+
+/// A function of the host application, callable from Gold.
+struct HostFunction {
+    name: String,
+    params: Vec<Type>,
+    return_type: Type,
+    address: *const u8,
+}
+
+/// Checks every loaded module and sets up their compilation, with the file
+/// that was loaded as the program. Each file with errors is handed to
+/// `report`, and then there is nothing to compile. With `dump` set, every
+/// function is printed before and after it is optimized.
+pub fn build<R>(modules: Vec<Module>, dump: bool, report: R) -> Option<Compilation>
+where
+    R: FnMut(&Module, Vec<ParseError<LineCol>>, Vec<TypeError>),
+{
+    build_with_host(modules, &[], dump, report)
+}
+
+/// Checks and lowers every loaded module the same way as `build`, for
+/// running them without compiling. The program comes last.
+pub fn lower<R>(modules: Vec<Module>, dump: bool, report: R) -> Option<Vec<lir::Module>>
+where
+    R: FnMut(&Module, Vec<ParseError<LineCol>>, Vec<TypeError>),
+{
+    lower_with_host(modules, &[], dump, report)
+}
+
+fn build_with_host<R>(modules: Vec<Module>, host: &[HostFunction], dump: bool, report: R) -> Option<Compilation>
+where
+    R: FnMut(&Module, Vec<ParseError<LineCol>>, Vec<TypeError>),
+{
+    let mut lowered = lower_with_host(modules, host, dump, report)?;
+    let mut comp = Compilation::new(lowered.pop().unwrap());
+    for module in lowered {
+        comp.add_module(module);
+    }
+    for function in host {
+        comp.add_symbol(&function.name, function.address);
+    }
+    Some(comp)
+}
+
+fn lower_with_host<R>(mut modules: Vec<Module>, host: &[HostFunction], dump: bool, mut report: R) -> Option<Vec<lir::Module>>
+where
+    R: FnMut(&Module, Vec<ParseError<LineCol>>, Vec<TypeError>),
+{
+    // Analysis still runs on whatever parsed, so all errors in every file
+    // show up at once
+    let mut analyzers = Vec::new();
+    let mut failed = false;
+    for index in 0..modules.len() {
+        let mut analyzer = Analyzer::for_module(&modules[index]);
+        for function in host {
+            analyzer.declare_host_function(&function.name, &function.params, function.return_type.clone());
+        }
+        analyzer.check_module(&modules, index);
+        let module = &mut modules[index];
+        let mut type_errors = std::mem::take(&mut module.errors);
+        type_errors.append(&mut analyzer.errors);
+        let parse_errors = std::mem::take(&mut module.program.errors);
+        if !parse_errors.is_empty() || !type_errors.is_empty() {
+            failed = true;
+            report(module, parse_errors, type_errors);
+        }
+        analyzers.push(analyzer);
+    }
+    if failed {
+        return None;
+    }
+
+    for module in &mut modules {
+        let functions = std::mem::take(&mut module.program.functions);
+        if dump {
+            eprintln!(";; {} before optimizing\n{}", module.filename, optimize::dump(&functions));
+        }
+        module.program.functions = optimize::optimize(functions);
+        if dump {
+            eprintln!(";; {} after optimizing\n{}", module.filename, optimize::dump(&module.program.functions));
+        }
+    }
+
+    // The file that was loaded always comes last
+    let program = modules.len() - 1;
+    Some(modules.iter().zip(&analyzers).enumerate().map(|(index, (module, analyzer))| {
+        let namespace = if index == program { None } else { Some(module.name.as_str()) };
+        analyzer.lower(&module.program.functions, namespace)
+    }).collect())
+}
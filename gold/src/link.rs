@@ -0,0 +1,69 @@
+//! Links the object files of programs compiled ahead of time into
+//! executables, using the C compiler of the system.
+//!
+//! Compiled code calls into the runtime in `gold_standard`, which Cargo also
+//! builds as a static library next to `gold` itself.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The static library of the runtime.
+pub const RUNTIME: &str = "libgold_standard.a";
+
+// What the runtime needs from the system, which is what Rust's standard
+// library needs
+#[cfg(target_os = "linux")]
+const SYSTEM_LIBRARIES: &[&str] = &["-lgcc_s", "-lutil", "-lrt", "-lpthread", "-lm", "-ldl", "-lc"];
+#[cfg(target_os = "macos")]
+const SYSTEM_LIBRARIES: &[&str] = &["-lSystem", "-lc", "-lm"];
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const SYSTEM_LIBRARIES: &[&str] = &["-lpthread", "-lm", "-ldl", "-lc"];
+
+/// Where the runtime is, which is `GOLD_RUNTIME` when that's set and
+/// otherwise next to the running executable.
+pub fn runtime() -> Result<PathBuf, String> {
+    if let Some(runtime) = env::var_os("GOLD_RUNTIME") {
+        return Ok(PathBuf::from(runtime));
+    }
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let runtime = exe.with_file_name(RUNTIME);
+    if runtime.is_file() {
+        Ok(runtime)
+    } else {
+        Err(format!("can't find the runtime at {}, set GOLD_RUNTIME to where {} is", runtime.display(), RUNTIME))
+    }
+}
+
+/// Links an object file into an executable with `cc`, or whatever `CC`
+/// names, along with the C libraries its externals are from. Gives back what
+/// `cc` printed when it fails.
+pub fn link(object: &Path, output: &Path, libraries: &[String]) -> Result<(), String> {
+    let runtime = runtime()?;
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let result = Command::new(&cc)
+        .arg("-o")
+        .arg(output)
+        .arg(object)
+        .args(libraries.iter().map(|library| library_flag(library)))
+        .arg(&runtime)
+        .args(SYSTEM_LIBRARIES)
+        .output()
+        .map_err(|e| format!("can't run {}: {}", cc, e))?;
+    if result.status.success() {
+        Ok(())
+    } else {
+        Err(format!("linking {} failed:\n{}", output.display(), String::from_utf8_lossy(&result.stderr)))
+    }
+}
+
+// Names a library the way `gold_standard::ffi::resolve` finds it: `from m`
+// and `from libm` are both `-lm`, while a file name such as `libm.so.6` is
+// linked as it's written
+fn library_flag(library: &str) -> String {
+    if library.contains('.') {
+        ["-l:", library].concat()
+    } else {
+        ["-l", library.strip_prefix("lib").unwrap_or(library)].concat()
+    }
+}
@@ -1,22 +1,196 @@
-use peg::{error::ParseError, str::LineCol};
-
-use gold_backend::{analysis::lower::Lower, lir};
-use gold_backend::analysis::lower::Analyzer;
-use gold_frontend::frontend;
-use gold_frontend::parse::Parser;
+use gold::link;
+use gold_backend::codegen::crash::CRASH_EXIT_CODE;
 use gold_backend::codegen::Compilation;
-use std::env::args;
+use gold_backend::interp::{self, Interpreter};
+use gold_backend::lir::config::Config;
+use gold_frontend::error::print_errors;
+use gold_frontend::module;
+use gold_standard::mem::{self, LEAK_EXIT_CODE};
+use std::env::{self, args};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: gold test <dir>\n       gold [run] <file> [--interp] [--dump-optimizations] [--opt-level=none|speed|speed_and_size] \
+[--no-verify] [--baseline-cpu] [--target=<triple>] [--emit-asm] [--test=<name>] [--debug-info] [--leak-check]\n       \
+gold build <file> [-o <output>] [--target=<triple>] [--opt-level=none|speed|speed_and_size] [--no-verify] [--baseline-cpu] [--test=<name>] [--debug-info]";
 
 fn main() -> Result<(), String> {
-    let file = args().skip(1).next().expect("Need a file path please!");
-    let mut parser = Parser::new(&file)?;
-    let expr = parser.parse_file()?;
-    let mut analyze = Analyzer::new(parser.file_contents, parser.filename.to_owned());
-    expr.typecheck(&mut analyze);
+    let mut file = None;
+    let mut dump = false;
+    let mut config = Config::default();
+    let mut emit_asm = false;
+    let mut interp = false;
+    let mut test_name = None;
+    let mut leak_check = false;
+    let mut build = false;
+    let mut output = None;
+    // `gold run <file>` is the same as `gold <file>`
+    let mut args = args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("run") => {
+            args.next();
+        }
+        // Compiles the program ahead of time instead of running it
+        Some("build") => {
+            args.next();
+            build = true;
+        }
+        Some("test") => {
+            args.next();
+            let dir = args.next().ok_or(USAGE)?;
+            return test(Path::new(&dir));
+        }
+        _ => {}
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Where `gold build` writes the program
+            "-o" => output = Some(args.next().ok_or(USAGE)?),
+            // Interprets the program instead of compiling it
+            "--interp" => interp = true,
+            // Prints every function before and after it's optimized
+            "--dump-optimizations" => dump = true,
+            "--no-verify" => config.verify = false,
+            // Only uses what every CPU of the architecture has
+            "--baseline-cpu" => config.host_features = false,
+            // Prints the assembly of every function instead of running the program
+            "--emit-asm" => emit_asm = true,
+            // Tells gdb where Gold lines and variables are in the compiled code
+            "--debug-info" => config.debug_info = true,
+            // Fails when values the program allocated are never freed
+            "--leak-check" => leak_check = true,
+            // Runs one of the tests in the file instead of the program
+            flag if flag.starts_with("--test=") => test_name = Some(flag["--test=".len()..].to_owned()),
+            flag if flag.starts_with("--opt-level=") => config.opt_level = flag["--opt-level=".len()..].parse()?,
+            flag if flag.starts_with("--target=") => config.target = Some(Config::parse_target(&flag["--target=".len()..])?),
+            flag if flag.starts_with("--") => return Err(["unknown flag ", flag, "\n", USAGE].join("")),
+            _ => file = Some(arg),
+        }
+    }
+    let file = file.ok_or(USAGE)?;
+    if config.cross_compiling() && !build && !emit_asm {
+        let target = config.target.as_ref().unwrap();
+        return Err(format!("code compiled for {} can't be run here: pass --emit-asm to print its assembly, \
+            or use `gold build` to write an object file for it", target));
+    }
+    let source = fs::read_to_string(&file).map_err(|e| e.to_string())?;
+    // A test file can be run on its own too
+    let source = gold::golden::without_expectations(&source);
+    let modules = module::load(&file, source);
+
+    let report = |module: &module::Module, parse_errors, type_errors| {
+        print_errors(&module.filename, &module.source, parse_errors, type_errors);
+    };
+    if interp && !build {
+        let modules = gold::lower(modules, dump, report).unwrap_or_else(|| std::process::exit(1));
+        let interpreter = Interpreter::new(&modules);
+        let result = match test_name {
+            Some(name) => interpreter.call(&name, Vec::new()).map(|_| ()),
+            None => interpreter.run(),
+        };
+        match result {
+            Ok(()) => {}
+            // Exits the way the compiled program does when it crashes
+            Err(interp::Error::Trap(message)) => {
+                io::stdout().flush().ok();
+                eprintln!("error: {}", message);
+                std::process::exit(CRASH_EXIT_CODE);
+            }
+            Err(error) => return Err(error.to_string()),
+        }
+        if leak_check {
+            check_leaks();
+        }
+        return Ok(());
+    }
+
+    let comp = gold::build(modules, dump, report);
+    match comp {
+        Some(mut comp) => {
+            comp.set_config(config.clone());
+            if let Some(name) = &test_name {
+                comp.set_entry(name);
+            }
+            if build {
+                build_program(&mut comp, &file, output, &config)?;
+            } else if emit_asm {
+                print!("{}", comp.emit_assembly()?);
+            } else {
+                comp.run()?;
+                if leak_check {
+                    check_leaks();
+                }
+            }
+        }
+        None => std::process::exit(1),
+    }
+
+    Ok(())
+}
+
+/// Compiles the program to an object file and links it into an executable,
+/// which is named after the file unless `output` says otherwise. An output
+/// ending in `.o` is left as the object file, and so is the program for
+/// another target, since the runtime is only built for this one.
+fn build_program(comp: &mut Compilation, file: &str, output: Option<String>, config: &Config) -> Result<(), String> {
+    let libraries = comp.libraries();
+    let object = comp.emit_object()?;
+    let stem = Path::new(file).file_stem().ok_or(USAGE)?;
+    let output = match output {
+        Some(output) => PathBuf::from(output),
+        None if config.cross_compiling() => Path::new(stem).with_extension("o"),
+        None => PathBuf::from(stem),
+    };
+    if config.cross_compiling() || output.extension() == Some("o".as_ref()) {
+        return fs::write(&output, object).map_err(|e| format!("can't write {}: {}", output.display(), e));
+    }
+
+    let object_file = output.with_extension("o");
+    fs::write(&object_file, object).map_err(|e| format!("can't write {}: {}", object_file.display(), e))?;
+    let linked = link::link(&object_file, &output, &libraries);
+    fs::remove_file(&object_file).ok();
+    linked
+}
 
-    let mut comp = Compilation::new(analyze, vec![expr.clone()]);
+/// Exits with `LEAK_EXIT_CODE` when values are still allocated once the
+/// program is done.
+fn check_leaks() {
+    let (values, bytes) = mem::allocated();
+    if values > 0 {
+        let plural = if values == 1 { "value was" } else { "values were" };
+        eprintln!("error: {} {} never freed, taking up {} bytes", values, plural, bytes);
+        std::process::exit(LEAK_EXIT_CODE);
+    }
+}
 
-    comp.gen_fn(expr);
+/// Runs every test file and every test written in Gold in a directory,
+/// reporting each one the way `cargo test` does.
+fn test(dir: &Path) -> Result<(), String> {
+    let gold = env::current_exe().map_err(|e| e.to_string())?;
+    let cases = gold::golden::find(dir);
+    println!("running {} tests", cases.len());
+    let mut failures = Vec::new();
+    for case in &cases {
+        match case.check(&gold) {
+            Ok(()) => println!("test {} ... ok", case),
+            Err(problems) => {
+                println!("test {} ... FAILED", case);
+                failures.push((case, problems));
+            }
+        }
+    }
 
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (case, problems) in &failures {
+            println!("\n---- {} ----\n{}", case, problems);
+        }
+    }
+    let result = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed", result, cases.len() - failures.len(), failures.len());
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
     Ok(())
-}
\ No newline at end of file
+}
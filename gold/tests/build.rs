@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use gold_backend::codegen::crash::CRASH_EXIT_CODE;
+
+const SOURCE: &str = "// half is a function.\n// Params:\n// 'n' is of type Int.\n// Returns: Float\nfn {\n  to_float(n) / to_float(2)\n}\n\n\
+    // main is a function.\n// Params:\n// 'a' is of type Int.\n// Returns: Float\nfn {\n  println(\"halving\");\n  half(a + 2)\n}\n";
+
+/// A built executable prints and exits like the program does under `gold`.
+#[test]
+fn builds_executables() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let dir = std::env::temp_dir().join(format!("gold-builds-executables-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("half.gold");
+    fs::write(&file, SOURCE).unwrap();
+
+    let build = Command::new(gold).arg("build").arg(&file).arg("-o").arg(dir.join("half")).output().unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let built = Command::new(dir.join("half")).output().unwrap();
+    let run = Command::new(gold).arg(&file).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&built.stdout), "halving\n1.5\n");
+    assert_eq!(built.stdout, run.stdout);
+    assert_eq!(built.status.code(), Some(0));
+    // The object file is only kept when it's asked for
+    assert!(!dir.join("half.o").exists());
+
+    fs::write(&file, "// main is a function.\n// Params:\n// Returns: Int\nfn {\n  1 / 0\n}\n").unwrap();
+    let build = Command::new(gold).arg("build").arg(&file).arg("-o").arg(dir.join("crash")).output().unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let crashed = Command::new(dir.join("crash")).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(crashed.status.code(), Some(CRASH_EXIT_CODE));
+}
+
+/// Code for another target is written as an object file for it, and isn't
+/// run.
+#[test]
+fn cross_compiles() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let dir = std::env::temp_dir().join(format!("gold-cross-compiles-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("half.gold");
+    fs::write(&file, SOURCE).unwrap();
+    let target = if cfg!(target_arch = "aarch64") { "x86_64-unknown-linux-gnu" } else { "aarch64-unknown-linux-gnu" };
+    // The `e_machine` of an ELF file
+    let machine = if cfg!(target_arch = "aarch64") { 62u16 } else { 183 };
+
+    let build = Command::new(gold).arg("build").arg(&file).arg(format!("--target={}", target)).current_dir(&dir).output().unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let object = fs::read(dir.join("half.o")).unwrap();
+    assert_eq!(&object[..4], b"\x7fELF");
+    assert_eq!(u16::from_le_bytes([object[18], object[19]]), machine);
+
+    let run = Command::new(gold).arg(&file).arg(format!("--target={}", target)).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert!(!run.status.success());
+    assert!(stderr.contains("can't be run here"), "{}", stderr);
+    assert!(run.stdout.is_empty());
+}
+
+/// A program calling into a C library is linked against it, found the same
+/// way `gold` finds it when running the program.
+#[test]
+fn links_c_libraries() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let dir = std::env::temp_dir().join(format!("gold-links-c-libraries-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("triple.c"), "long long triple(long long x) { return 3 * x; }\n").unwrap();
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let library = Command::new(cc).args(["-shared", "-fPIC", "-o", "libtriple.so", "triple.c"]).current_dir(&dir).output().unwrap();
+    assert!(library.status.success(), "{}", String::from_utf8_lossy(&library.stderr));
+    let file = dir.join("triple.gold");
+    fs::write(&file, "// triple is an external function from triple.\n// Params:\n// 'x' is of type Int.\n// Returns: Int\n\n\
+        // main is a function.\n// Params:\n// Returns: Int\nfn {\n  triple(14)\n}\n").unwrap();
+
+    // The library is checked for the function while compiling too
+    let build = Command::new(gold).arg("build").arg(&file).current_dir(&dir)
+        .env("LIBRARY_PATH", &dir).env("LD_LIBRARY_PATH", &dir).output().unwrap();
+    assert!(build.status.success(), "{}", String::from_utf8_lossy(&build.stderr));
+    let built = Command::new(dir.join("triple")).env("LD_LIBRARY_PATH", &dir).output().unwrap();
+    let run = Command::new(gold).arg(&file).env("LD_LIBRARY_PATH", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&built.stdout), "42\n");
+    assert_eq!(built.stdout, run.stdout);
+}
@@ -0,0 +1,104 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use gold::{Engine, Error};
+
+const SCRIPT: &str = "
+// add is a function.
+// Params:
+// 'a' is of type Int.
+// 'b' is of type Int.
+// Returns: Int
+fn {
+  a + b
+}
+
+// bigger is a function.
+// Params:
+// 'a' is of type Int.
+// 'b' is of type Int.
+// Returns: Bool
+fn {
+  a > b
+}
+
+// greet is a function.
+// Params:
+// 'name' is of type String.
+// Returns: String
+fn {
+  \"Hello, {name}!\"
+}
+";
+
+extern "C" fn host_square(x: i64) -> i64 {
+    x * x
+}
+
+#[test]
+fn calls_compiled_functions() {
+    let mut engine = Engine::new();
+    engine.compile(SCRIPT).unwrap();
+
+    let add = engine.get::<extern "C" fn(i64, i64) -> i64>("add").unwrap();
+    assert_eq!(add(40, 2), 42);
+
+    let bigger = engine.get::<extern "C" fn(i64, i64) -> bool>("bigger").unwrap();
+    assert!(bigger(2, 1));
+    assert!(!bigger(1, 2));
+
+    let greet = engine.get::<extern "C" fn(*const c_char) -> *const c_char>("greet").unwrap();
+    let greeting = unsafe { CStr::from_ptr(greet(b"Gold\0".as_ptr() as *const c_char)) };
+    assert_eq!(greeting.to_str().unwrap(), "Hello, Gold!");
+}
+
+#[test]
+fn scripts_call_host_functions() {
+    let mut engine = Engine::new();
+    engine.register("square", host_square as extern "C" fn(i64) -> i64);
+    engine.compile("
+// hypotenuse_squared is a function.
+// Params:
+// 'a' is of type Int.
+// 'b' is of type Int.
+// Returns: Int
+fn {
+  square(a) + square(b)
+}
+").unwrap();
+
+    let hypotenuse_squared = engine.get::<extern "C" fn(i64, i64) -> i64>("hypotenuse_squared").unwrap();
+    assert_eq!(hypotenuse_squared(3, 4), 25);
+}
+
+#[test]
+fn checks_signatures() {
+    let mut engine = Engine::new();
+    engine.compile(SCRIPT).unwrap();
+
+    match engine.get::<extern "C" fn(i64) -> i64>("add") {
+        Err(Error::Signature { expected, found, .. }) => {
+            assert_eq!(expected, "fn(Int, Int) -> Int");
+            assert_eq!(found, "fn(Int) -> Int");
+        }
+        _ => panic!("expected a signature mismatch"),
+    }
+    assert!(matches!(engine.get::<extern "C" fn()>("missing"), Err(Error::NotFound(_))));
+}
+
+#[test]
+fn reports_compile_errors() {
+    let mut engine = Engine::new();
+    let error = engine.compile("
+// broken is a function.
+// Params:
+// Returns: Int
+fn {
+  nope(1)
+}
+").unwrap_err();
+    match error {
+        Error::Compile(errors) => assert!(errors.contains("Function with the name 'nope' does not exist"), "{}", errors),
+        other => panic!("expected compile errors, got {:?}", other),
+    }
+}
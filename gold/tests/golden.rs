@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use gold::golden::{self, Case, Expectations};
+use gold_backend::codegen::crash::CRASH_EXIT_CODE;
+use gold_standard::mem::LEAK_EXIT_CODE;
+
+/// Runs every test file and test under `tests/` at the root of the repository.
+#[test]
+fn golden_files() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests");
+    let cases = golden::find(&dir);
+    assert!(cases.iter().any(|case| matches!(case, Case::Test(..))));
+
+    let failures = cases.iter()
+        .filter_map(|case| case.check(gold).err().map(|problems| format!("{}:\n{}", case, problems)))
+        .collect::<Vec<String>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn reads_expectations() {
+    let source = "// expect-stdout: a  b\n// expect-stdout:\n// expect-error: NotDefined\n// expect-exit: 3\n";
+    assert_eq!(Expectations::parse(source), Some(Expectations {
+        stdout: vec!["a  b".to_owned(), String::new()],
+        errors: vec!["NotDefined".to_owned()],
+        exit_code: Some(3),
+    }));
+    assert_eq!(Expectations::parse("// main is a function.\n"), None);
+    // Blanked out rather than removed, so nothing else moves
+    let blanked = golden::without_expectations(source);
+    assert_eq!((blanked.len(), blanked.trim()), (source.len(), ""));
+    let source = "// expect-stdout: é\r\n// main is a function.\n";
+    let blanked = golden::without_expectations(source);
+    assert_eq!(blanked.len(), source.len());
+    assert_eq!(blanked.trim_start(), "// main is a function.\n");
+}
+
+/// A failed assertion is reported with the source of the assertion.
+#[test]
+fn failed_assertions() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let file = std::env::temp_dir().join(format!("gold-failed-assertions-{}.gold", std::process::id()));
+    fs::write(&file, "// test_sum is a test.\nfn {\n  var sum = 1 + 2;\n  assert_eq(sum, 4);\n}\n").unwrap();
+
+    assert_eq!(golden::tests(&file, fs::read_to_string(&file).unwrap()), vec!["test_sum"]);
+    let report = golden::check_test(gold, &file, "test_sum").unwrap_err();
+    fs::remove_file(&file).unwrap();
+    assert!(report.contains("assertion failed: left is 3, right is 4"), "{}", report);
+    assert!(report.contains(":4:3"), "{}", report);
+    assert!(report.contains("assert_eq(sum, 4);\n  │   ^^^^^^^^^^^^^^^^^"), "{}", report);
+}
+
+/// A crash is reported with the Gold functions that were running. The
+/// interpreter exits the same way, without them.
+#[test]
+fn crashes() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let file = std::env::temp_dir().join(format!("gold-crashes-{}.gold", std::process::id()));
+    let source = "// divide is a function.\n// Params:\n// 'a' is of type Int.\n// 'b' is of type Int.\n// Returns: Int\nfn {\n  a / b\n}\n\n\
+        // main is a function.\n// Params:\n// Returns: Int\nfn {\n  divide(1, 0)\n}\n";
+    fs::write(&file, source).unwrap();
+
+    let output = Command::new(gold).arg(&file).output().unwrap();
+    let interpreted = Command::new(gold).arg(&file).arg("--interp").output().unwrap();
+    fs::remove_file(&file).unwrap();
+    let stderr = String::from_utf8_lossy(&interpreted.stderr);
+    assert_eq!(interpreted.status.code(), Some(CRASH_EXIT_CODE), "{}", stderr);
+    assert!(stderr.contains("division by zero"), "{}", stderr);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(CRASH_EXIT_CODE), "{}", stderr);
+    assert!(stderr.contains("division by zero"), "{}", stderr);
+    assert!(stderr.contains("in `divide`"), "{}", stderr);
+    assert!(stderr.contains("`main` called `divide` here"), "{}", stderr);
+    assert!(stderr.contains("0: divide at "), "{}", stderr);
+    assert!(stderr.contains(":7:3\n"), "{}", stderr);
+    assert!(stderr.contains(":14:3\n"), "{}", stderr);
+}
+
+/// Values that refer to each other are never freed, which `--leak-check`
+/// reports.
+#[test]
+fn leaks() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let file = std::env::temp_dir().join(format!("gold-leaks-{}.gold", std::process::id()));
+    let source = "// Holder is a structure.\n// Fields:\n// 'item' is of type Maybe.\n\n\
+        // Maybe is one of:\n// 'Some' with 'holder' of type Holder.\n// 'Nothing'.\n\n\
+        // main is a function.\n// Params:\n// Returns: Void\nfn {\n  var h = Holder(Nothing);\n  h.item = Some(h);\n}\n";
+    fs::write(&file, source).unwrap();
+
+    let output = Command::new(gold).arg(&file).output().unwrap();
+    assert!(output.status.success());
+    let output = Command::new(gold).arg(&file).arg("--leak-check").output().unwrap();
+    fs::remove_file(&file).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(LEAK_EXIT_CODE), "{}", stderr);
+    assert!(stderr.contains("2 values were never freed, taking up 24 bytes"), "{}", stderr);
+}
+
+/// Both backends print what the entry point gives back the same way, and
+/// refuse to run one they can't call.
+#[test]
+fn entry_points() {
+    let gold = Path::new(env!("CARGO_BIN_EXE_gold"));
+    let file = std::env::temp_dir().join(format!("gold-entry-points-{}.gold", std::process::id()));
+    let run = |source: &str, interp: bool| {
+        fs::write(&file, source).unwrap();
+        let mut command = Command::new(gold);
+        command.arg(&file);
+        if interp {
+            command.arg("--interp");
+        }
+        let output = command.output().unwrap();
+        (output.status.code(), String::from_utf8_lossy(&output.stdout).into_owned(), String::from_utf8_lossy(&output.stderr).into_owned())
+    };
+    let header = |params: &str, returns: &str| format!("// main is a function.\n// Params:\n{}// Returns: {}\nfn {{\n", params, returns);
+    let half = header("", "Float") + "  to_float(3) / to_float(2)\n}\n";
+    let less = header("// 'a' is of type Int.\n// 'b' is of type Int.\n", "Bool") + "  a < b\n}\n";
+    let named = header("// 'name' is of type String.\n", "Void") + "  println(name);\n}\n";
+    let text = header("", "String") + "  \"text\"\n}\n";
+
+    for interp in &[false, true] {
+        assert_eq!(run(&half, *interp), (Some(0), "1.5\n".to_owned(), String::new()));
+        assert_eq!(run(&less, *interp), (Some(0), "1\n".to_owned(), String::new()));
+        let (code, stdout, stderr) = run(&named, *interp);
+        assert_eq!((code, stdout.as_str()), (Some(1), ""));
+        assert!(stderr.contains("'main' can't be run: it takes (String)"), "{}", stderr);
+        let (code, _, stderr) = run(&text, *interp);
+        assert_eq!(code, Some(1));
+        assert!(stderr.contains("'main' can't be run: it returns String"), "{}", stderr);
+    }
+    fs::remove_file(&file).unwrap();
+}
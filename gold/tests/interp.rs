@@ -0,0 +1,139 @@
+use gold::Engine;
+use gold_backend::interp::{Interpreter, Value};
+use gold_frontend::module;
+
+const SCRIPT: &str = "
+// Shape is one of:
+// 'Circle' with 'radius' of type Int.
+// 'Square' with 'side' of type Int.
+// 'Nothing'.
+
+// sum_to is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var i = 0;
+  var total = 0;
+  while i < n {
+    i = i + 1;
+    total = total + i ^ 2;
+  }
+  total
+}
+
+// classify is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var label = \"{n}\";
+  if label is \"0\" {
+    0
+  } elif n < 0 {
+    -1 * (0 - n) / 3
+  } else {
+    n / 3
+  }
+}
+
+// area is a function.
+// Params:
+// 'size' is of type Int.
+// Returns: Int
+fn {
+  var shape = Nothing;
+  if size > 10 {
+    shape = Circle(size);
+  } elif size > 0 {
+    shape = Square(size);
+  }
+  match shape {
+    Circle(r) { 3 * r * r }
+    Square(s) { s * s }
+    else { 0 }
+  }
+}
+
+// measure is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var f = to_float(n) / to_float(2);
+  var root = to_int(sqrt(to_float(abs(n)) * to_float(3)));
+  root + clamp(n, -3, 3) * gcd(n, 12) + max(n, 2) - min(n, 2) + to_int(round(f) + floor(f) + abs(f) ^ to_float(2))
+}
+
+// tally is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var seen = Map(Int, Int);
+  var items = [n, n * 2, n, 3];
+  var i = 0;
+  while i < len(items) {
+    var item = get(items, i);
+    if contains(seen, item) {
+      insert(seen, item, get(seen, item) + 1);
+    } else {
+      insert(seen, item, 1);
+    }
+    i = i + 1;
+  }
+  len(seen) * 10 + get(seen, n) + get(keys(seen), 0)
+}
+
+// skip is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  var total = 0;
+  for i in 0..=n {
+    if i is 3 {
+      continue;
+    }
+    if i > 8 {
+      break;
+    }
+    total = total + i;
+  }
+  for c in \"ab\" {
+    total = total * 2;
+  }
+  for item in [n, 1] {
+    total = total + item;
+  }
+  total
+}
+
+// unset is a function.
+// Params:
+// 'n' is of type Int.
+// Returns: Int
+fn {
+  if n > 5 {
+    var big = n * 2;
+  }
+  big + 1
+}
+";
+
+/// Runs every function through both backends, which have to agree.
+#[test]
+fn agrees_with_the_jit() {
+    let mut engine = Engine::new();
+    engine.compile(SCRIPT).unwrap();
+    let modules = gold::lower(module::load("script.gold", SCRIPT.to_owned()), false, |_, _, _| panic!("the script has errors")).unwrap();
+    let interpreter = Interpreter::new(&modules);
+
+    for name in &["sum_to", "classify", "area", "measure", "tally", "skip", "unset"] {
+        let compiled = engine.get::<extern "C" fn(i64) -> i64>(name).unwrap();
+        for n in &[-7, -1, 0, 1, 2, 5, 11, 20] {
+            let interpreted = interpreter.call(name, vec![Value::Int(*n)]).unwrap();
+            assert_eq!(interpreted, Value::Int(compiled(*n)), "{}({})", name, n);
+        }
+    }
+}